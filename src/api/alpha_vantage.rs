@@ -1,5 +1,6 @@
 //! Alpha Vantage price source.
 
+use crate::api::PriceSource;
 use crate::config::read_api_keys;
 use crate::types::PriceResponse;
 
@@ -46,6 +47,20 @@ pub async fn get_price_from_alpha_vantage(symbol: &str) -> Result<f64, String> {
     Ok(response.price)
 }
 
+/// [`PriceSource`] for Alpha Vantage `GLOBAL_QUOTE` (quotes only; needs
+/// `alpha_vantage_api_key` in the API key file).
+pub struct AlphaVantage;
+
+impl PriceSource for AlphaVantage {
+    fn name(&self) -> &'static str {
+        "alphavantage"
+    }
+
+    async fn quote(&self, symbol: &str, _category: &str) -> Result<f64, String> {
+        get_price_from_alpha_vantage(symbol).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,6 +3,8 @@
 use reqwest::Client;
 use serde::Deserialize;
 
+use crate::api::{PriceSource, unsupported};
+
 #[derive(Deserialize, Debug)]
 struct BinancePrice {
     price: String,
@@ -44,6 +46,22 @@ pub async fn get_price_from_binance(symbol: &str) -> Result<f64, String> {
     }
 }
 
+/// [`PriceSource`] for Binance spot (crypto only, quoted in USDT; quotes only).
+pub struct Binance;
+
+impl PriceSource for Binance {
+    fn name(&self) -> &'static str {
+        "binance"
+    }
+
+    async fn quote(&self, symbol: &str, category: &str) -> Result<f64, String> {
+        match category {
+            "Crypto" => get_price_from_binance(symbol).await,
+            _ => unsupported("Binance", category),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3,6 +3,7 @@
 use reqwest::Client;
 use serde::Deserialize;
 
+use crate::api::{PriceSource, unsupported};
 use crate::config::read_api_keys;
 
 #[derive(Deserialize, Debug)]
@@ -60,6 +61,23 @@ pub async fn get_rate(from: &str, to: &str) -> Result<f64, String> {
         .ok_or_else(|| format!("[ExchangeRate] Cannot find rate for {}", to))
 }
 
+/// [`PriceSource`] for ExchangeRate-API (forex only; quotes only; needs
+/// `exchangerate_api_key` in the API key file).
+pub struct ExchangeRate;
+
+impl PriceSource for ExchangeRate {
+    fn name(&self) -> &'static str {
+        "exchangerate"
+    }
+
+    async fn quote(&self, symbol: &str, category: &str) -> Result<f64, String> {
+        match category {
+            "Forex" => get_rate("USD", symbol).await,
+            _ => unsupported("ExchangeRate", category),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
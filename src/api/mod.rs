@@ -1,4 +1,11 @@
 //! Price and history data providers, one submodule per upstream API.
+//!
+//! Each provider exposes a unit struct implementing [`PriceSource`], which maps
+//! a portfolio `(symbol, category)` onto the provider's own naming. The
+//! per-category order in which they are tried is configured in
+//! [`crate::sources`].
+
+use std::future::Future;
 
 pub mod alpha_vantage;
 pub mod binance;
@@ -7,3 +14,40 @@ pub mod pyth;
 pub mod redstone;
 pub mod twse;
 pub mod yahoo;
+
+/// A price provider that can be tried as one link of a fallback chain.
+///
+/// `symbol` and `category` are as written in the portfolio. For `Forex`,
+/// `symbol` is the currency code and a quote is the `USD/{ccy}` rate (units of
+/// the currency per USD), matching the price-map key.
+pub trait PriceSource {
+    /// The provider's name in `sources.toml` (e.g. `yahoo`).
+    fn name(&self) -> &'static str;
+
+    /// The latest price, or an `Err` string if the provider has none (including
+    /// for categories it does not cover).
+    fn quote(
+        &self,
+        symbol: &str,
+        category: &str,
+    ) -> impl Future<Output = Result<f64, String>> + Send;
+
+    /// Daily `(timestamp, close)` pairs between `from` and `to` (epoch
+    /// seconds). Providers without a history endpoint keep this default.
+    fn history(
+        &self,
+        symbol: &str,
+        category: &str,
+        from: i64,
+        to: i64,
+    ) -> impl Future<Output = Result<Vec<(i64, f64)>, String>> + Send {
+        let _ = (symbol, category, from, to);
+        let name = self.name();
+        async move { Err(format!("[{}] history is not supported", name)) }
+    }
+}
+
+/// The `Err` a provider returns for a category it does not cover.
+pub(crate) fn unsupported(source: &str, category: &str) -> Result<f64, String> {
+    Err(format!("[{}] {} is not supported", source, category))
+}
@@ -7,10 +7,14 @@ use launchdarkly_sdk_transport::HyperTransport;
 
 use futures::StreamExt;
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, watch};
+
+use crate::api::PriceSource;
+use crate::paths;
+use crate::types::{PriceEntry, PriceMap};
 
 /// The Pyth feed-id table, compiled into the binary so no external file is
 /// needed at runtime and the TOML is parsed exactly once (not per lookup).
@@ -18,7 +22,28 @@ static PYTH_FEEDS: Lazy<toml::Value> = Lazy::new(|| {
     toml::from_str(include_str!("data/pyth.toml")).expect("bundled pyth.toml must be valid TOML")
 });
 
-const BASE_URL: &str = "https://hermes.pyth.network";
+/// The user's `pyth_overrides.toml` (same layout as the bundled table), read
+/// once; a missing or invalid file means no overrides.
+static FEED_OVERRIDES: Lazy<toml::Value> = Lazy::new(|| {
+    let path = paths::pyth_overrides_file();
+    read_feed_overrides(&path).unwrap_or_else(|e| {
+        crate::log_line!("[pyth] ignoring {}: {}", path, e);
+        toml::Value::Table(Default::default())
+    })
+});
+
+/// The catalog cached by `price feeds sync` (Pyth symbol -> feed id), read
+/// once; a missing or invalid file means no catalog.
+static SYNCED_FEEDS: Lazy<HashMap<String, String>> = Lazy::new(|| {
+    let path = paths::pyth_feeds_file();
+    read_feed_catalog(&path).unwrap_or_else(|e| {
+        crate::log_line!("[pyth] ignoring {}: {}", path, e);
+        HashMap::new()
+    })
+});
+
+/// The production Hermes endpoint (also the default for `price feeds sync`).
+pub const HERMES_URL: &str = "https://hermes.pyth.network";
 const BENCHMARKS_URL: &str = "https://benchmarks.pyth.network";
 
 /// Map a portfolio `(symbol, category)` to a Pyth Benchmarks TradingView symbol.
@@ -30,10 +55,17 @@ pub fn pyth_tv_symbol(symbol: &str, category: &str) -> Option<String> {
         "US-Stock" | "US-ETF" => Some(format!("Equity.US.{}/USD", sym)),
         // For forex we always price against USD, e.g. USD/TWD.
         "Forex" => Some(format!("FX.USD/{}", sym)),
+        // Precious and industrial metals are spot `Metal.X/USD` feeds; energy
+        // (oil and its futures) lives under `Commodities.`.
+        "Commodity" if METALS.contains(&sym.as_str()) => Some(format!("Metal.{}/USD", sym)),
+        "Commodity" => Some(format!("Commodities.{}", sym)),
         _ => None,
     }
 }
 
+/// Commodity symbols Pyth lists as metals (quoted per troy ounce or per unit).
+const METALS: &[&str] = &["XAU", "XAG", "XPT", "XPD", "XCU", "XAL", "XNI"];
+
 /// Fetch historical daily close prices from the Pyth Benchmarks TradingView shim.
 ///
 /// `tv_symbol` is a Pyth TradingView symbol (e.g. `Equity.US.AAPL/USD`).
@@ -97,70 +129,156 @@ pub async fn get_history_from_pyth(
     }
 }
 
+/// One decoded Hermes price update.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PythQuote {
+    pub price: f64,
+    /// Confidence interval around `price` (±, same units).
+    pub conf: f64,
+    /// Unix epoch seconds at which the publishers produced this price.
+    pub publish_time: i64,
+    /// Exponentially-weighted moving average price, if the update carried one.
+    pub ema_price: Option<f64>,
+    /// Confidence interval around `ema_price`.
+    pub ema_conf: Option<f64>,
+}
+
 /// A collection of `(symbol, price)` entries that a price stream can write into.
 ///
-/// Implemented for both `Vec<(String, f64)>` and `HashMap<String, f64>` so the
-/// streaming code can update either an ordered list or a keyed map.
+/// Implemented for `Vec<(String, f64)>`, `HashMap<String, f64>` (price only)
+/// and the live [`PriceMap`] (which also keeps the confidence and publish time,
+/// and stamps each update with the time and `pyth` as source) so the streaming
+/// code can update an ordered list or a keyed map.
 pub trait PriceContainer {
-    /// Set `symbol` to `price`, replacing any existing entry for that symbol.
-    fn update(&mut self, symbol: String, price: f64);
+    /// Set `symbol` to `quote`, replacing any existing entry for that symbol.
+    fn update(&mut self, symbol: String, quote: &PythQuote);
 }
 
 impl PriceContainer for Vec<(String, f64)> {
-    fn update(&mut self, symbol: String, price: f64) {
+    fn update(&mut self, symbol: String, quote: &PythQuote) {
         if let Some(entry) = self.iter_mut().find(|(s, _)| *s == symbol) {
-            entry.1 = price;
+            entry.1 = quote.price;
         } else {
-            self.push((symbol, price));
+            self.push((symbol, quote.price));
         }
     }
 }
 
 impl PriceContainer for HashMap<String, f64> {
-    fn update(&mut self, symbol: String, price: f64) {
-        self.insert(symbol, price);
+    fn update(&mut self, symbol: String, quote: &PythQuote) {
+        self.insert(symbol, quote.price);
+    }
+}
+
+impl PriceContainer for PriceMap {
+    fn update(&mut self, symbol: String, quote: &PythQuote) {
+        self.0.insert(
+            symbol,
+            PriceEntry {
+                price: quote.price,
+                updated_at: chrono::Utc::now().timestamp(),
+                source: "pyth".to_string(),
+                conf: Some(quote.conf),
+                publish_time: Some(quote.publish_time),
+            },
+        );
+    }
+}
+
+/// Decode one `{price, conf, expo, publish_time}` object: `price` and `conf`
+/// are integer strings scaled by `10^expo`. Returns `(price, conf,
+/// publish_time)`.
+fn parse_scaled_price(price_obj: &serde_json::Value) -> Option<(f64, f64, i64)> {
+    let expo = price_obj.get("expo")?.as_i64()?;
+    let scale = 10f64.powi(expo as i32);
+    let scaled = |field: &str| -> Option<f64> {
+        Some(price_obj.get(field)?.as_str()?.parse::<f64>().ok()? * scale)
+    };
+    let publish_time = price_obj.get("publish_time")?.as_i64()?;
+    Some((scaled("price")?, scaled("conf")?, publish_time))
+}
+
+/// Decode one entry of a Hermes `parsed` array into a [`PythQuote`]; the EMA
+/// is optional.
+fn parse_price_update(entry: &serde_json::Value) -> Option<PythQuote> {
+    let (price, conf, publish_time) = parse_scaled_price(entry.get("price")?)?;
+    let ema = entry.get("ema_price").and_then(parse_scaled_price);
+    Some(PythQuote {
+        price,
+        conf,
+        publish_time,
+        ema_price: ema.map(|(p, _, _)| p),
+        ema_conf: ema.map(|(_, c, _)| c),
+    })
+}
+
+/// Fetch the most recent price for feed `id` with a single Hermes REST call,
+/// for one-shot lookups that don't warrant a stream.
+pub async fn get_latest_price_from_pyth(id: &str) -> Result<f64, String> {
+    let url = format!(
+        "{}/v2/updates/price/latest?ids[]={}&parsed=true",
+        HERMES_URL, id
+    );
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("[Pyth] Failed to query latest price for {}: {}", id, e))?;
+
+    if !response.status().is_success() {
+        return Err(format!("[Pyth] HTTP error: {}", response.status()));
     }
+
+    let json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("[Pyth] JSON format error: {}", e))?;
+
+    json.get("parsed")
+        .and_then(|v| v.as_array())
+        .and_then(|entries| entries.first())
+        .and_then(parse_price_update)
+        .map(|quote| quote.price)
+        .ok_or_else(|| format!("[Pyth] No price in latest update for {}", id))
 }
 
-/// 訂閱 Pyth 即時價格串流，並將價格回傳給 callback 函數。
+/// 訂閱 Pyth 即時價格串流（可同時訂閱多個 feed），並將價格回傳給 callback 函數。
 ///
 /// # 參數
-/// - `id`: Pyth price feed 的 ID（hex 字串）
-/// - `on_price`: 回呼函數，接收實際價格（`f64`）
+/// - `ids`: Pyth price feed 的 ID 列表（hex 字串）
+/// - `on_price`: 回呼函數，接收 feed ID（小寫、無 `0x`）與完整報價（[`PythQuote`]）
 ///
 /// # 範例
-/// pyth_stream::subscribe_price_stream("0xe62d...", |price| println!("價格: {}", price)).await;
+/// get_price_stream_from_pyth(&ids, |id, quote| println!("{}: {}", id, quote.price)).await;
 pub async fn get_price_stream_from_pyth<F>(
-    id: &str,
+    ids: &[String],
     mut on_price: F,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
-    F: FnMut(f64) + Send + 'static,
+    F: FnMut(&str, &PythQuote) + Send + 'static,
 {
-    let url = format!("{}/v2/updates/price/stream?ids[]={}", BASE_URL, id);
-
     let transport = HyperTransport::new_https()?;
-    let mut stream = ClientBuilder::for_url(&url)?
+    let mut stream = ClientBuilder::for_url(&stream_url(ids))?
         .build_with_transport(transport)
         .stream();
 
     while let Some(event) = stream.next().await {
         match event {
             Ok(SSE::Event(ev)) => {
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&ev.data) {
-                    if let Some(parsed_array) = json.get("parsed").and_then(|v| v.as_array()) {
-                        for entry in parsed_array {
-                            if let Some(price_obj) = entry.get("price") {
-                                if let (Some(price_str), Some(expo)) = (
-                                    price_obj.get("price").and_then(|p| p.as_str()),
-                                    price_obj.get("expo").and_then(|e| e.as_i64()),
-                                ) {
-                                    if let Ok(price_int) = price_str.parse::<f64>() {
-                                        let actual_price = price_int * 10f64.powi(expo as i32);
-                                        on_price(actual_price);
-                                    }
-                                }
-                            }
+                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&ev.data)
+                    && let Some(parsed_array) = json.get("parsed").and_then(|v| v.as_array())
+                {
+                    for entry in parsed_array {
+                        if let Some(id) = entry.get("id").and_then(|v| v.as_str())
+                            && let Some(quote) = parse_price_update(entry)
+                        {
+                            on_price(&normalize_feed_id(id), &quote);
                         }
                     }
                 }
@@ -175,15 +293,53 @@ where
     Ok(())
 }
 
-/// Look up the Pyth price-feed id for `(symbol, category)` in the bundled feed
-/// table.
+/// The Hermes streaming URL for every feed in `ids`.
+fn stream_url(ids: &[String]) -> String {
+    let query: Vec<String> = ids.iter().map(|id| format!("ids[]={}", id)).collect();
+    format!("{}/v2/updates/price/stream?{}", HERMES_URL, query.join("&"))
+}
+
+/// A feed id in the form Hermes reports it in updates: lower-case hex without
+/// the `0x` prefix.
+fn normalize_feed_id(id: &str) -> String {
+    id.trim_start_matches("0x").to_ascii_lowercase()
+}
+
+/// Look up the Pyth price-feed id for `(symbol, category)`: in the user's
+/// `pyth_overrides.toml` first, then the catalog cached by `price feeds sync`,
+/// then the bundled feed table.
 ///
-/// `symbol` is upper-cased before lookup. Returns an `Err` string if the
-/// category is absent from the table, the symbol is absent within it, or the
-/// stored id is not a string.
+/// `symbol` is upper-cased before lookup. Returns an `Err` string if no table
+/// has the symbol, or the bundled entry is not a string.
 pub fn get_pyth_feed_id(symbol: &str, category: &str) -> Result<String, String> {
+    lookup_feed_id(
+        &FEED_OVERRIDES,
+        &SYNCED_FEEDS,
+        &PYTH_FEEDS,
+        symbol,
+        category,
+    )
+}
+
+fn lookup_feed_id(
+    overrides: &toml::Value,
+    catalog: &HashMap<String, String>,
+    bundled: &toml::Value,
+    symbol: &str,
+    category: &str,
+) -> Result<String, String> {
     let target = symbol.to_uppercase();
-    let feeds = PYTH_FEEDS
+    if let Some(id) = overrides
+        .get(category)
+        .and_then(|feeds| feeds.get(&target))
+        .and_then(|id| id.as_str())
+    {
+        return Ok(id.to_string());
+    }
+    if let Some(id) = catalog_symbol(&target, category).and_then(|s| catalog.get(&s)) {
+        return Ok(id.clone());
+    }
+    let feeds = bundled
         .get(category)
         .ok_or_else(|| format!("No Pyth feeds for category {}", category))?;
     let feed_id = feeds
@@ -195,10 +351,329 @@ pub fn get_pyth_feed_id(symbol: &str, category: &str) -> Result<String, String>
     Ok(raw.to_string())
 }
 
+/// The Hermes catalog symbol for an upper-cased `(symbol, category)`, e.g.
+/// `Crypto.BTC/USD`; for `Forex` the symbol is already a pair (`USD/TWD`).
+fn catalog_symbol(symbol: &str, category: &str) -> Option<String> {
+    match category {
+        "Forex" => Some(format!("FX.{}", symbol)),
+        _ => pyth_tv_symbol(symbol, category),
+    }
+}
+
+/// Read a feed-override file laid out like the bundled table
+/// (`[category]` then `SYMBOL = "0x…"`). A missing file yields an empty table.
+fn read_feed_overrides(path: &str) -> Result<toml::Value, String> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => toml::from_str(&content).map_err(|e| format!("Failed to parse TOML: {}", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(toml::Value::Table(Default::default()))
+        }
+        Err(e) => Err(format!("Failed to read file: {}", e)),
+    }
+}
+
+/// Read a catalog written by [`sync_feed_catalog`]. A missing file yields an
+/// empty catalog.
+fn read_feed_catalog(path: &str) -> Result<HashMap<String, String>, String> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => {
+            serde_json::from_str(&content).map_err(|e| format!("Failed to parse JSON: {}", e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(format!("Failed to read file: {}", e)),
+    }
+}
+
+/// Map a Hermes `/v2/price_feeds` response to `Pyth symbol -> 0x feed id`,
+/// skipping entries without an id or symbol.
+fn parse_feed_catalog(json: &serde_json::Value) -> Result<BTreeMap<String, String>, String> {
+    let entries = json
+        .as_array()
+        .ok_or_else(|| "[Pyth] price_feeds response is not an array".to_string())?;
+    Ok(entries
+        .iter()
+        .filter_map(|entry| {
+            let id = entry.get("id")?.as_str()?;
+            let symbol = entry.get("attributes")?.get("symbol")?.as_str()?;
+            Some((symbol.to_string(), format!("0x{}", normalize_feed_id(id))))
+        })
+        .collect())
+}
+
+/// Download the full feed catalog from the Hermes instance at `base_url` (e.g.
+/// `https://hermes.pyth.network`) and write it to `path` as JSON. Returns the
+/// number of feeds written. Running programs pick it up on their next start.
+pub async fn sync_feed_catalog(base_url: &str, path: &str) -> Result<usize, String> {
+    let url = format!("{}/v2/price_feeds", base_url.trim_end_matches('/'));
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("[Pyth] Failed to query {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("[Pyth] HTTP error: {}", response.status()));
+    }
+    let json: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("[Pyth] JSON format error: {}", e))?;
+    let catalog = parse_feed_catalog(&json)?;
+    if catalog.is_empty() {
+        return Err("[Pyth] price_feeds response listed no feeds".to_string());
+    }
+
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create data dir: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&catalog)
+        .map_err(|e| format!("Failed to serialize catalog: {}", e))?;
+    // Write then rename so a failed sync never leaves a truncated catalog.
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, content).map_err(|e| format!("Failed to write {}: {}", tmp, e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(catalog.len())
+}
+
+/// Backoff bounds for stream reconnection.
+const RECONNECT_MIN_BACKOFF: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A session that stays connected at least this long is considered healthy, so
+/// the backoff is reset to its minimum after it drops.
+const RECONNECT_HEALTHY_SESSION: Duration = Duration::from_secs(30);
+
+/// How long to wait after the feed set changes before (re)connecting, so a
+/// burst of subscriptions (e.g. at startup or on a config reload) costs one
+/// connection rather than one per feed.
+const RESUBSCRIBE_SETTLE: Duration = Duration::from_millis(500);
+
+/// One multiplexed Hermes SSE connection for every live Pyth feed.
+///
+/// Holds the wanted `price key -> feed id` subscriptions. A background task
+/// streams all of their ids over a single connection and writes each update
+/// under every key that wants that feed. Changing the set reconnects once with
+/// the new ids; a dropped connection is retried for all feeds together with
+/// capped exponential backoff, since the SSE stream ends on any network blip
+/// and a long-running deployment must not silently keep stale prices.
+#[derive(Clone)]
+pub struct PythStreams {
+    feeds: watch::Sender<BTreeMap<String, String>>,
+}
+
+impl PythStreams {
+    /// Start the connection task writing into `prices`. It stays idle until
+    /// the first [`subscribe`](Self::subscribe).
+    pub fn spawn<C>(prices: Arc<Mutex<C>>) -> Self
+    where
+        C: PriceContainer + Send + 'static,
+    {
+        let (feeds, rx) = watch::channel(BTreeMap::new());
+        tokio::spawn(run_multiplexed_stream(rx, prices));
+        PythStreams { feeds }
+    }
+
+    /// Stream feed `id` into the price map under `key`. Returns `false` if
+    /// `key` was already subscribed to that feed (nothing changes).
+    pub fn subscribe(&self, key: &str, id: &str) -> bool {
+        let id = normalize_feed_id(id);
+        self.feeds.send_if_modified(|feeds| {
+            feeds.insert(key.to_string(), id.clone()).as_ref() != Some(&id)
+        })
+    }
+
+    /// Stop streaming into `key`. Returns `false` if it was not subscribed.
+    pub fn unsubscribe(&self, key: &str) -> bool {
+        self.feeds
+            .send_if_modified(|feeds| feeds.remove(key).is_some())
+    }
+
+    /// The price keys currently subscribed, in order.
+    pub fn keys(&self) -> Vec<String> {
+        self.feeds.borrow().keys().cloned().collect()
+    }
+}
+
+/// Group `key -> feed id` subscriptions by feed: the ids to request, and the
+/// keys each streamed id updates.
+fn feed_routes(feeds: &BTreeMap<String, String>) -> (Vec<String>, HashMap<String, Vec<String>>) {
+    let mut routes: HashMap<String, Vec<String>> = HashMap::new();
+    for (key, id) in feeds {
+        routes.entry(id.clone()).or_default().push(key.clone());
+    }
+    let mut ids: Vec<String> = routes.keys().cloned().collect();
+    ids.sort();
+    (ids, routes)
+}
+
+/// Write `quote` from feed `id` under each of `keys` still subscribed to it in
+/// `feeds`. The check runs under the price lock: a key unsubscribed and then
+/// removed from the map while this write was in flight stays removed.
+async fn write_update<C: PriceContainer>(
+    prices: &Mutex<C>,
+    feeds: &watch::Receiver<BTreeMap<String, String>>,
+    id: &str,
+    keys: &[String],
+    quote: &PythQuote,
+) {
+    let mut prices = prices.lock().await;
+    let feeds = feeds.borrow();
+    for key in keys {
+        if feeds.get(key).is_some_and(|wanted| wanted == id) {
+            prices.update(key.clone(), quote);
+        }
+    }
+}
+
+/// The connection task behind [`PythStreams`]: returns once every handle has
+/// been dropped.
+async fn run_multiplexed_stream<C>(
+    mut feeds: watch::Receiver<BTreeMap<String, String>>,
+    prices: Arc<Mutex<C>>,
+) where
+    C: PriceContainer + Send + 'static,
+{
+    let mut backoff = RECONNECT_MIN_BACKOFF;
+
+    loop {
+        if feeds.changed().await.is_err() {
+            return;
+        }
+        // Let a burst of subscription changes settle before connecting.
+        tokio::time::sleep(RESUBSCRIBE_SETTLE).await;
+
+        loop {
+            let (ids, routes) = feed_routes(&feeds.borrow_and_update());
+            if ids.is_empty() {
+                crate::log_line!("[pyth] no feeds subscribed; stream idle");
+                break;
+            }
+            crate::log_line!("[pyth] streaming {} feeds", ids.len());
+            let routes = Arc::new(routes);
+            let started = Instant::now();
+
+            // Map the non-`Send` `Box<dyn Error>` to a `String` inside the
+            // select so it never lives across an `.await`; otherwise the task
+            // future would not be `Send` and could not be spawned.
+            let outcome = {
+                let prices = Arc::clone(&prices);
+                let routes = Arc::clone(&routes);
+                let wanted = feeds.clone();
+                let session = get_price_stream_from_pyth(&ids, move |id, quote| {
+                    if let Some(keys) = routes.get(id) {
+                        let prices = Arc::clone(&prices);
+                        let wanted = wanted.clone();
+                        let id = id.to_string();
+                        let keys = keys.clone();
+                        let quote = quote.clone();
+                        tokio::spawn(async move {
+                            write_update(&prices, &wanted, &id, &keys, &quote).await;
+                        });
+                    }
+                });
+                tokio::select! {
+                    result = session => Some(result.map_err(|e| e.to_string())),
+                    changed = feeds.changed() => {
+                        if changed.is_err() {
+                            return;
+                        }
+                        None
+                    }
+                }
+            };
+
+            match outcome {
+                // The feed set changed: reconnect with the new ids (after the
+                // settle delay) without counting it as a failure.
+                None => {
+                    tokio::time::sleep(RESUBSCRIBE_SETTLE).await;
+                    continue;
+                }
+                Some(Ok(())) => crate::log_line!("[pyth] stream ended; reconnecting"),
+                Some(Err(e)) => crate::log_line!("[pyth] stream failed: {}; reconnecting", e),
+            }
+            for key in routes.values().flatten() {
+                crate::metrics::record_stream_reconnect(key);
+            }
+
+            // A long-lived session indicates the feeds are healthy, so don't
+            // penalise the reconnect with an inflated backoff.
+            if started.elapsed() >= RECONNECT_HEALTHY_SESSION {
+                backoff = RECONNECT_MIN_BACKOFF;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                changed = feeds.changed() => {
+                    if changed.is_err() {
+                        return;
+                    }
+                }
+            }
+            backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+        }
+    }
+}
+
+/// [`PriceSource`] for Pyth: Hermes latest price for quotes, Benchmarks for
+/// history (crypto, US equities, forex and commodities).
+pub struct Pyth;
+
+impl PriceSource for Pyth {
+    fn name(&self) -> &'static str {
+        "pyth"
+    }
+
+    async fn quote(&self, symbol: &str, category: &str) -> Result<f64, String> {
+        // Forex feeds are keyed by pair, e.g. USD/TWD.
+        let feed_symbol = match category {
+            "Forex" => format!("USD/{}", symbol.to_uppercase()),
+            _ => symbol.to_string(),
+        };
+        let id = get_pyth_feed_id(&feed_symbol, category)?;
+        get_latest_price_from_pyth(&id).await
+    }
+
+    async fn history(
+        &self,
+        symbol: &str,
+        category: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<(i64, f64)>, String> {
+        let tv_symbol = pyth_tv_symbol(symbol, category)
+            .ok_or_else(|| format!("No Pyth symbol mapping for {} ({})", symbol, category))?;
+        get_history_from_pyth(&tv_symbol, from, to).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_price_update() {
+        let entry = serde_json::json!({
+            "id": "e62d",
+            "price": { "price": "6512345", "conf": "1200", "expo": -5, "publish_time": 1 },
+            "ema_price": { "price": "6500000", "conf": "1000", "expo": -5, "publish_time": 1 }
+        });
+        let quote = parse_price_update(&entry).unwrap();
+        assert!((quote.price - 65.12345).abs() < 1e-9);
+        assert!((quote.conf - 0.012).abs() < 1e-9);
+        assert_eq!(quote.publish_time, 1);
+        assert!((quote.ema_price.unwrap() - 65.0).abs() < 1e-9);
+        assert!((quote.ema_conf.unwrap() - 0.01).abs() < 1e-9);
+        assert!(parse_price_update(&serde_json::json!({ "price": {} })).is_none());
+
+        // The EMA is optional.
+        let entry = serde_json::json!({
+            "price": { "price": "100", "conf": "1", "expo": 0, "publish_time": 5 }
+        });
+        assert_eq!(parse_price_update(&entry).unwrap().ema_price, None);
+    }
+
     #[test]
     fn test_pyth_tv_symbol() {
         assert_eq!(pyth_tv_symbol("eth", "Crypto").unwrap(), "Crypto.ETH/USD");
@@ -211,6 +686,11 @@ mod tests {
             "Equity.US.QQQ/USD"
         );
         assert_eq!(pyth_tv_symbol("TWD", "Forex").unwrap(), "FX.USD/TWD");
+        assert_eq!(pyth_tv_symbol("xau", "Commodity").unwrap(), "Metal.XAU/USD");
+        assert_eq!(
+            pyth_tv_symbol("WTI1M", "Commodity").unwrap(),
+            "Commodities.WTI1M"
+        );
         assert!(pyth_tv_symbol("2330", "TW-Stock").is_none());
     }
 
@@ -229,83 +709,115 @@ mod tests {
         // Timestamps should be strictly increasing.
         assert!(series.windows(2).all(|w| w[0].0 < w[1].0));
     }
-}
-
-/// Backoff bounds for stream reconnection.
-const RECONNECT_MIN_BACKOFF: Duration = Duration::from_secs(1);
-const RECONNECT_MAX_BACKOFF: Duration = Duration::from_secs(60);
-/// A session that stays connected at least this long is considered healthy, so
-/// the backoff is reset to its minimum after it drops.
-const RECONNECT_HEALTHY_SESSION: Duration = Duration::from_secs(30);
-
-/// Stream a Pyth feed into `prices` under `key`, reconnecting indefinitely with
-/// capped exponential backoff whenever the SSE connection ends or errors.
-///
-/// The underlying SSE stream terminates on any network blip; without this loop a
-/// long-running deployment would silently lose feeds one by one and keep showing
-/// stale prices. The backoff resets after a healthy session so transient drops
-/// recover quickly while a persistently failing feed is not hammered.
-pub async fn stream_into_map<C>(id: String, key: String, prices: Arc<Mutex<C>>)
-where
-    C: PriceContainer + Send + 'static,
-{
-    let mut backoff = RECONNECT_MIN_BACKOFF;
 
-    loop {
-        let started = Instant::now();
-
-        // Confine the non-`Send` `Box<dyn Error>` returned by the stream to this
-        // block so it is dropped before the `.await` points below; otherwise the
-        // surrounding task future would not be `Send` and could not be spawned.
-        {
-            let prices_for_cb = Arc::clone(&prices);
-            let key_for_cb = key.clone();
-            let result = get_price_stream_from_pyth(&id, move |price| {
-                let prices = Arc::clone(&prices_for_cb);
-                let key = key_for_cb.clone();
-                tokio::spawn(async move {
-                    prices.lock().await.update(key, price);
-                });
-            })
-            .await;
+    #[test]
+    fn test_feed_routes_groups_keys_by_id() {
+        let feeds = BTreeMap::from([
+            ("ETH".to_string(), "aa".to_string()),
+            ("USD/TWD".to_string(), "bb".to_string()),
+            ("WETH".to_string(), "aa".to_string()),
+        ]);
+        let (ids, routes) = feed_routes(&feeds);
+        assert_eq!(ids, vec!["aa", "bb"]);
+        assert_eq!(routes["aa"], vec!["ETH", "WETH"]);
+        assert_eq!(
+            stream_url(&ids),
+            format!("{}/v2/updates/price/stream?ids[]=aa&ids[]=bb", HERMES_URL)
+        );
+    }
 
-            match result {
-                Ok(()) => crate::log_line!("[pyth] stream for {} ended; reconnecting", key),
-                Err(e) => crate::log_line!("[pyth] stream for {} failed: {}; reconnecting", key, e),
-            }
-        }
+    #[tokio::test]
+    async fn test_pyth_streams_subscribe_and_unsubscribe() {
+        let streams = PythStreams::spawn(Arc::new(Mutex::new(PriceMap::default())));
+        assert!(streams.subscribe("ETH", "0xAA"));
+        assert!(!streams.subscribe("ETH", "aa"));
+        assert!(streams.subscribe("USD/TWD", "bb"));
+        assert_eq!(streams.keys(), vec!["ETH", "USD/TWD"]);
+        assert!(streams.unsubscribe("ETH"));
+        assert!(!streams.unsubscribe("ETH"));
+        assert_eq!(streams.keys(), vec!["USD/TWD"]);
+    }
 
-        // A long-lived session indicates the feed is healthy, so don't penalise
-        // the reconnect with an inflated backoff.
-        if started.elapsed() >= RECONNECT_HEALTHY_SESSION {
-            backoff = RECONNECT_MIN_BACKOFF;
-        }
+    #[tokio::test]
+    async fn test_updates_in_flight_skip_unsubscribed_keys() {
+        let prices = Mutex::new(HashMap::<String, f64>::new());
+        let (feeds, wanted) = watch::channel(BTreeMap::from([
+            ("ETH".to_string(), "aa".to_string()),
+            ("WETH".to_string(), "aa".to_string()),
+        ]));
+        let keys = vec!["ETH".to_string(), "WETH".to_string()];
+        let quote = PythQuote {
+            price: 2_000.0,
+            conf: 1.0,
+            publish_time: 1_700_000_000,
+            ema_price: None,
+            ema_conf: None,
+        };
+        write_update(&prices, &wanted, "aa", &keys, &quote).await;
+        assert_eq!(prices.lock().await.len(), 2);
+
+        // Evict WETH the way a reload does, then let an update routed before
+        // the eviction land.
+        feeds.send_modify(|feeds| {
+            feeds.remove("WETH");
+        });
+        prices.lock().await.remove("WETH");
+        write_update(&prices, &wanted, "aa", &keys, &quote).await;
+        let prices = prices.lock().await;
+        assert!(prices.contains_key("ETH"));
+        assert!(!prices.contains_key("WETH"));
+    }
 
-        tokio::time::sleep(backoff).await;
-        backoff = (backoff * 2).min(RECONNECT_MAX_BACKOFF);
+    #[test]
+    fn test_feed_lookup_order() {
+        let overrides: toml::Value = toml::from_str("[Crypto]\nETH = \"0xuser\"").unwrap();
+        let catalog = HashMap::from([
+            ("Crypto.ETH/USD".to_string(), "0xsynced-eth".to_string()),
+            ("Crypto.NEWCOIN/USD".to_string(), "0xsynced-new".to_string()),
+            ("FX.USD/TWD".to_string(), "0xsynced-twd".to_string()),
+        ]);
+        let bundled: toml::Value =
+            toml::from_str("[Crypto]\nETH = \"0xbundled\"\nBTC = \"0xbtc\"").unwrap();
+        let lookup =
+            |symbol, category| lookup_feed_id(&overrides, &catalog, &bundled, symbol, category);
+
+        assert_eq!(lookup("eth", "Crypto").unwrap(), "0xuser");
+        assert_eq!(lookup("newcoin", "Crypto").unwrap(), "0xsynced-new");
+        assert_eq!(lookup("USD/TWD", "Forex").unwrap(), "0xsynced-twd");
+        assert_eq!(lookup("btc", "Crypto").unwrap(), "0xbtc");
+        assert!(lookup("nope", "Crypto").is_err());
+        assert!(lookup("2330", "TW-Stock").is_err());
     }
-}
 
-/// Spawn a background task that streams live prices for `(symbol, category)`
-/// into `prices`, keyed by `symbol`.
-///
-/// Resolves the feed id via [`get_pyth_feed_id`]; if no feed exists for the
-/// holding the task logs and exits, otherwise it runs [`stream_into_map`]
-/// (reconnecting indefinitely). Returns immediately.
-pub fn spawn_price_stream<C>(symbol: &str, category: &str, prices: Arc<Mutex<C>>)
-where
-    C: PriceContainer + Send + 'static,
-{
-    let symbol = symbol.to_string();
-    let category = category.to_string();
-    tokio::spawn(async move {
-        let id = match get_pyth_feed_id(&symbol, &category) {
-            Ok(id) => id,
-            Err(e) => {
-                crate::log_line!("[pyth] no live feed for {} ({}): {}", symbol, category, e);
-                return;
-            }
-        };
-        stream_into_map(id, symbol, prices).await;
-    });
+    #[tokio::test]
+    async fn test_sync_feed_catalog_from_stand_in_server() {
+        use axum::{Json, Router, routing::get};
+
+        let body = serde_json::json!([
+            { "id": "E62DF6C8", "attributes": { "symbol": "Crypto.ETH/USD", "asset_type": "Crypto" } },
+            { "id": "49f6b65c", "attributes": { "symbol": "Equity.US.AAPL/USD" } },
+            { "id": "no-symbol", "attributes": {} }
+        ]);
+        let app = Router::new().route("/v2/price_feeds", get(move || async move { Json(body) }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let dir = std::env::temp_dir().join(format!("price-feeds-sync-{}", std::process::id()));
+        let path = dir.join("pyth_feeds.json").to_string_lossy().into_owned();
+        assert_eq!(sync_feed_catalog(&base_url, &path).await, Ok(2));
+
+        let catalog = read_feed_catalog(&path).unwrap();
+        assert_eq!(catalog["Crypto.ETH/USD"], "0xe62df6c8");
+        assert_eq!(catalog["Equity.US.AAPL/USD"], "0x49f6b65c");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Nothing listening: the error is reported and no file is written.
+        assert!(
+            sync_feed_catalog("http://127.0.0.1:1", &path)
+                .await
+                .is_err()
+        );
+        assert!(read_feed_catalog(&path).unwrap().is_empty());
+    }
 }
@@ -3,6 +3,8 @@
 use reqwest::Client;
 use serde::Deserialize;
 
+use crate::api::PriceSource;
+
 #[derive(Deserialize, Debug)]
 struct RedstonePrice {
     value: f64,
@@ -37,7 +39,7 @@ pub async fn get_price_from_redstone(symbol: &str) -> Result<f64, String> {
             .await
             .map_err(|e| format!("[RedStone] Returned JSON format error: {}", e))?;
 
-        if let Some(price_data) = data.get(0) {
+        if let Some(price_data) = data.first() {
             Ok(price_data.value)
         } else {
             Err(format!("[RedStone] No price data found for {}", symbol))
@@ -47,6 +49,19 @@ pub async fn get_price_from_redstone(symbol: &str) -> Result<f64, String> {
     }
 }
 
+/// [`PriceSource`] for RedStone (crypto and US equities; quotes only).
+pub struct RedStone;
+
+impl PriceSource for RedStone {
+    fn name(&self) -> &'static str {
+        "redstone"
+    }
+
+    async fn quote(&self, symbol: &str, _category: &str) -> Result<f64, String> {
+        get_price_from_redstone(symbol).await
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,8 +1,19 @@
-//! Taiwan Stock Exchange (TWSE) price source.
+//! Taiwan Stock Exchange (TWSE) price source. The MIS quote endpoint also
+//! serves Taipei Exchange (TPEx, OTC-listed) stocks under an `otc_` channel.
 
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate, TimeZone};
+use chrono_tz::Asia::Taipei;
 use reqwest::Client;
 use serde::Deserialize;
 
+use crate::api::{PriceSource, unsupported};
+
+const MIS_URL: &str = "https://mis.twse.com.tw/stock/api/getStockInfo.jsp";
+/// Channels per MIS request; longer lists are split into several requests.
+const MIS_BATCH_SIZE: usize = 50;
+
 #[derive(Deserialize, Debug)]
 struct TwseResponse {
     #[serde(rename = "msgArray")]
@@ -11,113 +22,397 @@ struct TwseResponse {
 
 #[derive(Deserialize, Debug)]
 struct TwseStock {
+    #[serde(default)]
+    c: String, // Stock code
     z: String, // Last traded price
     a: String, // Ask price (multiple prices separated by "_", take the first)
     b: String, // Bid price (multiple prices separated by "_", take the first)
     y: String, // Previous close
 }
 
-/// Fetch the current price of TWSE-listed stock `symbol` (e.g. `2330`).
-///
-/// Uses the last traded price when available; if it is `"-"`, falls back to the
-/// geometric mean of the best ask and bid, and if those cannot be parsed, to the
-/// previous close. Returns an `Err` string on request, HTTP, JSON, missing-data
-/// or parse failure.
-pub async fn get_price_from_twse(symbol: &str) -> Result<f64, String> {
-    let pair = format!("tse_{}.tw", symbol);
-    let url = format!(
-        "https://mis.twse.com.tw/stock/api/getStockInfo.jsp?ex_ch={}",
-        pair
-    );
+/// One symbol's quote from the MIS endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TwseQuote {
+    /// The last traded price; if none yet, the geometric mean of the best ask
+    /// and bid; failing that, the previous close.
+    pub price: f64,
+    /// The previous close, if it parses.
+    pub prev_close: Option<f64>,
+}
 
-    let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|e| e.to_string())?;
+/// The MIS `ex_ch` channel for `symbol`: `otc_{symbol}.tw` for TPEx-listed
+/// (`TW-OTC`) holdings, `tse_{symbol}.tw` for TWSE-listed ones.
+pub fn mis_channel(symbol: &str, category: &str) -> String {
+    match category {
+        "TW-OTC" => format!("otc_{}.tw", symbol),
+        _ => format!("tse_{}.tw", symbol),
+    }
+}
+
+impl TwseStock {
+    fn quote(&self) -> Result<TwseQuote, String> {
+        let prev_close = self.y.parse::<f64>().ok();
+        // Use last traded price if available
+        let price = if self.z != "-" {
+            self.z
+                .parse::<f64>()
+                .map_err(|_| "[TWSE] Failed to parse price as float".to_string())?
+        } else {
+            // Use geometric mean of ask and bid if last traded price is unavailable
+            let a1 = self.a.split('_').next().unwrap_or("-");
+            let b1 = self.b.split('_').next().unwrap_or("-");
+            match (a1.parse::<f64>(), b1.parse::<f64>()) {
+                (Ok(a), Ok(b)) => (a * b).sqrt(),
+                // Use previous close if ask and bid cannot be parsed
+                _ => prev_close
+                    .ok_or("[TWSE] Failed to parse previous close as float".to_string())?,
+            }
+        };
+        Ok(TwseQuote { price, prev_close })
+    }
+}
+
+/// Decode an MIS response body into `code -> quote`.
+fn parse_quotes(text: &str) -> Result<HashMap<String, Result<TwseQuote, String>>, String> {
+    let data: TwseResponse = serde_json::from_str(text)
+        .map_err(|e| format!("[TWSE] Returned JSON format error: {}\n{}", e, text))?;
+    Ok(data
+        .msg_array
+        .iter()
+        .map(|stock| (stock.c.clone(), stock.quote()))
+        .collect())
+}
 
+/// Query one batch of MIS channels (pipe-separated in a single request).
+async fn fetch_quote_batch(
+    client: &Client,
+    channels: &[String],
+) -> Result<HashMap<String, Result<TwseQuote, String>>, String> {
+    let url = format!("{}?ex_ch={}", MIS_URL, channels.join("|"));
     let response = client
         .get(&url)
         .send()
         .await
-        .map_err(|e| format!("[TWSE] Failed to query price for {}: {}", symbol, e))?;
+        .map_err(|e| format!("[TWSE] Failed to query {} quotes: {}", channels.len(), e))?;
+    if !response.status().is_success() {
+        return Err(format!("[TWSE] HTTP error code: {}", response.status()));
+    }
+    let text = response.text().await.unwrap_or_default();
+    parse_quotes(&text)
+}
 
-    if response.status().is_success() {
-        let text = response.text().await.unwrap_or_default();
-        let data: TwseResponse = serde_json::from_str(&text)
-            .map_err(|e| format!("[TWSE] Returned JSON format error: {}\n{}", e, text))?;
-        let stock = data
-            .msg_array
-            .get(0)
-            .ok_or("[TWSE] Cannot find stock data")?;
+/// Fetch quotes for many Taiwan holdings, given as `(symbol, category)` pairs,
+/// with one MIS request per [`MIS_BATCH_SIZE`] channels instead of one per
+/// symbol.
+///
+/// Every requested symbol is in the returned map: its quote, or an `Err`
+/// string if its batch failed or the response had no usable data for it.
+pub async fn get_quotes_from_twse(
+    holdings: &[(String, String)],
+) -> HashMap<String, Result<TwseQuote, String>> {
+    let mut results = HashMap::new();
+    let client = match Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            for (symbol, _) in holdings {
+                results.insert(symbol.clone(), Err(e.to_string()));
+            }
+            return results;
+        }
+    };
 
-        // Use last traded price if available
-        if stock.z != "-" {
-            stock
-                .z
-                .parse::<f64>()
-                .map_err(|_| "[TWSE] Failed to parse price as float".to_string())
-        } else {
-            // Use geometric mean of ask and bid if last traded price is unavailable
-            let a1 = stock.a.split('_').next().unwrap_or("-");
-            let b1 = stock.b.split('_').next().unwrap_or("-");
-            let a1f = a1.parse::<f64>();
-            let b1f = b1.parse::<f64>();
-            match (a1f, b1f) {
-                (Ok(a), Ok(b)) => {
-                    let geo_mean = (a * b).sqrt();
-                    Ok(geo_mean)
+    for batch in holdings.chunks(MIS_BATCH_SIZE) {
+        let channels: Vec<String> = batch
+            .iter()
+            .map(|(symbol, category)| mis_channel(symbol, category))
+            .collect();
+        match fetch_quote_batch(&client, &channels).await {
+            Ok(mut quotes) => {
+                for (symbol, _) in batch {
+                    let quote = quotes
+                        .remove(symbol)
+                        .unwrap_or_else(|| Err("[TWSE] Cannot find stock data".to_string()));
+                    results.insert(symbol.clone(), quote);
                 }
-                _ => {
-                    // Use previous close if ask and bid cannot be parsed
-                    stock
-                        .y
-                        .parse::<f64>()
-                        .map_err(|_| "[TWSE] Failed to parse previous close as float".to_string())
+            }
+            Err(e) => {
+                for (symbol, _) in batch {
+                    results.insert(symbol.clone(), Err(e.clone()));
                 }
             }
         }
-    } else {
-        Err(format!("[TWSE] HTTP error code: {}", response.status()))
     }
+    results
+}
+
+/// Fetch one holding's quote via [`get_quotes_from_twse`].
+async fn get_quote_from_twse(symbol: &str, category: &str) -> Result<TwseQuote, String> {
+    get_quotes_from_twse(&[(symbol.to_string(), category.to_string())])
+        .await
+        .remove(symbol)
+        .unwrap_or_else(|| Err("[TWSE] Cannot find stock data".to_string()))
+}
+
+/// Fetch the current price of Taiwan stock `symbol` (e.g. `2330`, or `6488`
+/// for `category` `TW-OTC`).
+///
+/// Uses the last traded price when available; if it is `"-"`, falls back to the
+/// geometric mean of the best ask and bid, and if those cannot be parsed, to the
+/// previous close. Returns an `Err` string on request, HTTP, JSON, missing-data
+/// or parse failure.
+pub async fn get_price_from_twse(symbol: &str, category: &str) -> Result<f64, String> {
+    get_quote_from_twse(symbol, category)
+        .await
+        .map(|quote| quote.price)
 }
 
-/// Fetch the previous-close price of TWSE-listed stock `symbol`.
+/// Fetch the previous-close price of Taiwan stock `symbol` (listed on TPEx when
+/// `category` is `TW-OTC`, otherwise on TWSE).
 ///
 /// Returns the previous close (the `y` field), or an `Err` string on request,
 /// HTTP, JSON, missing-data or parse failure.
-pub async fn get_close_price_from_twse(symbol: &str) -> Result<f64, String> {
-    let pair = format!("tse_{}.tw", symbol);
-    let url = format!(
-        "https://mis.twse.com.tw/stock/api/getStockInfo.jsp?ex_ch={}",
-        pair
-    );
+pub async fn get_close_price_from_twse(symbol: &str, category: &str) -> Result<f64, String> {
+    get_quote_from_twse(symbol, category)
+        .await?
+        .prev_close
+        .ok_or_else(|| "[TWSE] Failed to parse previous close as float".to_string())
+}
+
+/// TWSE OpenAPI endpoint listing the market holidays of the current year.
+pub const HOLIDAY_SCHEDULE_URL: &str =
+    "https://openapi.twse.com.tw/v1/holidaySchedule/holidaySchedule";
+
+#[derive(Deserialize, Debug)]
+struct HolidayEntry {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "Date")]
+    date: String, // ROC calendar, e.g. "1150101" for 2026-01-01
+}
+
+/// Parse an ROC-calendar date (years counted from 1912), either compact
+/// (`1150101`) or slash-separated (`115/01/01`), into 2026-01-01.
+fn parse_roc_date(date: &str) -> Option<NaiveDate> {
+    let (year, month, day) = match date.split('/').collect::<Vec<_>>()[..] {
+        [year, month, day] => (year, month, day),
+        [compact] if compact.len() >= 5 => {
+            let (year, month_day) = compact.split_at(compact.len() - 4);
+            (year, &month_day[..2], &month_day[2..])
+        }
+        _ => return None,
+    };
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    if !(digits(year) && digits(month) && digits(day)) {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(
+        year.parse::<i32>().ok()? + 1911,
+        month.parse().ok()?,
+        day.parse().ok()?,
+    )
+}
 
+/// The weekday closures in a holiday-schedule response, sorted.
+///
+/// The schedule also lists the first and last trading days around the Lunar
+/// New Year ("...開始交易日", "...最後交易日"); those trade and are skipped.
+/// Settlement-only days ("市場無交易...") do not trade and are kept.
+fn parse_holiday_schedule(text: &str) -> Result<Vec<NaiveDate>, String> {
+    let entries: Vec<HolidayEntry> = serde_json::from_str(text)
+        .map_err(|e| format!("[TWSE] Holiday schedule JSON format error: {}", e))?;
+    let mut closed: Vec<NaiveDate> = entries
+        .iter()
+        .filter(|entry| !entry.name.contains("交易日") || entry.name.contains("無交易"))
+        .filter_map(|entry| parse_roc_date(&entry.date))
+        .filter(|date| date.weekday().num_days_from_monday() < 5)
+        .collect();
+    closed.sort();
+    closed.dedup();
+    Ok(closed)
+}
+
+/// Download TWSE's holiday schedule from `url` and cache its closures at `path`
+/// as a JSON list of `YYYY-MM-DD` dates, for [`crate::calendar`]. Returns the
+/// number of closures written.
+pub async fn sync_holiday_schedule(url: &str, path: &str) -> Result<usize, String> {
     let client = Client::builder()
-        .timeout(std::time::Duration::from_secs(5))
+        .timeout(std::time::Duration::from_secs(30))
         .build()
         .map_err(|e| e.to_string())?;
-
     let response = client
-        .get(&url)
+        .get(url)
         .send()
         .await
-        .map_err(|e| format!("[TWSE] Failed to query close price for {}: {}", symbol, e))?;
+        .map_err(|e| format!("[TWSE] Failed to query {}: {}", url, e))?;
+    if !response.status().is_success() {
+        return Err(format!("[TWSE] HTTP error code: {}", response.status()));
+    }
+    let text = response.text().await.unwrap_or_default();
+    let closed = parse_holiday_schedule(&text)?;
+    if closed.is_empty() {
+        return Err("[TWSE] Holiday schedule listed no closures".to_string());
+    }
+
+    let dates: Vec<String> = closed.iter().map(|date| date.to_string()).collect();
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create data dir: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(&dates)
+        .map_err(|e| format!("Failed to serialize holidays: {}", e))?;
+    // Write then rename so a failed sync never leaves a truncated file.
+    let tmp = format!("{}.tmp", path);
+    std::fs::write(&tmp, content).map_err(|e| format!("Failed to write {}: {}", tmp, e))?;
+    std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(dates.len())
+}
+
+/// TWSE daily trading report: one listed stock's bars for one calendar month.
+const STOCK_DAY_URL: &str = "https://www.twse.com.tw/rwd/zh/afterTrading/STOCK_DAY";
+/// Pause between monthly pages; TWSE blocks clients that send more than about
+/// three requests every five seconds.
+const STOCK_DAY_PAGE_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Deserialize, Debug)]
+struct StockDayResponse {
+    stat: String,
+    #[serde(default)]
+    fields: Vec<String>,
+    #[serde(default)]
+    data: Vec<Vec<String>>,
+}
+
+/// Unix timestamp of the 13:30 Taipei close on `date`.
+fn close_timestamp(date: NaiveDate) -> Option<i64> {
+    let close = date.and_hms_opt(13, 30, 0)?;
+    Taipei
+        .from_local_datetime(&close)
+        .single()
+        .map(|t| t.timestamp())
+}
+
+/// Decode one `STOCK_DAY` page into `(close timestamp, close)` pairs. A month
+/// without trades ("很抱歉，沒有符合條件的資料!") yields no bars; days without a
+/// close (`--`, e.g. a suspension) are skipped.
+fn parse_stock_day(text: &str) -> Result<Vec<(i64, f64)>, String> {
+    let page: StockDayResponse = serde_json::from_str(text)
+        .map_err(|e| format!("[TWSE] STOCK_DAY JSON format error: {}", e))?;
+    if page.stat != "OK" {
+        if page.stat.contains("沒有符合條件") {
+            return Ok(Vec::new());
+        }
+        return Err(format!("[TWSE] STOCK_DAY error: {}", page.stat));
+    }
+    let close_column = page
+        .fields
+        .iter()
+        .position(|field| field == "收盤價")
+        .ok_or("[TWSE] STOCK_DAY response has no close column")?;
+    Ok(page
+        .data
+        .iter()
+        .filter_map(|row| {
+            let date = parse_roc_date(row.first()?.trim())?;
+            let close = row
+                .get(close_column)?
+                .replace(',', "")
+                .parse::<f64>()
+                .ok()?;
+            Some((close_timestamp(date)?, close))
+        })
+        .collect())
+}
+
+/// Fetch the daily closes of TWSE-listed `symbol` between `from` and `to`
+/// (unix epoch seconds) from the official `STOCK_DAY` report, one request per
+/// calendar month (Taipei time), oldest first.
+///
+/// Returns `(timestamp, close)` pairs stamped at the 13:30 Taipei close, or an
+/// `Err` string if any month fails or the window holds no trading days.
+pub async fn get_history_from_twse(
+    symbol: &str,
+    from: i64,
+    to: i64,
+) -> Result<Vec<(i64, f64)>, String> {
+    let month_of = |ts: i64| {
+        chrono::DateTime::from_timestamp(ts, 0)
+            .map(|t| t.with_timezone(&Taipei).date_naive())
+            .and_then(|date| date.with_day(1))
+            .ok_or_else(|| format!("[TWSE] Invalid timestamp: {}", ts))
+    };
+    let (first_month, last_month) = (month_of(from)?, month_of(to)?);
+
+    let client = Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
 
-    if response.status().is_success() {
+    let mut series = Vec::new();
+    let mut month = first_month;
+    while month <= last_month {
+        if month != first_month {
+            tokio::time::sleep(STOCK_DAY_PAGE_DELAY).await;
+        }
+        let url = format!(
+            "{}?date={}&stockNo={}&response=json",
+            STOCK_DAY_URL,
+            month.format("%Y%m%d"),
+            symbol
+        );
+        let response = client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| format!("[TWSE] Failed to query history {}: {}", symbol, e))?;
+        if !response.status().is_success() {
+            return Err(format!("[TWSE] HTTP error code: {}", response.status()));
+        }
         let text = response.text().await.unwrap_or_default();
-        let data: TwseResponse = serde_json::from_str(&text)
-            .map_err(|e| format!("[TWSE] Returned JSON format error: {}\n{}", e, text))?;
-        let stock = data
-            .msg_array
-            .get(0)
-            .ok_or("[TWSE] Cannot find stock data")?;
+        series.extend(
+            parse_stock_day(&text)?
+                .into_iter()
+                .filter(|(ts, _)| *ts >= from && *ts <= to),
+        );
+        month = month
+            .checked_add_months(chrono::Months::new(1))
+            .ok_or("[TWSE] Date out of range")?;
+    }
+
+    if series.is_empty() {
+        return Err(format!("[TWSE] No history for {}", symbol));
+    }
+    Ok(series)
+}
+
+/// [`PriceSource`] for the TWSE real-time quote API (Taiwan equities, TWSE and
+/// TPEx listed) and the `STOCK_DAY` daily report (history, TWSE-listed only).
+pub struct Twse;
+
+impl PriceSource for Twse {
+    fn name(&self) -> &'static str {
+        "twse"
+    }
+
+    async fn quote(&self, symbol: &str, category: &str) -> Result<f64, String> {
+        match category {
+            "TW-Stock" | "TW-ETF" | "TW-OTC" => get_price_from_twse(symbol, category).await,
+            _ => unsupported("TWSE", category),
+        }
+    }
 
-        stock
-            .y
-            .parse::<f64>()
-            .map_err(|_| "[TWSE] Failed to parse previous close as float".to_string())
-    } else {
-        Err(format!("[TWSE] HTTP error code: {}", response.status()))
+    async fn history(
+        &self,
+        symbol: &str,
+        category: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<(i64, f64)>, String> {
+        match category {
+            "TW-Stock" | "TW-ETF" => get_history_from_twse(symbol, from, to).await,
+            _ => Err(format!("[TWSE] {} history is not supported", category)),
+        }
     }
 }
 
@@ -128,9 +423,97 @@ mod tests {
     #[tokio::test]
     async fn test_get_price_from_twse() {
         let symbol = "2330"; // TSMC
-        match get_price_from_twse(symbol).await {
+        match get_price_from_twse(symbol, "TW-Stock").await {
             Ok(price) => println!("Price of {}: {}", symbol, price),
             Err(e) => eprintln!("Error: {}", e),
         }
     }
+
+    #[test]
+    fn test_mis_channel() {
+        assert_eq!(mis_channel("2330", "TW-Stock"), "tse_2330.tw");
+        assert_eq!(mis_channel("0050", "TW-ETF"), "tse_0050.tw");
+        assert_eq!(mis_channel("6488", "TW-OTC"), "otc_6488.tw");
+    }
+
+    #[test]
+    fn test_parse_quotes_per_symbol() {
+        let text = r#"{"msgArray":[
+            {"c":"2330","z":"1010.0000","a":"1015.0000_1020.0000_","b":"1010.0000_","y":"1000.0000"},
+            {"c":"6488","z":"-","a":"451.0000_","b":"449.0000_","y":"445.0000"},
+            {"c":"0050","z":"-","a":"-","b":"-","y":"180.5000"},
+            {"c":"9999","z":"-","a":"-","b":"-","y":"-"}
+        ]}"#;
+        let quotes = parse_quotes(text).unwrap();
+        assert_eq!(
+            quotes["2330"],
+            Ok(TwseQuote {
+                price: 1010.0,
+                prev_close: Some(1000.0)
+            })
+        );
+        let otc = quotes["6488"].as_ref().unwrap();
+        assert!((otc.price - (451.0f64 * 449.0).sqrt()).abs() < 1e-9);
+        assert_eq!(quotes["0050"].as_ref().unwrap().price, 180.5);
+        assert!(quotes["9999"].is_err());
+        assert!(parse_quotes("not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_holiday_schedule_keeps_closures_only() {
+        let text = r#"[
+            {"Name":"中華民國開國紀念日","Date":"1150101","Weekday":"四","Description":"依規定放假1日。"},
+            {"Name":"國曆新年開始交易日","Date":"1150102","Weekday":"五","Description":"國曆新年開始交易。"},
+            {"Name":"市場無交易，僅辦理結算交割作業","Date":"1150212","Weekday":"四","Description":""},
+            {"Name":"農曆春節前最後交易日","Date":"1150211","Weekday":"三","Description":""},
+            {"Name":"農曆除夕","Date":"1150216","Weekday":"一","Description":""},
+            {"Name":"農曆春節","Date":"1150215","Weekday":"日","Description":""}
+        ]"#;
+        let dates: Vec<String> = parse_holiday_schedule(text)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(dates, ["2026-01-01", "2026-02-12", "2026-02-16"]);
+        assert_eq!(
+            parse_roc_date("1141225"),
+            NaiveDate::from_ymd_opt(2025, 12, 25)
+        );
+        assert_eq!(
+            parse_roc_date("115/01/01"),
+            NaiveDate::from_ymd_opt(2026, 1, 1)
+        );
+        assert_eq!(parse_roc_date("115-01-01"), None);
+    }
+
+    #[test]
+    fn test_parse_stock_day_reads_roc_dates_and_closes() {
+        let text = r#"{"stat":"OK","date":"20250101",
+            "fields":["日期","成交股數","成交金額","開盤價","最高價","最低價","收盤價","漲跌價差","成交筆數"],
+            "data":[
+                ["114/01/02","27,027,455","28,547,339,950","1,070.00","1,075.00","1,050.00","1,055.00","-20.00","48,383"],
+                ["114/01/03","31,248,122","33,447,981,102","1,060.00","1,075.00","1,060.00","1,070.00","+15.00","40,112"],
+                ["114/01/06","0","0","--","--","--","--"," 0.00","0"]
+            ]}"#;
+        let bars = parse_stock_day(text).unwrap();
+        let jan2 = close_timestamp(NaiveDate::from_ymd_opt(2025, 1, 2).unwrap()).unwrap();
+        assert_eq!(bars, [(jan2, 1055.0), (jan2 + 86_400, 1070.0)]);
+        // 13:30 Taipei is 05:30 UTC, inside the same UTC day.
+        assert_eq!(jan2.rem_euclid(86_400), 5 * 3_600 + 1_800);
+
+        let empty = r#"{"stat":"很抱歉，沒有符合條件的資料!"}"#;
+        assert_eq!(parse_stock_day(empty), Ok(Vec::new()));
+        assert!(parse_stock_day(r#"{"stat":"查詢日期大於今日，請重新查詢!"}"#).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_get_history_from_twse() {
+        if std::env::var("RUN_LIVE_PRICE_TESTS").as_deref() != Ok("1") {
+            return;
+        }
+        let to = chrono::Utc::now().timestamp();
+        let from = to - 40 * 86_400;
+        let series = get_history_from_twse("2330", from, to).await.unwrap();
+        assert!(series.iter().all(|(ts, _)| *ts >= from && *ts <= to));
+    }
 }
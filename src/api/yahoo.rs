@@ -3,6 +3,9 @@
 use reqwest::Client;
 use serde::Deserialize;
 
+use crate::api::PriceSource;
+use crate::exchange;
+
 /// Yahoo Finance rejects requests without a browser-like User-Agent (HTTP 429
 /// "Edge: Too Many Requests"), so every call must send one.
 const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0 Safari/537.36";
@@ -69,8 +72,8 @@ pub async fn get_price_from_yahoo(symbol: &str) -> Result<f64, String> {
         .chart
         .result
         .as_ref()
-        .and_then(|r| r.get(0))
-        .and_then(|r| r.indicators.quote.get(0))
+        .and_then(|r| r.first())
+        .and_then(|r| r.indicators.quote.first())
         .and_then(|q| q.close.last().copied().flatten());
 
     match close {
@@ -122,13 +125,13 @@ pub async fn get_history_from_yahoo(
         .chart
         .result
         .as_ref()
-        .and_then(|r| r.get(0))
+        .and_then(|r| r.first())
         .ok_or_else(|| format!("[Yahoo] No history result for {}", symbol))?;
 
     let quote = result
         .indicators
         .quote
-        .get(0)
+        .first()
         .ok_or_else(|| format!("[Yahoo] No quote data for {}", symbol))?;
 
     let series = result
@@ -183,13 +186,13 @@ pub async fn get_history_from_yahoo_range(
         .chart
         .result
         .as_ref()
-        .and_then(|r| r.get(0))
+        .and_then(|r| r.first())
         .ok_or_else(|| format!("[Yahoo] No history result for {}", symbol))?;
 
     let quote = result
         .indicators
         .quote
-        .get(0)
+        .first()
         .ok_or_else(|| format!("[Yahoo] No quote data for {}", symbol))?;
 
     let series = result
@@ -203,6 +206,59 @@ pub async fn get_history_from_yahoo_range(
     Ok(series)
 }
 
+/// The Yahoo Finance ticker for a portfolio holding: `.TW` for TWSE-listed
+/// and `.TWO` for TPEx-listed Taiwan equities, the exchange's suffix for a
+/// `Stock.{code}` listing (see [`crate::exchange`]), `-USD` for crypto and
+/// `{ccy}=X` (units per USD) for forex.
+pub fn yahoo_symbol(symbol: &str, category: &str) -> String {
+    if let Some(exchange) = exchange::for_category(category) {
+        return exchange.yahoo_symbol(symbol);
+    }
+    match category {
+        "TW-Stock" | "TW-ETF" => format!("{}.TW", symbol),
+        "TW-OTC" => format!("{}.TWO", symbol),
+        "Crypto" => format!("{}-USD", symbol.to_uppercase()),
+        "Forex" => format!("{}=X", symbol.to_uppercase()),
+        _ => symbol.to_string(),
+    }
+}
+
+/// Convert a Yahoo price for `category` into the holding's currency units
+/// (London listings are quoted in pence).
+fn to_currency_units(category: &str, price: f64) -> f64 {
+    exchange::for_category(category).map_or(price, |exchange| exchange.to_currency_units(price))
+}
+
+/// [`PriceSource`] for Yahoo Finance (every category; quotes and history).
+pub struct Yahoo;
+
+impl PriceSource for Yahoo {
+    fn name(&self) -> &'static str {
+        "yahoo"
+    }
+
+    async fn quote(&self, symbol: &str, category: &str) -> Result<f64, String> {
+        get_price_from_yahoo(&yahoo_symbol(symbol, category))
+            .await
+            .map(|price| to_currency_units(category, price))
+    }
+
+    async fn history(
+        &self,
+        symbol: &str,
+        category: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<(i64, f64)>, String> {
+        let series =
+            get_history_from_yahoo_range(&yahoo_symbol(symbol, category), from, to, "1d").await?;
+        Ok(series
+            .into_iter()
+            .map(|(ts, price)| (ts, to_currency_units(category, price)))
+            .collect())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -215,4 +271,15 @@ mod tests {
             Err(e) => eprintln!("Error: {}", e),
         }
     }
+
+    #[test]
+    fn test_yahoo_symbol() {
+        assert_eq!(yahoo_symbol("2330", "TW-Stock"), "2330.TW");
+        assert_eq!(yahoo_symbol("6488", "TW-OTC"), "6488.TWO");
+        assert_eq!(yahoo_symbol("eth", "Crypto"), "ETH-USD");
+        assert_eq!(yahoo_symbol("twd", "Forex"), "TWD=X");
+        assert_eq!(yahoo_symbol("AAPL", "US-Stock"), "AAPL");
+        assert_eq!(yahoo_symbol("7203", "Stock.JP"), "7203.T");
+        assert_eq!(to_currency_units("Stock.UK", 2_650.0), 26.5);
+    }
 }
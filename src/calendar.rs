@@ -0,0 +1,581 @@
+//! Exchange trading calendars: on which days and at what hours a market
+//! trades, so polling, staleness and the TUI can tell a closed market from a
+//! broken feed.
+//!
+//! The TWSE calendar trades Monday–Friday, 09:00–13:30 Asia/Taipei, except on
+//! closures gathered from three lists:
+//!
+//! - a holiday table compiled into the binary (`src/data/twse_holidays.toml`),
+//!   which runs to the end of 2026;
+//! - TWSE's published schedule, cached by `price calendar sync` in
+//!   `<data dir>/twse_holidays.json`;
+//! - extra closures in `<config dir>/twse_holidays.toml`, for typhoon days and
+//!   other closures announced at short notice:
+//!
+//! ```toml
+//! closed = [2026-07-24, 2026-07-25]
+//! ```
+//!
+//! Both files are optional and are re-read when they change. A warning is
+//! logged when none of the lists has a holiday in the current year, since the
+//! calendar would then treat every holiday as a trading day.
+//!
+//! The NYSE calendar (which Nasdaq shares) splits each trading day into
+//! pre-market (04:00–09:30 New York time), the regular session (09:30–16:00,
+//! or 13:00 on early-close days) and after-hours (four hours past the regular
+//! close). Its holidays and early closes are compiled into the binary
+//! (`src/data/nyse_calendar.toml`).
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::sync::{Arc, RwLock};
+
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Timelike, Utc, Weekday};
+use chrono_tz::America::New_York;
+use chrono_tz::Asia::Taipei;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::paths;
+use crate::serde_util::deserialize_date;
+
+/// TWSE continuous trading session, in minutes after midnight Taipei time.
+const TWSE_OPEN_MINUTE: u32 = 9 * 60; // 09:00
+const TWSE_CLOSE_MINUTE: u32 = 13 * 60 + 30; // 13:30
+
+/// Whether a market is trading at a given moment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketStatus {
+    /// Inside the trading session of a trading day.
+    Open,
+    /// Outside the session, or on a weekend.
+    Closed,
+    /// A weekday on which the exchange does not trade.
+    Holiday,
+}
+
+impl fmt::Display for MarketStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            MarketStatus::Open => "OPEN",
+            MarketStatus::Closed => "CLOSED",
+            MarketStatus::Holiday => "HOLIDAY",
+        })
+    }
+}
+
+/// The TWSE (and TPEx, which keeps the same hours) trading calendar.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TwseCalendar {
+    closed: BTreeSet<NaiveDate>,
+}
+
+impl TwseCalendar {
+    /// A calendar with the given weekday closures.
+    pub fn new(closed: impl IntoIterator<Item = NaiveDate>) -> Self {
+        TwseCalendar {
+            closed: closed.into_iter().collect(),
+        }
+    }
+
+    /// Whether the exchange trades on `date` (a Taipei calendar date).
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.closed.contains(&date)
+    }
+
+    /// Whether any closure is listed in `year`. TWSE closes for holidays every
+    /// year, so a year without one is a year the holiday lists do not reach.
+    pub fn covers_year(&self, year: i32) -> bool {
+        self.closed.iter().any(|date| date.year() == year)
+    }
+
+    /// The market status at `dt`, in whatever timezone it is given; the
+    /// weekday and session hours are checked in Taipei time.
+    pub fn status_at<Tz: TimeZone>(&self, dt: DateTime<Tz>) -> MarketStatus {
+        let local = dt.with_timezone(&Taipei);
+        let date = local.date_naive();
+        if matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            return MarketStatus::Closed;
+        }
+        if self.closed.contains(&date) {
+            return MarketStatus::Holiday;
+        }
+        let minute = local.hour() * 60 + local.minute();
+        if (TWSE_OPEN_MINUTE..TWSE_CLOSE_MINUTE).contains(&minute) {
+            MarketStatus::Open
+        } else {
+            MarketStatus::Closed
+        }
+    }
+
+    /// Whether the market is in session at `dt`.
+    pub fn is_open_at<Tz: TimeZone>(&self, dt: DateTime<Tz>) -> bool {
+        self.status_at(dt) == MarketStatus::Open
+    }
+
+    /// Seconds of trading session between the Unix timestamps `from` and `to`.
+    /// A quote taken at `from` cannot have moved for longer than this.
+    pub fn trading_secs_between(&self, from: i64, to: i64) -> i64 {
+        let (Some(start), Some(end)) = (
+            DateTime::<Utc>::from_timestamp(from, 0),
+            DateTime::<Utc>::from_timestamp(to, 0),
+        ) else {
+            return 0;
+        };
+        let last_day = end.with_timezone(&Taipei).date_naive();
+        let mut total = 0;
+        for day in start.with_timezone(&Taipei).date_naive().iter_days() {
+            if day > last_day {
+                break;
+            }
+            if !self.is_trading_day(day) {
+                continue;
+            }
+            let at = |minute: u32| {
+                day.and_hms_opt(minute / 60, minute % 60, 0)
+                    .and_then(|t| Taipei.from_local_datetime(&t).single())
+                    .map_or(0, |t| t.timestamp())
+            };
+            let open = at(TWSE_OPEN_MINUTE).max(from);
+            let close = at(TWSE_CLOSE_MINUTE).min(to);
+            total += (close - open).max(0);
+        }
+        total
+    }
+}
+
+/// A Taipei calendar date, accepted as a TOML date or a `"YYYY-MM-DD"` string.
+#[derive(Deserialize)]
+struct Day(#[serde(deserialize_with = "deserialize_date")] NaiveDate);
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HolidayFile {
+    #[serde(default)]
+    closed: Vec<Day>,
+}
+
+/// Parse a holiday file (`closed = [...]`) into its dates.
+pub fn parse_holidays(content: &str) -> Result<Vec<NaiveDate>, String> {
+    let file: HolidayFile =
+        toml::from_str(content).map_err(|e| format!("Failed to parse TOML: {}", e))?;
+    Ok(file.closed.into_iter().map(|Day(date)| date).collect())
+}
+
+/// Read the extra closures at `path`. A missing file lists none.
+pub fn read_holidays(path: &str) -> Result<Vec<NaiveDate>, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => parse_holidays(&content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read file: {}", e)),
+    }
+}
+
+/// Read the schedule cached by `price calendar sync` at `path` (a JSON list of
+/// `YYYY-MM-DD` dates). A missing file lists none.
+pub fn read_synced_holidays(path: &str) -> Result<Vec<NaiveDate>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read file: {}", e)),
+    };
+    let dates: Vec<String> =
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    dates
+        .iter()
+        .map(|date| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|e| format!("Invalid date {}: {}", date, e))
+        })
+        .collect()
+}
+
+/// The holiday table compiled into the binary.
+fn bundled_holidays() -> Vec<NaiveDate> {
+    parse_holidays(include_str!("data/twse_holidays.toml"))
+        .expect("bundled twse_holidays.toml must be valid")
+}
+
+/// Build the TWSE calendar from the bundled table plus the synced schedule and
+/// the user's extra closures. Returns an `Err` string if either file exists
+/// but cannot be read or parsed.
+pub fn read_twse_calendar() -> Result<TwseCalendar, String> {
+    let synced_path = paths::twse_holidays_cache_file();
+    let local_path = paths::twse_holidays_file();
+    let synced =
+        read_synced_holidays(&synced_path).map_err(|e| format!("{}: {}", synced_path, e))?;
+    let local = read_holidays(&local_path).map_err(|e| format!("{}: {}", local_path, e))?;
+    Ok(TwseCalendar::new(
+        bundled_holidays().into_iter().chain(synced).chain(local),
+    ))
+}
+
+/// Log a warning if `calendar` lists no holidays for the current Taipei year,
+/// which would otherwise show every holiday as a trading day.
+fn warn_if_outdated(calendar: &TwseCalendar) {
+    let year = Utc::now().with_timezone(&Taipei).year();
+    if !calendar.covers_year(year) {
+        crate::log_line!(
+            "[calendar] no TWSE holidays known for {}; run `price calendar sync`",
+            year
+        );
+    }
+}
+
+static TWSE: Lazy<RwLock<Arc<TwseCalendar>>> = Lazy::new(|| {
+    let calendar = read_twse_calendar().unwrap_or_else(|e| {
+        crate::log_line!("[config] {}; using the bundled TWSE holidays", e);
+        TwseCalendar::new(bundled_holidays())
+    });
+    warn_if_outdated(&calendar);
+    RwLock::new(Arc::new(calendar))
+});
+
+/// The TWSE calendar currently in effect (loaded on first use).
+pub fn twse() -> Arc<TwseCalendar> {
+    TWSE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Re-read the holiday files, keeping the calendar in effect if one is invalid.
+pub fn reload_twse() -> Result<(), String> {
+    let calendar = read_twse_calendar()?;
+    warn_if_outdated(&calendar);
+    *TWSE.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(calendar);
+    Ok(())
+}
+
+/// NYSE session boundaries, in minutes after midnight New York time.
+const NYSE_PRE_OPEN_MINUTE: u32 = 4 * 60; // 04:00
+const NYSE_OPEN_MINUTE: u32 = 9 * 60 + 30; // 09:30
+const NYSE_CLOSE_MINUTE: u32 = 16 * 60; // 16:00
+const NYSE_EARLY_CLOSE_MINUTE: u32 = 13 * 60; // 13:00
+/// After-hours trading runs this long past the regular close.
+const NYSE_POST_MINUTES: u32 = 4 * 60;
+
+/// Which part of the US trading day a moment falls in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsSession {
+    /// Pre-market, before the opening bell.
+    Pre,
+    /// The regular session.
+    Regular,
+    /// After-hours, following the closing bell.
+    Post,
+    /// Overnight, weekends and holidays.
+    Closed,
+}
+
+impl fmt::Display for UsSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            UsSession::Pre => "PRE",
+            UsSession::Regular => "REG",
+            UsSession::Post => "POST",
+            UsSession::Closed => "CLOSED",
+        })
+    }
+}
+
+/// The NYSE trading calendar: holidays and early closes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NyseCalendar {
+    closed: BTreeSet<NaiveDate>,
+    early_close: BTreeSet<NaiveDate>,
+}
+
+impl NyseCalendar {
+    /// A calendar with the given full-day closures and 13:00 early closes.
+    pub fn new(
+        closed: impl IntoIterator<Item = NaiveDate>,
+        early_close: impl IntoIterator<Item = NaiveDate>,
+    ) -> Self {
+        NyseCalendar {
+            closed: closed.into_iter().collect(),
+            early_close: early_close.into_iter().collect(),
+        }
+    }
+
+    /// Whether the exchange trades on `date` (a New York calendar date).
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.closed.contains(&date)
+    }
+
+    /// The closing bell on trading day `date`, in minutes after midnight.
+    fn close_minute(&self, date: NaiveDate) -> u32 {
+        if self.early_close.contains(&date) {
+            NYSE_EARLY_CLOSE_MINUTE
+        } else {
+            NYSE_CLOSE_MINUTE
+        }
+    }
+
+    /// The session at `dt`, in whatever timezone it is given; the day and
+    /// hours are checked in New York time.
+    pub fn session_at<Tz: TimeZone>(&self, dt: DateTime<Tz>) -> UsSession {
+        let local = dt.with_timezone(&New_York);
+        let date = local.date_naive();
+        if !self.is_trading_day(date) {
+            return UsSession::Closed;
+        }
+        let minute = local.hour() * 60 + local.minute();
+        let close = self.close_minute(date);
+        if (NYSE_PRE_OPEN_MINUTE..NYSE_OPEN_MINUTE).contains(&minute) {
+            UsSession::Pre
+        } else if (NYSE_OPEN_MINUTE..close).contains(&minute) {
+            UsSession::Regular
+        } else if (close..close + NYSE_POST_MINUTES).contains(&minute) {
+            UsSession::Post
+        } else {
+            UsSession::Closed
+        }
+    }
+
+    /// Unix timestamp of the closing bell on `date`, or `None` if the exchange
+    /// does not trade that day.
+    pub fn regular_close(&self, date: NaiveDate) -> Option<i64> {
+        if !self.is_trading_day(date) {
+            return None;
+        }
+        let minute = self.close_minute(date);
+        let close = date.and_hms_opt(minute / 60, minute % 60, 0)?;
+        New_York
+            .from_local_datetime(&close)
+            .single()
+            .map(|t| t.timestamp())
+    }
+
+    /// The first closing bell after the Unix timestamp `now`.
+    pub fn next_regular_close(&self, now: i64) -> Option<i64> {
+        let today = DateTime::<Utc>::from_timestamp(now, 0)?
+            .with_timezone(&New_York)
+            .date_naive();
+        today
+            .iter_days()
+            .take(14)
+            .filter_map(|date| self.regular_close(date))
+            .find(|close| *close > now)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NyseFile {
+    #[serde(default)]
+    closed: Vec<Day>,
+    #[serde(default)]
+    early_close: Vec<Day>,
+}
+
+static NYSE: Lazy<NyseCalendar> = Lazy::new(|| {
+    let file: NyseFile = toml::from_str(include_str!("data/nyse_calendar.toml"))
+        .expect("bundled nyse_calendar.toml must be valid");
+    NyseCalendar::new(
+        file.closed.into_iter().map(|Day(date)| date),
+        file.early_close.into_iter().map(|Day(date)| date),
+    )
+});
+
+/// The NYSE calendar compiled into the binary.
+pub fn nyse() -> &'static NyseCalendar {
+    &NYSE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    // ── TwseCalendar::is_open_at ──────────────────────────────────────────────
+    //
+    // All dates use Asia/Taipei via Taipei.with_ymd_and_hms so tests are
+    // deterministic and require no network access.
+    //
+    // Calendar reference (verified):
+    //   2024-01-22 Mon · 2024-01-25 Thu · 2024-01-27 Sat · 2024-01-28 Sun
+
+    #[test]
+    fn twse_open_at_exactly_09_00() {
+        let dt = Taipei.with_ymd_and_hms(2024, 1, 22, 9, 0, 0).unwrap();
+        assert!(
+            TwseCalendar::default().is_open_at(dt),
+            "09:00 Taipei on a Monday must be open"
+        );
+    }
+
+    #[test]
+    fn twse_closed_at_exactly_13_30() {
+        let dt = Taipei.with_ymd_and_hms(2024, 1, 22, 13, 30, 0).unwrap();
+        assert!(
+            !TwseCalendar::default().is_open_at(dt),
+            "13:30 Taipei (close boundary) must be closed"
+        );
+    }
+
+    #[test]
+    fn twse_open_at_13_29() {
+        let dt = Taipei.with_ymd_and_hms(2024, 1, 22, 13, 29, 0).unwrap();
+        assert!(
+            TwseCalendar::default().is_open_at(dt),
+            "13:29 Taipei (last open minute) must be open"
+        );
+    }
+
+    #[test]
+    fn twse_closed_on_saturday() {
+        let dt = Taipei.with_ymd_and_hms(2024, 1, 27, 11, 0, 0).unwrap();
+        assert!(
+            !TwseCalendar::default().is_open_at(dt),
+            "Saturday must be closed regardless of time"
+        );
+    }
+
+    #[test]
+    fn twse_closed_on_sunday() {
+        let dt = Taipei.with_ymd_and_hms(2024, 1, 28, 11, 0, 0).unwrap();
+        assert!(
+            !TwseCalendar::default().is_open_at(dt),
+            "Sunday must be closed regardless of time"
+        );
+    }
+
+    #[test]
+    fn twse_closed_thursday_at_08_59() {
+        let dt = Taipei.with_ymd_and_hms(2024, 1, 25, 8, 59, 0).unwrap();
+        assert!(
+            !TwseCalendar::default().is_open_at(dt),
+            "08:59 Taipei on Thursday must be closed (before open)"
+        );
+    }
+
+    /// Verifies that Asia/Taipei is used for both weekday detection and hour
+    /// comparison, not the server's UTC time.
+    ///
+    /// UTC Sunday 2024-01-21 16:00 = Taipei Monday 2024-01-22 00:00.
+    /// UTC Monday 2024-01-22 01:00 = Taipei Monday 2024-01-22 09:00 (market open).
+    ///
+    /// A buggy implementation that checked trading hours in UTC (01:00) would
+    /// return `false`; the correct implementation uses Taipei time (09:00) and
+    /// returns `true`. The UTC-Sunday timestamp shows that the weekday check
+    /// also uses Taipei dates: the same moment is a Sunday in UTC but a Monday
+    /// in Taipei.
+    #[test]
+    fn twse_uses_taipei_tz_not_utc() {
+        let calendar = TwseCalendar::default();
+
+        // UTC Sunday 16:00 = Taipei Monday 00:00 — weekday in Taipei, weekend in UTC.
+        let utc_sunday = Utc.with_ymd_and_hms(2024, 1, 21, 16, 0, 0).unwrap();
+        assert_eq!(
+            utc_sunday.with_timezone(&Taipei).weekday(),
+            chrono::Weekday::Mon
+        );
+        assert!(
+            !calendar.is_open_at(utc_sunday),
+            "Taipei Monday 00:00 must be closed (before market open)"
+        );
+
+        // UTC Monday 01:00 = Taipei Monday 09:00 — exactly at open in Taipei.
+        let utc_monday_early = Utc.with_ymd_and_hms(2024, 1, 22, 1, 0, 0).unwrap();
+        assert!(
+            calendar.is_open_at(utc_monday_early),
+            "Taipei Monday 09:00 must be open even though UTC is 01:00 (outside UTC market hours)"
+        );
+    }
+
+    #[test]
+    fn twse_closed_on_listed_holiday() {
+        let calendar = TwseCalendar::new([date(2026, 2, 17)]);
+        let lunar_new_year = Taipei.with_ymd_and_hms(2026, 2, 17, 10, 0, 0).unwrap();
+        assert_eq!(calendar.status_at(lunar_new_year), MarketStatus::Holiday);
+        assert!(!calendar.is_open_at(lunar_new_year));
+        let next_day = Taipei.with_ymd_and_hms(2026, 2, 18, 10, 0, 0).unwrap();
+        assert_eq!(calendar.status_at(next_day), MarketStatus::Open);
+        let saturday = Taipei.with_ymd_and_hms(2026, 2, 21, 10, 0, 0).unwrap();
+        assert_eq!(calendar.status_at(saturday), MarketStatus::Closed);
+    }
+
+    #[test]
+    fn trading_secs_skip_nights_weekends_and_holidays() {
+        let calendar = TwseCalendar::new([date(2024, 1, 22)]);
+        let ts = |d, h, m| {
+            Taipei
+                .with_ymd_and_hms(2024, 1, d, h, m, 0)
+                .unwrap()
+                .timestamp()
+        };
+        // Friday 13:00 to 13:30 is the last half hour of the week's session.
+        assert_eq!(
+            calendar.trading_secs_between(ts(19, 13, 0), ts(19, 15, 0)),
+            1_800
+        );
+        // Friday's close to Tuesday 09:10 crosses a weekend and a closed Monday.
+        assert_eq!(
+            calendar.trading_secs_between(ts(19, 13, 30), ts(23, 9, 10)),
+            600
+        );
+        assert_eq!(
+            calendar.trading_secs_between(ts(20, 10, 0), ts(20, 11, 0)),
+            0
+        );
+    }
+
+    #[test]
+    fn holiday_files_accept_dates_and_strings() {
+        let dates = parse_holidays("closed = [2026-07-24, \"2026-07-25\"]").unwrap();
+        assert_eq!(dates, [date(2026, 7, 24), date(2026, 7, 25)]);
+        assert!(parse_holidays("open = [2026-07-24]").is_err());
+        let calendar = TwseCalendar::new(dates);
+        assert!(calendar.covers_year(2026));
+        assert!(!calendar.covers_year(2027));
+        assert_eq!(
+            read_holidays("/nonexistent/twse_holidays.toml"),
+            Ok(Vec::new())
+        );
+        assert!(!bundled_holidays().is_empty());
+    }
+
+    #[test]
+    fn nyse_sessions_follow_new_york_time() {
+        let calendar = NyseCalendar::new([date(2025, 7, 4)], [date(2025, 7, 3)]);
+        let at = |d, h, m| New_York.with_ymd_and_hms(2025, 7, d, h, m, 0).unwrap();
+        assert_eq!(calendar.session_at(at(2, 3, 59)), UsSession::Closed);
+        assert_eq!(calendar.session_at(at(2, 4, 0)), UsSession::Pre);
+        assert_eq!(calendar.session_at(at(2, 9, 30)), UsSession::Regular);
+        assert_eq!(calendar.session_at(at(2, 15, 59)), UsSession::Regular);
+        assert_eq!(calendar.session_at(at(2, 16, 0)), UsSession::Post);
+        assert_eq!(calendar.session_at(at(2, 20, 0)), UsSession::Closed);
+        // Early close before Independence Day, then the holiday itself.
+        assert_eq!(calendar.session_at(at(3, 13, 0)), UsSession::Post);
+        assert_eq!(calendar.session_at(at(3, 17, 0)), UsSession::Closed);
+        assert_eq!(calendar.session_at(at(4, 11, 0)), UsSession::Closed);
+        // 09:30 EDT is 13:30 UTC.
+        let utc = Utc.with_ymd_and_hms(2025, 7, 2, 13, 30, 0).unwrap();
+        assert_eq!(calendar.session_at(utc), UsSession::Regular);
+    }
+
+    #[test]
+    fn nyse_next_close_skips_holidays_and_weekends() {
+        let calendar = NyseCalendar::new([date(2025, 7, 4)], [date(2025, 7, 3)]);
+        let at = |d, h, m| {
+            New_York
+                .with_ymd_and_hms(2025, 7, d, h, m, 0)
+                .unwrap()
+                .timestamp()
+        };
+        assert_eq!(
+            calendar.next_regular_close(at(3, 10, 0)),
+            Some(at(3, 13, 0))
+        );
+        // After Thursday's early close: Friday is a holiday, so next Monday.
+        assert_eq!(
+            calendar.next_regular_close(at(3, 13, 0)),
+            Some(at(7, 16, 0))
+        );
+        assert_eq!(calendar.regular_close(date(2025, 7, 5)), None);
+        assert!(nyse().is_trading_day(date(2026, 7, 6)));
+        assert!(!nyse().is_trading_day(date(2026, 7, 3)));
+    }
+}
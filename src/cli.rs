@@ -0,0 +1,565 @@
+//! Non-interactive subcommands, so the tracker can be driven from scripts and
+//! cron as well as through the TUI.
+//!
+//! ```text
+//! price [tui] [--serve ADDR] [--reject-stale] [--us-close-only]
+//!                                               live terminal UI (default)
+//! price daemon [--serve ADDR] [--reject-stale] [--us-close-only]
+//!                                               record history headless until SIGTERM
+//! price quote <category> <symbol> [--json]      current price of one asset
+//! price total [--currency X] [--json]           portfolio value now
+//! price history export [--format csv|json] [--output PATH]
+//! price backfill                                rebuild daily history and save it
+//! price gains [--method fifo|lifo|average] [--output PATH]
+//! price feeds sync [--url URL]                  refresh the cached Pyth feed catalog
+//! price calendar sync [--url URL]               refresh the cached TWSE holiday schedule
+//! ```
+//!
+//! Results go to stdout; errors are returned to `main`, which prints them to
+//! stderr and exits non-zero. Diagnostics from the shared code still go to the
+//! log file.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::future::join_all;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::api::{pyth, twse};
+use crate::config;
+use crate::gains::{self, LotMethod};
+use crate::get::get_live_price;
+use crate::history;
+use crate::paths;
+use crate::server;
+use crate::stream::{self, SharedHistory};
+use crate::types::Portfolio;
+use crate::valuation;
+
+/// Usage text printed by `price help` and on argument errors.
+pub const USAGE: &str = "\
+Usage:
+  price [tui] [--serve ADDR] [--reject-stale] [--us-close-only]
+                                                live terminal UI (default)
+  price daemon [--serve ADDR] [--reject-stale] [--us-close-only]
+                                                record history headless until SIGTERM
+  price quote <category> <symbol> [--json]      current price of one asset
+  price total [--currency X] [--json]           portfolio value now
+  price history export [--format csv|json] [--output PATH]
+  price backfill                                rebuild daily history and save it
+  price gains [--method fifo|lifo|average] [--output PATH]
+  price feeds sync [--url URL]                  refresh the cached Pyth feed catalog
+  price calendar sync [--url URL]               refresh the cached TWSE holiday schedule
+  price help
+
+--serve PORT (localhost) or --serve IP:PORT also exposes a JSON API.
+--reject-stale skips live snapshots while a needed price is stale.
+--us-close-only values US equities in snapshots at their regular-session close
+outside NYSE trading hours.";
+
+/// Output format of `price history export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// A parsed command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// Run the interactive TUI (the behaviour with no arguments), optionally
+    /// serving the HTTP API on `serve`.
+    Tui {
+        serve: Option<SocketAddr>,
+        reject_stale: bool,
+        us_close_only: bool,
+    },
+    /// Run the background tasks without a terminal until signalled, optionally
+    /// serving the HTTP API on `serve`.
+    Daemon {
+        serve: Option<SocketAddr>,
+        reject_stale: bool,
+        us_close_only: bool,
+    },
+    /// Print the current price of one asset.
+    Quote {
+        category: String,
+        symbol: String,
+        json: bool,
+    },
+    /// Print the current portfolio value in USD and `currency` (default: each
+    /// configured display currency).
+    Total {
+        currency: Option<String>,
+        json: bool,
+    },
+    /// Print (or write to `output`) the recorded snapshot history.
+    HistoryExport {
+        format: ExportFormat,
+        output: Option<String>,
+    },
+    /// Run the historical back-fill once and persist it.
+    Backfill,
+    /// Write the realized-gains report for the ledger.
+    Gains {
+        method: LotMethod,
+        output: Option<String>,
+    },
+    /// Download the Pyth feed catalog from Hermes at `url` (default: the
+    /// public endpoint) into the data dir.
+    FeedsSync { url: Option<String> },
+    /// Download TWSE's holiday schedule from `url` (default: the TWSE OpenAPI
+    /// endpoint) into the data dir.
+    CalendarSync { url: Option<String> },
+    /// Print usage.
+    Help,
+}
+
+/// Parse the arguments after the program name.
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let mut positional: Vec<&str> = Vec::new();
+    let mut flags: HashMap<&str, Option<&str>> = HashMap::new();
+    // Flags in the order given, so the first one a command rejects is reported.
+    let mut given: Vec<&str> = Vec::new();
+
+    let mut iter = args.iter().map(String::as_str);
+    while let Some(arg) = iter.next() {
+        match arg {
+            "--json" | "--reject-stale" | "--us-close-only" => {
+                flags.insert(arg, None);
+                given.push(arg);
+            }
+            "--currency" | "--format" | "--output" | "--method" | "--serve" | "--url" => {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("{} requires a value", arg))?;
+                flags.insert(arg, Some(value));
+                given.push(arg);
+            }
+            "-h" | "--help" => return Ok(Command::Help),
+            _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    if let Some((name, allowed)) = command_flags(&positional)
+        && let Some(flag) = given.iter().find(|flag| !allowed.contains(flag))
+    {
+        return Err(format!("Unknown option for {}: {}", name, flag));
+    }
+
+    let json = flags.contains_key("--json");
+    let reject_stale = flags.contains_key("--reject-stale");
+    let us_close_only = flags.contains_key("--us-close-only");
+    let value = |name: &str| flags.get(name).copied().flatten().map(String::from);
+    let serve = value("--serve")
+        .map(|addr| server::parse_bind_addr(&addr))
+        .transpose()?;
+
+    let command = match positional.as_slice() {
+        [] | ["tui"] => Command::Tui {
+            serve,
+            reject_stale,
+            us_close_only,
+        },
+        ["daemon"] => Command::Daemon {
+            serve,
+            reject_stale,
+            us_close_only,
+        },
+        ["help"] => Command::Help,
+        ["quote", category, symbol] => Command::Quote {
+            category: category.to_string(),
+            symbol: symbol.to_string(),
+            json,
+        },
+        ["quote", ..] => return Err("quote expects <category> <symbol>".to_string()),
+        ["total"] => Command::Total {
+            currency: value("--currency").map(|c| c.to_uppercase()),
+            json,
+        },
+        ["history", "export"] => Command::HistoryExport {
+            format: match value("--format").as_deref() {
+                None | Some("csv") => ExportFormat::Csv,
+                Some("json") => ExportFormat::Json,
+                Some(other) => return Err(format!("Unknown format: {}", other)),
+            },
+            output: value("--output"),
+        },
+        ["backfill"] => Command::Backfill,
+        ["gains"] => Command::Gains {
+            method: match value("--method") {
+                Some(m) => m.parse()?,
+                None => LotMethod::Fifo,
+            },
+            output: value("--output"),
+        },
+        ["feeds", "sync"] => Command::FeedsSync {
+            url: value("--url"),
+        },
+        ["calendar", "sync"] => Command::CalendarSync {
+            url: value("--url"),
+        },
+        other => return Err(format!("Unknown command: {}", other.join(" "))),
+    };
+
+    Ok(command)
+}
+
+/// The name of the subcommand `positional` selects and the options it takes,
+/// or `None` if it names no command (reported by [`parse_args`]).
+fn command_flags(positional: &[&str]) -> Option<(&'static str, &'static [&'static str])> {
+    let live: &[&str] = &["--serve", "--reject-stale", "--us-close-only"];
+    Some(match positional {
+        [] | ["tui"] => ("tui", live),
+        ["daemon"] => ("daemon", live),
+        ["help"] => ("help", &[]),
+        ["quote", ..] => ("quote", &["--json"]),
+        ["total"] => ("total", &["--currency", "--json"]),
+        ["history", "export"] => ("history export", &["--format", "--output"]),
+        ["backfill"] => ("backfill", &[]),
+        ["gains"] => ("gains", &["--method", "--output"]),
+        ["feeds", "sync"] => ("feeds sync", &["--url"]),
+        ["calendar", "sync"] => ("calendar sync", &["--url"]),
+        _ => return None,
+    })
+}
+
+/// Run a non-TUI command to completion.
+pub async fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Tui { .. } | Command::Daemon { .. } => {
+            Err("the TUI and daemon are started by main, not cli::run".to_string())
+        }
+        Command::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Command::Quote {
+            category,
+            symbol,
+            json,
+        } => quote(&category, &symbol, json).await,
+        Command::Total { currency, json } => total(currency, json).await,
+        Command::HistoryExport { format, output } => export_history(format, output),
+        Command::Backfill => backfill().await,
+        Command::Gains { method, output } => realized_gains(method, output).await,
+        Command::FeedsSync { url } => feeds_sync(url).await,
+        Command::CalendarSync { url } => calendar_sync(url).await,
+    }
+}
+
+async fn quote(category: &str, symbol: &str, json: bool) -> Result<(), String> {
+    let price = get_live_price(symbol, category).await?;
+    if json {
+        println!(
+            "{}",
+            json!({ "category": category, "symbol": symbol, "price": price })
+        );
+    } else {
+        println!("{}", price);
+    }
+    Ok(())
+}
+
+fn load_holdings() -> Result<(Portfolio, Option<crate::ledger::Ledger>), String> {
+    config::read_holdings(&paths::portfolio_file(), &paths::transactions_file())
+}
+
+/// Fetch every price needed to value `portfolio` in USD and in `currencies`, in
+/// parallel. Prices that cannot be fetched are reported on stderr and left out,
+/// so the affected holdings count as zero (as they would in the TUI).
+async fn fetch_price_map(portfolio: &Portfolio, currencies: &[String]) -> HashMap<String, f64> {
+    // (price-map key, symbol, category)
+    let mut requests: Vec<(String, String, String)> = Vec::new();
+    for item in portfolio.iter() {
+        if let Some(key) = valuation::price_key(item) {
+            requests.push((key.to_string(), item.symbol.clone(), item.category.clone()));
+        }
+    }
+    for pair in stream::required_forex_pairs(portfolio, currencies) {
+        let ccy = pair.trim_start_matches("USD/").to_string();
+        requests.push((pair, ccy, "Forex".to_string()));
+    }
+    requests.sort();
+    requests.dedup();
+
+    let results = join_all(
+        requests
+            .into_iter()
+            .map(|(key, symbol, category)| async move {
+                let result = get_live_price(&symbol, &category).await;
+                (key, result)
+            }),
+    )
+    .await;
+
+    let mut map = HashMap::new();
+    for (key, result) in results {
+        match result {
+            Ok(price) => {
+                map.insert(key, price);
+            }
+            Err(e) => eprintln!("warning: no price for {}: {}", key, e),
+        }
+    }
+    map
+}
+
+async fn total(currency: Option<String>, json: bool) -> Result<(), String> {
+    let (portfolio, _) = load_holdings()?;
+    let currencies = match currency {
+        Some(currency) => vec![currency],
+        None => config::read_target_forex_or_default(&paths::target_forex_file()),
+    };
+
+    let map = fetch_price_map(&portfolio, &currencies).await;
+    let (categories, total_usd) = history::compute_category_values(&portfolio, &map);
+    let converted: Vec<(&str, Option<Decimal>)> = currencies
+        .iter()
+        .map(|currency| {
+            let value = valuation::from_usd(total_usd, currency, &map);
+            (currency.as_str(), value)
+        })
+        .collect();
+
+    if json {
+        // `currency` / `total` are the first display currency; `totals` has
+        // every one.
+        println!(
+            "{}",
+            json!({
+                "total_usd": total_usd,
+                "currency": converted[0].0,
+                "total": converted[0].1,
+                "totals": converted.iter().copied().collect::<HashMap<_, _>>(),
+                "categories": categories,
+            })
+        );
+    } else {
+        let mut categories: Vec<_> = categories.into_iter().collect();
+        categories.sort_by(|a, b| a.0.cmp(&b.0));
+        for (category, value) in categories {
+            println!("{}\t{:.2}", category, value);
+        }
+        println!("Total (USD)\t{:.2}", total_usd);
+        for (currency, value) in converted {
+            if currency == "USD" {
+                continue;
+            }
+            match value {
+                Some(value) => println!("Total ({})\t{:.2}", currency, value),
+                None => eprintln!("warning: no USD/{} rate available", currency),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn export_history(format: ExportFormat, output: Option<String>) -> Result<(), String> {
+    let snapshots = history::load_history(&paths::history_file());
+    let rendered = match format {
+        ExportFormat::Csv => history::to_csv(&snapshots),
+        ExportFormat::Json => serde_json::to_string_pretty(&snapshots)
+            .map_err(|e| format!("Failed to serialize history: {}", e))?,
+    };
+    match output {
+        Some(path) => write_output(&path, &rendered),
+        None => {
+            print!("{}", rendered);
+            Ok(())
+        }
+    }
+}
+
+fn write_output(path: &str, content: &str) -> Result<(), String> {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+    }
+    std::fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
+
+async fn backfill() -> Result<(), String> {
+    let (portfolio, ledger) = load_holdings()?;
+    let history: SharedHistory =
+        Arc::new(Mutex::new(history::load_history(&paths::history_file())));
+    let before = history.lock().await.len();
+    stream::backfill_history_task(history.clone(), portfolio, ledger).await;
+    let after = history.lock().await.len();
+    println!(
+        "{} snapshots in {} ({} new)",
+        after,
+        paths::history_file(),
+        after.saturating_sub(before)
+    );
+    Ok(())
+}
+
+async fn realized_gains(method: LotMethod, output: Option<String>) -> Result<(), String> {
+    let (_, ledger) = load_holdings()?;
+    let ledger = ledger.ok_or("No transactions.toml; realized gains need a ledger")?;
+    let path = output.unwrap_or_else(paths::realized_gains_csv_file);
+    let count = gains::export_report(&ledger, method, &path).await?;
+    println!("{} sales written to {}", count, path);
+    Ok(())
+}
+
+async fn feeds_sync(url: Option<String>) -> Result<(), String> {
+    let url = url.as_deref().unwrap_or(pyth::HERMES_URL);
+    let path = paths::pyth_feeds_file();
+    let count = pyth::sync_feed_catalog(url, &path).await?;
+    println!("{} Pyth feeds written to {}", count, path);
+    Ok(())
+}
+
+async fn calendar_sync(url: Option<String>) -> Result<(), String> {
+    let url = url.as_deref().unwrap_or(twse::HOLIDAY_SCHEDULE_URL);
+    let path = paths::twse_holidays_cache_file();
+    let count = twse::sync_holiday_schedule(url, &path).await?;
+    println!("{} TWSE closures written to {}", count, path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        parse_args(&args)
+    }
+
+    #[test]
+    fn no_arguments_runs_the_tui() {
+        let tui = Command::Tui {
+            serve: None,
+            reject_stale: false,
+            us_close_only: false,
+        };
+        assert_eq!(parse(&[]), Ok(tui.clone()));
+        assert_eq!(parse(&["tui"]), Ok(tui));
+    }
+
+    #[test]
+    fn quote_takes_category_and_symbol() {
+        assert_eq!(
+            parse(&["quote", "US-Stock", "AAPL", "--json"]),
+            Ok(Command::Quote {
+                category: "US-Stock".to_string(),
+                symbol: "AAPL".to_string(),
+                json: true,
+            })
+        );
+        assert!(parse(&["quote", "AAPL"]).is_err());
+    }
+
+    #[test]
+    fn total_currency_is_upper_cased() {
+        assert_eq!(
+            parse(&["total", "--currency", "twd"]),
+            Ok(Command::Total {
+                currency: Some("TWD".to_string()),
+                json: false,
+            })
+        );
+        assert!(parse(&["total", "--currency"]).is_err());
+    }
+
+    #[test]
+    fn history_export_defaults_to_csv_on_stdout() {
+        assert_eq!(
+            parse(&["history", "export"]),
+            Ok(Command::HistoryExport {
+                format: ExportFormat::Csv,
+                output: None,
+            })
+        );
+        assert_eq!(
+            parse(&[
+                "history", "export", "--format", "json", "--output", "h.json"
+            ]),
+            Ok(Command::HistoryExport {
+                format: ExportFormat::Json,
+                output: Some("h.json".to_string()),
+            })
+        );
+        assert!(parse(&["history", "export", "--format", "xml"]).is_err());
+    }
+
+    #[test]
+    fn gains_method_is_parsed() {
+        assert_eq!(
+            parse(&["gains", "--method", "lifo"]),
+            Ok(Command::Gains {
+                method: LotMethod::Lifo,
+                output: None,
+            })
+        );
+        assert!(parse(&["gains", "--method", "hifo"]).is_err());
+    }
+
+    #[test]
+    fn unknown_commands_and_options_are_rejected() {
+        assert!(parse(&["frobnicate"]).is_err());
+        assert!(parse(&["total", "--verbose"]).is_err());
+        assert_eq!(parse(&["backfill"]), Ok(Command::Backfill));
+        assert_eq!(
+            parse(&["daemon"]),
+            Ok(Command::Daemon {
+                serve: None,
+                reject_stale: false,
+                us_close_only: false
+            })
+        );
+        assert_eq!(
+            parse(&[
+                "daemon",
+                "--serve",
+                "7878",
+                "--reject-stale",
+                "--us-close-only"
+            ]),
+            Ok(Command::Daemon {
+                serve: Some("127.0.0.1:7878".parse().unwrap()),
+                reject_stale: true,
+                us_close_only: true
+            })
+        );
+        assert!(parse(&["--serve", "nowhere"]).is_err());
+        assert_eq!(
+            parse(&["feeds", "sync", "--url", "http://127.0.0.1:9"]),
+            Ok(Command::FeedsSync {
+                url: Some("http://127.0.0.1:9".to_string())
+            })
+        );
+        assert!(parse(&["feeds"]).is_err());
+        assert_eq!(
+            parse(&["calendar", "sync"]),
+            Ok(Command::CalendarSync { url: None })
+        );
+    }
+
+    #[test]
+    fn options_of_other_commands_are_rejected() {
+        assert_eq!(
+            parse(&["quote", "Crypto", "BTC", "--currency", "TWD"]),
+            Err("Unknown option for quote: --currency".to_string())
+        );
+        assert_eq!(
+            parse(&["total", "--serve", "80", "--method", "lifo"]),
+            Err("Unknown option for total: --serve".to_string())
+        );
+        assert_eq!(
+            parse(&["gains", "--us-close-only"]),
+            Err("Unknown option for gains: --us-close-only".to_string())
+        );
+        assert_eq!(
+            parse(&["--json"]),
+            Err("Unknown option for tui: --json".to_string())
+        );
+        assert!(parse(&["gains", "--method", "lifo", "--output", "g.csv"]).is_ok());
+    }
+}
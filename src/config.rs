@@ -1,10 +1,10 @@
 //! Reading and parsing of the TOML configuration files: portfolio holdings,
-//! API keys and the display (target) currency.
+//! API keys and the display (target) currencies.
 
 use std::collections::HashMap;
 use std::fs;
-use toml;
 
+use crate::ledger::{self, Ledger};
 use crate::types::{ApiKeys, Portfolio};
 
 /// Read and parse the portfolio TOML file at `path`.
@@ -27,6 +27,20 @@ pub fn try_read_portfolio(path: &str) -> Result<Portfolio, String> {
     toml::from_str(&content).map_err(|e| format!("Failed to parse TOML: {}", e))
 }
 
+/// Load the holdings: from the transaction ledger at `ledger_path` when it
+/// exists (returning the ledger too), otherwise from the portfolio file at
+/// `portfolio_path`. Returns an `Err` string if the file in use is unreadable
+/// or invalid.
+pub fn read_holdings(
+    portfolio_path: &str,
+    ledger_path: &str,
+) -> Result<(Portfolio, Option<Ledger>), String> {
+    match ledger::read_ledger(ledger_path)? {
+        Some(ledger) => Ok((ledger.portfolio(), Some(ledger))),
+        None => Ok((try_read_portfolio(portfolio_path)?, None)),
+    }
+}
+
 /// Read the API key TOML file at `path` into a `name -> key` map.
 ///
 /// A missing file is not an error: it yields an empty map. Returns an `Err`
@@ -44,33 +58,51 @@ pub fn read_api_keys(path: &str) -> Result<HashMap<String, String>, String> {
     Ok(keys.0)
 }
 
-/// Read the display currency from the `target` field of the TOML file at `path`.
+/// Read the display currencies from the TOML file at `path`: the `targets`
+/// list (`targets = ["TWD", "JPY"]`) or, for older files, the single `target`
+/// string. Codes are upper-cased and duplicates dropped, keeping file order.
 ///
-/// Returns an `Err` string if the file cannot be read or parsed, or if the
-/// `target` field is missing or not a string.
-pub fn read_target_forex(path: &str) -> Result<String, String> {
+/// Returns an `Err` string if the file cannot be read or parsed, or if neither
+/// field is present, a code is not a string, or the list is empty.
+pub fn read_target_forex(path: &str) -> Result<Vec<String>, String> {
     let content = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
     let value: toml::Value =
         toml::from_str(&content).map_err(|e| format!("Failed to parse TOML: {}", e))?;
-    value
-        .get("target")
-        .and_then(|v| v.as_str())
-        .map(String::from)
-        .ok_or_else(|| "Target field not found".to_string())
+    let codes: Vec<&toml::Value> = match (value.get("targets"), value.get("target")) {
+        (Some(toml::Value::Array(targets)), _) => targets.iter().collect(),
+        (Some(_), _) => return Err("targets must be a list of currency codes".to_string()),
+        (None, Some(target)) => vec![target],
+        (None, None) => return Err("Target field not found".to_string()),
+    };
+
+    let mut targets: Vec<String> = Vec::new();
+    for code in codes {
+        let code = code
+            .as_str()
+            .ok_or_else(|| format!("Currency code {} is not a string", code))?
+            .to_uppercase();
+        if !targets.contains(&code) {
+            targets.push(code);
+        }
+    }
+    if targets.is_empty() {
+        return Err("targets is empty".to_string());
+    }
+    Ok(targets)
 }
 
-/// Read the display currency, falling back to USD (with a diagnostic) when the
-/// file is missing or unparseable. Used for both the initial load and reloads.
-pub fn read_target_forex_or_default(path: &str) -> String {
+/// Read the display currencies, falling back to USD (with a diagnostic) when
+/// the file is missing or unusable. Used for both the initial load and reloads.
+pub fn read_target_forex_or_default(path: &str) -> Vec<String> {
     match read_target_forex(path) {
-        Ok(forex) => forex,
+        Ok(targets) => targets,
         Err(e) => {
             crate::log_line!(
                 "[config] {} not usable ({}); defaulting target forex to USD",
                 path,
                 e
             );
-            "USD".to_string()
+            vec!["USD".to_string()]
         }
     }
 }
@@ -89,6 +121,65 @@ mod tests {
         assert!(portfolio.get("Forex").is_some());
     }
 
+    #[test]
+    fn test_read_portfolio_cost_basis() {
+        let portfolio = read_portfolio("test/portfolio.toml");
+        let find = |symbol: &str| portfolio.iter().find(|i| i.symbol == symbol).unwrap();
+
+        // Flat `symbol = quantity` entries carry no cost basis.
+        let amd = find("amd");
+        assert_eq!(amd.quantity, 10.into());
+        assert!(amd.avg_cost.is_none());
+
+        let qqq = find("QQQ");
+        assert_eq!(qqq.quantity, 2.into());
+        assert_eq!(qqq.avg_cost, "420.5".parse().ok());
+        assert!(qqq.cost_currency.is_none());
+
+        let tsmc = find("2330");
+        assert_eq!(tsmc.avg_cost, Some(580.into()));
+        assert_eq!(tsmc.cost_currency.as_deref(), Some("TWD"));
+    }
+
+    #[test]
+    fn test_portfolio_rejects_unknown_holding_fields() {
+        let err = toml::from_str::<Portfolio>("[US-Stock]\namd = { quantity = 1, cost = 2 }");
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_portfolio_nests_exchange_listings() {
+        let portfolio = toml::from_str::<Portfolio>(
+            "[Stock.JP]\n7203 = 100\n\n[Stock.UK]\nSHEL = { quantity = 5, avg_cost = 26 }",
+        )
+        .unwrap();
+        let toyota = portfolio.get("Stock.JP").unwrap();
+        assert_eq!(toyota[0].symbol, "7203");
+        assert_eq!(toyota[0].quantity, 100.into());
+        assert_eq!(
+            portfolio.get("Stock.UK").unwrap()[0].avg_cost,
+            Some(26.into())
+        );
+
+        // Only `[Stock]` nests, and it needs an exchange.
+        assert!(toml::from_str::<Portfolio>("[Stock]\nAAPL = 1").is_err());
+        assert!(toml::from_str::<Portfolio>("[Stock.XX]\nAAPL = 1").is_err());
+        assert!(toml::from_str::<Portfolio>("[US-Stock.JP]\nAAPL = 1").is_err());
+    }
+
+    #[test]
+    fn test_read_holdings_prefers_ledger() {
+        let (portfolio, ledger) =
+            read_holdings("test/portfolio.toml", "test/transactions.toml").unwrap();
+        assert!(ledger.is_some());
+        assert!(portfolio.get("US-Stock").is_none()); // only in portfolio.toml
+
+        let (portfolio, ledger) =
+            read_holdings("test/portfolio.toml", "test/does_not_exist.toml").unwrap();
+        assert!(ledger.is_none());
+        assert!(portfolio.get("US-Stock").is_some());
+    }
+
     #[test]
     fn test_read_api_keys() {
         let api_keys = read_api_keys("test/api_key.toml").unwrap();
@@ -98,6 +189,23 @@ mod tests {
     #[test]
     fn test_read_target_forex() {
         let target = read_target_forex("test/target_forex.toml").unwrap();
-        assert_eq!(target, "TWD");
+        assert_eq!(target, vec!["TWD"]);
+    }
+
+    #[test]
+    fn test_read_target_forex_list() {
+        let dir = std::env::temp_dir().join(format!("price_targets_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("target_forex.toml");
+        let path = path.to_str().unwrap();
+
+        fs::write(path, "targets = [\"twd\", \"JPY\", \"EUR\", \"TWD\"]\n").unwrap();
+        assert_eq!(read_target_forex(path).unwrap(), vec!["TWD", "JPY", "EUR"]);
+
+        fs::write(path, "targets = []\n").unwrap();
+        assert!(read_target_forex(path).is_err());
+        assert_eq!(read_target_forex_or_default(path), vec!["USD"]);
+
+        let _ = fs::remove_dir_all(&dir);
     }
 }
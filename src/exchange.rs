@@ -0,0 +1,126 @@
+//! Exchange-suffixed equity categories for listings outside the US and
+//! Taiwan: `Stock.JP` (Tokyo), `Stock.HK` (Hong Kong), `Stock.UK` (London),
+//! and so on.
+//!
+//! One table describes every supported exchange: the suffix Yahoo Finance
+//! expects on its tickers and the currency its listings trade in. Quotes and
+//! history come from Yahoo, and holdings are valued through the matching
+//! `USD/{ccy}` forex stream, so adding a market is a one-line table entry.
+//!
+//! ```toml
+//! [Stock.JP]
+//! 7203 = 100    # Toyota, in JPY
+//!
+//! [Stock.UK]
+//! SHEL = 50     # Shell, in GBP
+//! ```
+
+/// Categories of the form `Stock.{code}` are exchange-listed equities.
+pub const CATEGORY_PREFIX: &str = "Stock.";
+
+/// A stock exchange reachable through an exchange-suffixed category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exchange {
+    /// The code after [`CATEGORY_PREFIX`] in the category name.
+    pub code: &'static str,
+    /// Appended to the symbol to form the Yahoo Finance ticker.
+    pub yahoo_suffix: &'static str,
+    /// The currency listings trade in.
+    pub currency: &'static str,
+    /// Prices are quoted in hundredths of `currency` (London quotes pence).
+    pub quoted_in_minor_unit: bool,
+}
+
+impl Exchange {
+    /// The Yahoo Finance ticker for `symbol` on this exchange. Hong Kong
+    /// codes are zero-padded to four digits (`700` -> `0700.HK`).
+    pub fn yahoo_symbol(&self, symbol: &str) -> String {
+        let symbol = symbol.to_uppercase();
+        if self.code == "HK" && symbol.bytes().all(|b| b.is_ascii_digit()) {
+            format!("{:0>4}{}", symbol, self.yahoo_suffix)
+        } else {
+            format!("{}{}", symbol, self.yahoo_suffix)
+        }
+    }
+
+    /// Convert a price as quoted on this exchange into `currency` units.
+    pub fn to_currency_units(&self, quoted: f64) -> f64 {
+        if self.quoted_in_minor_unit {
+            quoted / 100.0
+        } else {
+            quoted
+        }
+    }
+}
+
+const fn exchange(
+    code: &'static str,
+    yahoo_suffix: &'static str,
+    currency: &'static str,
+) -> Exchange {
+    Exchange {
+        code,
+        yahoo_suffix,
+        currency,
+        quoted_in_minor_unit: false,
+    }
+}
+
+/// Every supported exchange.
+pub const EXCHANGES: &[Exchange] = &[
+    exchange("JP", ".T", "JPY"),
+    exchange("HK", ".HK", "HKD"),
+    Exchange {
+        quoted_in_minor_unit: true,
+        ..exchange("UK", ".L", "GBP")
+    },
+    exchange("DE", ".DE", "EUR"),
+    exchange("FR", ".PA", "EUR"),
+    exchange("NL", ".AS", "EUR"),
+    exchange("CH", ".SW", "CHF"),
+    exchange("CA", ".TO", "CAD"),
+    exchange("AU", ".AX", "AUD"),
+    exchange("KR", ".KS", "KRW"),
+    exchange("SG", ".SI", "SGD"),
+    exchange("IN", ".NS", "INR"),
+    exchange("CN", ".SS", "CNY"),
+];
+
+/// Whether `category` names an exchange-suffixed equity category, known or
+/// not.
+pub fn is_exchange_category(category: &str) -> bool {
+    category.starts_with(CATEGORY_PREFIX)
+}
+
+/// The exchange for a `Stock.{code}` category (case-insensitive code), or
+/// `None` for any other category or an unsupported code.
+pub fn for_category(category: &str) -> Option<&'static Exchange> {
+    let code = category.strip_prefix(CATEGORY_PREFIX)?;
+    EXCHANGES
+        .iter()
+        .find(|exchange| exchange.code.eq_ignore_ascii_case(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exchange_categories_map_to_suffix_and_currency() {
+        let tokyo = for_category("Stock.JP").unwrap();
+        assert_eq!(tokyo.yahoo_symbol("7203"), "7203.T");
+        assert_eq!(tokyo.currency, "JPY");
+        assert_eq!(
+            for_category("Stock.hk").unwrap().yahoo_symbol("700"),
+            "0700.HK"
+        );
+
+        let london = for_category("Stock.UK").unwrap();
+        assert_eq!(london.yahoo_symbol("shel"), "SHEL.L");
+        assert_eq!(london.to_currency_units(2_650.0), 26.5);
+
+        assert!(for_category("Stock.XX").is_none());
+        assert!(is_exchange_category("Stock.XX"));
+        assert!(for_category("US-Stock").is_none());
+    }
+}
@@ -0,0 +1,209 @@
+//! How each `USD/{ccy}` rate the valuation needs is obtained.
+//!
+//! Pyth lists most currencies as `USD/{ccy}`, but a few majors only as
+//! `{ccy}/USD` (`EUR/USD`, `GBP/USD`, ...) and some not at all. [`resolve`]
+//! picks the first route that works, in order:
+//!
+//! 1. **direct**: stream the `USD/{ccy}` feed;
+//! 2. **inverse**: stream `{ccy}/USD` and invert it;
+//! 3. **cross**: triangulate through one of [`CROSS_CURRENCIES`], multiplying a
+//!    USD leg (`USD/{via}`, or `{via}/USD` inverted) by a `{via}/{ccy}` leg
+//!    (or `{ccy}/{via}` inverted);
+//! 4. **REST**: poll ExchangeRate-API ([`crate::api::exchangerate::get_rate`],
+//!    which needs `exchangerate_api_key` in `api_key.toml`).
+//!
+//! Legs are streamed into the price map under their own pair (`EUR/USD`) and
+//! the derived rate is written under `USD/{ccy}`, so valuation never needs to
+//! know which route was taken.
+
+use std::fmt;
+
+use rust_decimal::Decimal;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+
+use crate::api::pyth;
+use crate::types::PriceMap;
+use crate::valuation::BASE_CURRENCY;
+
+/// Currencies a missing rate may be triangulated through, in order of
+/// preference.
+pub const CROSS_CURRENCIES: &[&str] = &["EUR", "GBP", "JPY"];
+
+/// Source recorded for rates polled from ExchangeRate-API.
+pub const REST_SOURCE: &str = "exchangerate";
+/// How often a rate on the REST route is polled (seconds). ExchangeRate-API
+/// refreshes its rates at most hourly, so polling faster only spends quota.
+pub const REST_POLL_SECS: u64 = 3_600;
+/// Age after which a REST-polled rate is stale (seconds): two missed polls.
+pub const REST_STALE_SECS: i64 = 2 * REST_POLL_SECS as i64;
+
+/// One streamed feed a rate is derived from: the pair it is quoted as and
+/// whether its price has to be inverted to point away from USD.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Leg {
+    /// The Pyth pair, also the leg's price-map key (`EUR/USD`).
+    pub pair: String,
+    /// The rate is `1 / price` rather than `price`.
+    pub inverted: bool,
+}
+
+impl Leg {
+    fn new(base: &str, quote: &str, inverted: bool) -> Self {
+        Leg {
+            pair: format!("{}/{}", base, quote),
+            inverted,
+        }
+    }
+}
+
+/// The route a `USD/{ccy}` rate is obtained through; see the module docs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FxRoute {
+    /// The `USD/{ccy}` feed.
+    Direct,
+    /// The `{ccy}/USD` feed, inverted.
+    Inverse,
+    /// The USD leg times the `via` leg.
+    Cross { via: String, legs: [Leg; 2] },
+    /// Polled from ExchangeRate-API.
+    Rest,
+}
+
+impl FxRoute {
+    /// The feeds the `USD/{currency}` rate is derived from; empty for
+    /// [`FxRoute::Rest`].
+    pub fn legs(&self, currency: &str) -> Vec<Leg> {
+        let currency = currency.to_uppercase();
+        match self {
+            FxRoute::Direct => vec![Leg::new(BASE_CURRENCY, &currency, false)],
+            FxRoute::Inverse => vec![Leg::new(&currency, BASE_CURRENCY, true)],
+            FxRoute::Cross { legs, .. } => legs.to_vec(),
+            FxRoute::Rest => Vec::new(),
+        }
+    }
+
+    /// The `source` recorded for rates obtained through this route.
+    pub fn source(&self) -> &'static str {
+        match self {
+            FxRoute::Direct => "pyth",
+            FxRoute::Inverse => "pyth-inverse",
+            FxRoute::Cross { .. } => "pyth-cross",
+            FxRoute::Rest => REST_SOURCE,
+        }
+    }
+}
+
+impl fmt::Display for FxRoute {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FxRoute::Direct => write!(f, "direct"),
+            FxRoute::Inverse => write!(f, "inverse"),
+            FxRoute::Cross { via, .. } => write!(f, "cross via {}", via),
+            FxRoute::Rest => write!(f, "ExchangeRate-API"),
+        }
+    }
+}
+
+/// Whether Pyth has a feed for `pair` (`EUR/USD`), bundled, synced or
+/// overridden.
+pub fn has_pyth_feed(pair: &str) -> bool {
+    pyth::get_pyth_feed_id(pair, "Forex").is_ok()
+}
+
+/// The first route that can produce the `USD/{currency}` rate, given which
+/// pairs `has_feed` can stream (normally [`has_pyth_feed`]).
+pub fn resolve(currency: &str, has_feed: impl Fn(&str) -> bool) -> FxRoute {
+    let currency = currency.to_uppercase();
+    // `pair` streamed as-is, or its reverse inverted.
+    let leg = |base: &str, quote: &str| {
+        if has_feed(&format!("{}/{}", base, quote)) {
+            Some(Leg::new(base, quote, false))
+        } else if has_feed(&format!("{}/{}", quote, base)) {
+            Some(Leg::new(quote, base, true))
+        } else {
+            None
+        }
+    };
+
+    if let Some(direct) = leg(BASE_CURRENCY, &currency) {
+        return if direct.inverted {
+            FxRoute::Inverse
+        } else {
+            FxRoute::Direct
+        };
+    }
+    for via in CROSS_CURRENCIES.iter().filter(|via| **via != currency) {
+        if let (Some(usd_leg), Some(via_leg)) = (leg(BASE_CURRENCY, via), leg(via, &currency)) {
+            return FxRoute::Cross {
+                via: via.to_string(),
+                legs: [usd_leg, via_leg],
+            };
+        }
+    }
+    FxRoute::Rest
+}
+
+/// The rate `legs` multiply out to, with the update time of the oldest leg so
+/// a stalled leg makes the derived rate stale too; `None` until every leg has
+/// a non-zero price.
+pub fn derive_rate(legs: &[Leg], prices: &PriceMap) -> Option<(f64, i64)> {
+    if legs.is_empty() {
+        return None;
+    }
+    let mut rate = Decimal::ONE;
+    let mut updated_at = i64::MAX;
+    for leg in legs {
+        let entry = prices.get(&leg.pair)?;
+        let price = Decimal::from_f64(entry.price).filter(|price| !price.is_zero())?;
+        rate = if leg.inverted {
+            rate.checked_div(price)?
+        } else {
+            rate.checked_mul(price)?
+        };
+        updated_at = updated_at.min(entry.updated_at);
+    }
+    Some((rate.to_f64()?, updated_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_prefers_direct_then_inverse_then_cross_then_rest() {
+        // The bundled table lists USD/TWD but only EUR/USD.
+        assert_eq!(resolve("twd", has_pyth_feed), FxRoute::Direct);
+        assert_eq!(resolve("EUR", has_pyth_feed), FxRoute::Inverse);
+
+        let feeds = ["EUR/USD", "GBP/USD", "GBP/XYZ", "ABC/EUR"];
+        let has_feed = |pair: &str| feeds.contains(&pair);
+        assert_eq!(
+            resolve("ABC", has_feed),
+            FxRoute::Cross {
+                via: "EUR".to_string(),
+                legs: [Leg::new("EUR", "USD", true), Leg::new("ABC", "EUR", true)],
+            }
+        );
+        assert_eq!(resolve("XYZ", has_feed).to_string(), "cross via GBP");
+        assert_eq!(resolve("QQQ", has_feed), FxRoute::Rest);
+        assert!(FxRoute::Rest.legs("QQQ").is_empty());
+    }
+
+    #[test]
+    fn test_derive_rate_multiplies_legs_from_the_oldest_update() {
+        let mut prices = PriceMap::default();
+        prices.insert("EUR/USD".to_string(), 1.25, "pyth", 100);
+        prices.insert("EUR/SEK".to_string(), 11.5, "pyth", 90);
+
+        let inverse = FxRoute::Inverse.legs("EUR");
+        assert_eq!(derive_rate(&inverse, &prices), Some((0.8, 100)));
+
+        // USD/SEK = USD/EUR x EUR/SEK = 0.8 x 11.5.
+        let cross = [Leg::new("EUR", "USD", true), Leg::new("EUR", "SEK", false)];
+        assert_eq!(derive_rate(&cross, &prices), Some((9.2, 90)));
+
+        prices.insert("EUR/USD".to_string(), 0.0, "pyth", 100);
+        assert!(derive_rate(&cross, &prices).is_none());
+        assert!(derive_rate(&[], &prices).is_none());
+    }
+}
@@ -0,0 +1,476 @@
+//! Realized gains: match each sale in the transaction [`Ledger`] against earlier
+//! buys (FIFO, LIFO or average cost) and report the profit or loss in the trade
+//! currency and in USD.
+//!
+//! USD figures convert each buy at the `USD/{ccy}` rate of its own date and the
+//! sale proceeds at the rate of the sale date, so currency moves between buying
+//! and selling show up in the USD gain. The matching itself ([`realized_gains`])
+//! is pure; [`fetch_fx_rates`] is the only part that touches the network.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::prelude::ToPrimitive;
+
+use crate::get::get_history;
+use crate::ledger::{Ledger, TransactionKind};
+
+/// How many days before the first trade to start fetching FX history, so a
+/// trade on a Monday still finds the preceding Friday's rate.
+const FX_LOOKBACK_DAYS: i64 = 10;
+
+/// Which acquired units a sale is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LotMethod {
+    /// Oldest lots are sold first.
+    Fifo,
+    /// Newest lots are sold first.
+    Lifo,
+    /// Every unit carries the pooled average cost.
+    AverageCost,
+}
+
+impl FromStr for LotMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fifo" => Ok(LotMethod::Fifo),
+            "lifo" => Ok(LotMethod::Lifo),
+            "average" | "avg" | "average-cost" => Ok(LotMethod::AverageCost),
+            other => Err(format!("Unknown lot method: {}", other)),
+        }
+    }
+}
+
+/// Historical `USD/{ccy}` rates by date, as units of `ccy` per USD.
+#[derive(Debug, Clone, Default)]
+pub struct FxRates(HashMap<String, BTreeMap<NaiveDate, f64>>);
+
+impl FxRates {
+    /// Record the `USD/{currency}` rate on `date`.
+    pub fn insert(&mut self, currency: &str, date: NaiveDate, rate: f64) {
+        self.0
+            .entry(currency.to_uppercase())
+            .or_default()
+            .insert(date, rate);
+    }
+
+    /// The most recent non-zero `USD/{currency}` rate on or before `date`.
+    /// USD is always 1.0.
+    pub fn usd_rate(&self, currency: &str, date: NaiveDate) -> Option<f64> {
+        if currency.eq_ignore_ascii_case("USD") {
+            return Some(1.0);
+        }
+        self.0
+            .get(&currency.to_uppercase())?
+            .range(..=date)
+            .rev()
+            .map(|(_, rate)| *rate)
+            .find(|rate| *rate != 0.0)
+    }
+
+    /// Convert `amount` of `currency` on `date` to USD.
+    fn to_usd(&self, amount: f64, currency: &str, date: NaiveDate) -> Option<f64> {
+        self.usd_rate(currency, date).map(|rate| amount / rate)
+    }
+}
+
+/// The outcome of one sale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealizedGain {
+    /// Sale date.
+    pub date: NaiveDate,
+    /// Asset category of the holding sold.
+    pub category: String,
+    /// Asset symbol sold.
+    pub symbol: String,
+    /// Units sold.
+    pub quantity: f64,
+    /// Trade currency of `proceeds` and `cost_basis`.
+    pub currency: String,
+    /// Sale value net of the sale fee.
+    pub proceeds: f64,
+    /// Cost of the units sold, including their share of buy fees.
+    pub cost_basis: f64,
+    /// `proceeds` in USD at the sale-date rate, if known.
+    pub proceeds_usd: Option<f64>,
+    /// `cost_basis` in USD at the buy-date rates, if all are known.
+    pub cost_basis_usd: Option<f64>,
+}
+
+impl RealizedGain {
+    /// Gain (positive) or loss (negative) in the trade currency.
+    pub fn gain(&self) -> f64 {
+        self.proceeds - self.cost_basis
+    }
+
+    /// Gain or loss in USD, if both sides could be converted.
+    pub fn gain_usd(&self) -> Option<f64> {
+        Some(self.proceeds_usd? - self.cost_basis_usd?)
+    }
+}
+
+/// Acquired units not yet sold. With [`LotMethod::AverageCost`] a holding has a
+/// single pooled lot.
+#[derive(Debug, Clone)]
+struct Lot {
+    quantity: f64,
+    cost: f64,
+    cost_usd: Option<f64>,
+}
+
+impl Lot {
+    /// Split off `quantity` units (at most the whole lot), returning their share
+    /// of the cost and leaving the rest in `self`.
+    fn take(&mut self, quantity: f64) -> Lot {
+        let share = if self.quantity > 0.0 {
+            (quantity / self.quantity).min(1.0)
+        } else {
+            1.0
+        };
+        let taken = Lot {
+            quantity: quantity.min(self.quantity),
+            cost: self.cost * share,
+            cost_usd: self.cost_usd.map(|c| c * share),
+        };
+        self.quantity -= taken.quantity;
+        self.cost -= taken.cost;
+        self.cost_usd = self.cost_usd.zip(taken.cost_usd).map(|(c, t)| c - t);
+        taken
+    }
+}
+
+/// Match every sale in `ledger` against earlier buys using `method`, converting
+/// to USD with `rates`. Sales are returned in ledger (date) order.
+///
+/// The ledger has already rejected sales larger than the holding, so every sale
+/// is fully matched.
+pub fn realized_gains(ledger: &Ledger, method: LotMethod, rates: &FxRates) -> Vec<RealizedGain> {
+    let mut lots: HashMap<(String, String), VecDeque<Lot>> = HashMap::new();
+    let mut gains = Vec::new();
+
+    for tx in ledger.transactions() {
+        // The report is kept in f64; ledger amounts convert here.
+        let (Some(category), Some(price), Some(quantity), Some(fee)) = (
+            &tx.category,
+            tx.price.and_then(|p| p.to_f64()),
+            tx.quantity.to_f64(),
+            tx.fee.to_f64(),
+        ) else {
+            continue;
+        };
+        let key = (category.clone(), tx.symbol.clone());
+        let currency = tx.currency();
+        let held = lots.entry(key).or_default();
+
+        match tx.kind {
+            TransactionKind::Buy => {
+                let cost = quantity * price + fee;
+                let lot = Lot {
+                    quantity,
+                    cost,
+                    cost_usd: rates.to_usd(cost, &currency, tx.date),
+                };
+                match (method, held.front_mut()) {
+                    (LotMethod::AverageCost, Some(pool)) => {
+                        pool.quantity += lot.quantity;
+                        pool.cost += lot.cost;
+                        pool.cost_usd = pool.cost_usd.zip(lot.cost_usd).map(|(a, b)| a + b);
+                    }
+                    _ => held.push_back(lot),
+                }
+            }
+            TransactionKind::Sell => {
+                let mut remaining = quantity;
+                let mut cost_basis = 0.0;
+                let mut cost_basis_usd = Some(0.0);
+
+                while remaining > 0.0 {
+                    let lot = match method {
+                        LotMethod::Lifo => held.back_mut(),
+                        LotMethod::Fifo | LotMethod::AverageCost => held.front_mut(),
+                    };
+                    let Some(lot) = lot else { break };
+                    let taken = lot.take(remaining);
+                    remaining -= taken.quantity;
+                    cost_basis += taken.cost;
+                    cost_basis_usd = cost_basis_usd.zip(taken.cost_usd).map(|(a, b)| a + b);
+
+                    if lot.quantity <= 1e-9 {
+                        match method {
+                            LotMethod::Lifo => held.pop_back(),
+                            LotMethod::Fifo | LotMethod::AverageCost => held.pop_front(),
+                        };
+                    }
+                    if taken.quantity <= 0.0 {
+                        break;
+                    }
+                }
+
+                let proceeds = quantity * price - fee;
+                gains.push(RealizedGain {
+                    date: tx.date,
+                    category: category.clone(),
+                    symbol: tx.symbol.clone(),
+                    quantity,
+                    proceeds_usd: rates.to_usd(proceeds, &currency, tx.date),
+                    currency,
+                    proceeds,
+                    cost_basis,
+                    cost_basis_usd,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    gains
+}
+
+/// Fetch the daily `USD/{ccy}` history for every non-USD trade currency in
+/// `ledger`, from shortly before its first trade until now. Currencies whose
+/// history cannot be fetched are logged and left out, so their USD figures
+/// come out empty rather than wrong.
+pub async fn fetch_fx_rates(ledger: &Ledger) -> FxRates {
+    let mut rates = FxRates::default();
+    let Some(first) = ledger.transactions().first() else {
+        return rates;
+    };
+    let from = first
+        .date
+        .and_hms_opt(0, 0, 0)
+        .map(|dt| dt.and_utc().timestamp())
+        .unwrap_or_default()
+        - FX_LOOKBACK_DAYS * 86_400;
+    let to = Utc::now().timestamp();
+
+    let mut currencies: Vec<String> = ledger
+        .transactions()
+        .iter()
+        .filter(|tx| matches!(tx.kind, TransactionKind::Buy | TransactionKind::Sell))
+        .map(|tx| tx.currency())
+        .filter(|ccy| ccy != "USD")
+        .collect();
+    currencies.sort();
+    currencies.dedup();
+
+    for ccy in currencies {
+        match get_history(&ccy, "Forex", from, to).await {
+            Ok(series) => {
+                for (ts, rate) in series {
+                    if let Some(dt) = DateTime::from_timestamp(ts, 0) {
+                        rates.insert(&ccy, dt.date_naive(), rate);
+                    }
+                }
+            }
+            Err(e) => crate::log_line!("[gains] USD/{} history failed: {}", ccy, e),
+        }
+    }
+
+    rates
+}
+
+/// Export realized gains as CSV (one row per sale) for tax reporting. USD
+/// columns are left empty when a rate was unavailable.
+pub fn export_csv(gains: &[RealizedGain], path: &str) -> Result<(), String> {
+    let usd = |v: Option<f64>| v.map(|v| format!("{:.4}", v)).unwrap_or_default();
+
+    let mut out = String::from(
+        "date,category,symbol,quantity,currency,proceeds,cost_basis,gain,proceeds_usd,cost_basis_usd,gain_usd\n",
+    );
+    for g in gains {
+        out.push_str(&format!(
+            "{},{},{},{},{},{:.4},{:.4},{:.4},{},{},{}\n",
+            g.date,
+            g.category,
+            g.symbol,
+            g.quantity,
+            g.currency,
+            g.proceeds,
+            g.cost_basis,
+            g.gain(),
+            usd(g.proceeds_usd),
+            usd(g.cost_basis_usd),
+            usd(g.gain_usd()),
+        ));
+    }
+
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
+    }
+    fs::write(path, out).map_err(|e| format!("Failed to write CSV: {}", e))
+}
+
+/// Fetch the FX history `ledger` needs, match its sales with `method` and write
+/// the report to `path`. Returns the number of sales written.
+pub async fn export_report(
+    ledger: &Ledger,
+    method: LotMethod,
+    path: &str,
+) -> Result<usize, String> {
+    let rates = fetch_fx_rates(ledger).await;
+    let gains = realized_gains(ledger, method, &rates);
+    export_csv(&gains, path)?;
+    Ok(gains.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::parse_ledger;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    /// Two buys at different prices, then one sale spanning part of both.
+    const TWO_LOTS: &str = r#"
+        [[transaction]]
+        date = 2024-01-10
+        kind = "buy"
+        category = "US-Stock"
+        symbol = "AAPL"
+        quantity = 10
+        price = 100
+
+        [[transaction]]
+        date = 2024-02-10
+        kind = "buy"
+        category = "US-Stock"
+        symbol = "AAPL"
+        quantity = 10
+        price = 200
+
+        [[transaction]]
+        date = 2024-03-10
+        kind = "sell"
+        category = "US-Stock"
+        symbol = "AAPL"
+        quantity = 15
+        price = 300
+        fee = 5
+    "#;
+
+    #[test]
+    fn fifo_sells_oldest_lots_first() {
+        let ledger = parse_ledger(TWO_LOTS).unwrap();
+        let gains = realized_gains(&ledger, LotMethod::Fifo, &FxRates::default());
+        assert_eq!(gains.len(), 1);
+        let g = &gains[0];
+        // 10 @ 100 + 5 @ 200 = 2000; proceeds 4500 - 5 fee.
+        assert!((g.cost_basis - 2000.0).abs() < 1e-9);
+        assert!((g.proceeds - 4495.0).abs() < 1e-9);
+        assert!((g.gain() - 2495.0).abs() < 1e-9);
+        // USD trades need no rates.
+        assert!((g.gain_usd().unwrap() - 2495.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lifo_sells_newest_lots_first() {
+        let ledger = parse_ledger(TWO_LOTS).unwrap();
+        let gains = realized_gains(&ledger, LotMethod::Lifo, &FxRates::default());
+        // 10 @ 200 + 5 @ 100 = 2500.
+        assert!((gains[0].cost_basis - 2500.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn average_cost_pools_all_lots() {
+        let ledger = parse_ledger(TWO_LOTS).unwrap();
+        let gains = realized_gains(&ledger, LotMethod::AverageCost, &FxRates::default());
+        // Average 150 per unit * 15.
+        assert!((gains[0].cost_basis - 2250.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn usd_gain_uses_rates_of_buy_and_sale_dates() {
+        let ledger = parse_ledger(
+            r#"
+            [[transaction]]
+            date = 2024-01-10
+            kind = "buy"
+            category = "TW-Stock"
+            symbol = "2330"
+            quantity = 1000
+            price = 600
+
+            [[transaction]]
+            date = 2024-06-10
+            kind = "sell"
+            category = "TW-Stock"
+            symbol = "2330"
+            quantity = 1000
+            price = 600
+            "#,
+        )
+        .unwrap();
+
+        let mut rates = FxRates::default();
+        // Friday before the buy; the buy (a Wednesday) falls back to it.
+        rates.insert("TWD", date("2024-01-05"), 30.0);
+        rates.insert("TWD", date("2024-06-10"), 32.0);
+
+        let g = &realized_gains(&ledger, LotMethod::Fifo, &rates)[0];
+        assert_eq!(g.currency, "TWD");
+        // Flat in TWD, but the TWD weakened: 600000/30 = 20000 -> 600000/32 = 18750.
+        assert!(g.gain().abs() < 1e-9);
+        assert!((g.cost_basis_usd.unwrap() - 20_000.0).abs() < 1e-6);
+        assert!((g.gain_usd().unwrap() + 1250.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn missing_rate_leaves_usd_empty() {
+        let ledger = parse_ledger(
+            r#"
+            [[transaction]]
+            date = 2024-01-10
+            kind = "buy"
+            category = "TW-ETF"
+            symbol = "0050"
+            quantity = 10
+            price = 100
+
+            [[transaction]]
+            date = 2024-01-11
+            kind = "sell"
+            category = "TW-ETF"
+            symbol = "0050"
+            quantity = 10
+            price = 110
+            "#,
+        )
+        .unwrap();
+        let g = &realized_gains(&ledger, LotMethod::Fifo, &FxRates::default())[0];
+        assert!((g.gain() - 100.0).abs() < 1e-9);
+        assert!(g.gain_usd().is_none());
+    }
+
+    #[test]
+    fn parse_lot_method() {
+        assert_eq!("FIFO".parse::<LotMethod>(), Ok(LotMethod::Fifo));
+        assert_eq!("lifo".parse::<LotMethod>(), Ok(LotMethod::Lifo));
+        assert_eq!("average".parse::<LotMethod>(), Ok(LotMethod::AverageCost));
+        assert!("hifo".parse::<LotMethod>().is_err());
+    }
+
+    #[test]
+    fn export_writes_one_row_per_sale() {
+        let ledger = parse_ledger(TWO_LOTS).unwrap();
+        let gains = realized_gains(&ledger, LotMethod::Fifo, &FxRates::default());
+
+        let dir = std::env::temp_dir().join(format!("price_gains_{}", std::process::id()));
+        let path = dir.join("realized_gains.csv");
+        export_csv(&gains, path.to_str().unwrap()).unwrap();
+
+        let csv = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("date,category,symbol"));
+        assert!(lines[1].starts_with("2024-03-10,US-Stock,AAPL,15,USD,4495.0000,2000.0000"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
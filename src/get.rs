@@ -1,116 +1,185 @@
 //! Category-aware price and history lookup that selects and falls back across
 //! the provider APIs.
 
-// use crate::api::alpha_vantage::get_price_from_alpha_vantage;
-// use crate::api::binance::get_price_from_binance;
-use crate::api::pyth::{get_history_from_pyth, pyth_tv_symbol};
-use crate::api::redstone::get_price_from_redstone;
-use crate::api::twse::get_price_from_twse;
-use crate::api::yahoo::{get_history_from_yahoo_range, get_price_from_yahoo};
-
-/// Try `primary`; on failure try `secondary`; if both fail return
-/// `Err(err_msg(symbol))`.
+use std::collections::HashMap;
+
+use futures::FutureExt;
+use futures::future::join_all;
+
+use crate::api::PriceSource;
+use crate::api::pyth::Pyth;
+use crate::api::twse::get_quotes_from_twse;
+use crate::metrics;
+use crate::sources::{self, Source};
+
+/// Try each source of `chain` in order and return the first price; if every
+/// one fails, return an `Err` listing each source's error.
 ///
-/// Accepting `AsyncFn` bounds lets callers (and tests) inject any async
-/// callable — a real API function, an async closure stub, or a spy — without
-/// touching the fallback logic.
-async fn get_price_with_fetchers(
+/// Being generic over [`PriceSource`] lets tests drive the fallback logic with
+/// stub sources instead of the network.
+async fn quote_from_chain<S: PriceSource>(
+    chain: &[S],
     symbol: &str,
-    primary: impl AsyncFn(&str) -> Result<f64, String>,
-    secondary: impl AsyncFn(&str) -> Result<f64, String>,
-    err_msg: impl Fn(&str) -> String,
+    category: &str,
 ) -> Result<f64, String> {
-    if let Ok(price) = primary(symbol).await {
-        return Ok(price);
+    if chain.is_empty() {
+        return Err(format!("No price sources enabled for {}", category));
     }
-    if let Ok(price) = secondary(symbol).await {
-        return Ok(price);
+    resume_chain(chain, 0, Vec::new(), symbol, category).await
+}
+
+/// Continue [`quote_from_chain`] at source `start`, given the `errors` of the
+/// sources before it (e.g. a failed batch request to the first one).
+async fn resume_chain<S: PriceSource>(
+    chain: &[S],
+    start: usize,
+    mut errors: Vec<String>,
+    symbol: &str,
+    category: &str,
+) -> Result<f64, String> {
+    for (i, source) in chain.iter().enumerate().skip(start) {
+        if i == 1 {
+            metrics::record_fetch_fallback(symbol);
+        }
+        match source.quote(symbol, category).await {
+            Ok(price) => return Ok(price),
+            Err(e) => errors.push(format!("{}: {}", source.name(), e)),
+        }
     }
-    Err(err_msg(symbol))
+    metrics::record_fetch_failure(symbol);
+    Err(format!(
+        "Failed to get {} price for {} ({})",
+        category,
+        symbol,
+        errors.join("; ")
+    ))
 }
 
-/// Fetch the current price of `symbol` for the given asset `category`.
-///
-/// `US-Stock`/`US-ETF` try RedStone then Yahoo; `TW-Stock`/`TW-ETF` try TWSE
-/// then Yahoo. Returns an `Err` string for an unknown category, for `Crypto`
-/// (currently disabled), or when every source for the category fails.
+/// Like [`quote_from_chain`], for daily history.
+async fn history_from_chain<S: PriceSource>(
+    chain: &[S],
+    symbol: &str,
+    category: &str,
+    from: i64,
+    to: i64,
+) -> Result<Vec<(i64, f64)>, String> {
+    if chain.is_empty() {
+        return Err(format!("No history sources enabled for {}", category));
+    }
+    let mut errors = Vec::new();
+    for source in chain {
+        match source.history(symbol, category, from, to).await {
+            Ok(series) => return Ok(series),
+            Err(e) => errors.push(format!("{}: {}", source.name(), e)),
+        }
+    }
+    Err(format!(
+        "Failed to get {} history for {} ({})",
+        category,
+        symbol,
+        errors.join("; ")
+    ))
+}
+
+/// Fetch the current price of `symbol` for the given asset `category` from the
+/// category's quote chain in `sources.toml` (by default RedStone then Binance
+/// for crypto, RedStone then Yahoo for US equities, TWSE then Yahoo for Taiwan
+/// and Yahoo for forex). Returns an `Err` string for an unknown category or
+/// when every source for the category fails.
 pub async fn get_price(symbol: &str, category: &str) -> Result<f64, String> {
-    match category {
-        /*
-        "crypto" => {
-            if let Ok(price) = get_price_from_pyth(symbol).await {
-                return Ok(price);
-            }
-            if let Ok(price) = get_price_from_redstone(symbol).await {
-                return Ok(price);
-            }
-            if let Ok(price) = get_price_from_binance(symbol).await {
-                return Ok(price);
+    let config = sources::current();
+    let chain: &[Source] = config
+        .quote_chain(category)
+        .ok_or_else(|| format!("Unknown asset category: {}", category))?;
+    quote_from_chain(chain, symbol, category).await
+}
+
+/// Fetch the current price of many `(symbol, category)` holdings at once,
+/// keyed by symbol.
+///
+/// Holdings whose quote chain starts with `twse` share batched MIS requests
+/// (see [`get_quotes_from_twse`]); a symbol the batch has no price for carries
+/// on down the rest of its chain alone. Every other holding goes through
+/// [`get_price`].
+pub async fn get_prices(holdings: &[(String, String)]) -> HashMap<String, Result<f64, String>> {
+    let config = sources::current();
+    let (batched, single): (Vec<_>, Vec<_>) =
+        holdings.iter().cloned().partition(|(_, category)| {
+            config
+                .quote_chain(category)
+                .is_some_and(|chain| chain.first() == Some(&Source::Twse))
+        });
+    let mut quotes = get_quotes_from_twse(&batched).await;
+
+    let config = &config;
+    let lookups = batched
+        .into_iter()
+        .map(|(symbol, category)| {
+            let quote = quotes
+                .remove(&symbol)
+                .unwrap_or_else(|| Err("[TWSE] Cannot find stock data".to_string()));
+            async move {
+                let result = match quote {
+                    Ok(quote) => Ok(quote.price),
+                    Err(e) => {
+                        let chain = config.quote_chain(&category).unwrap_or_default();
+                        let errors = vec![format!("{}: {}", Source::Twse.name(), e)];
+                        resume_chain(chain, 1, errors, &symbol, &category).await
+                    }
+                };
+                (symbol, result)
             }
-            if let Ok(price) = get_price_from_alpha_vantage(symbol).await {
-                return Ok(price);
+            .boxed()
+        })
+        .chain(single.into_iter().map(|(symbol, category)| {
+            async move {
+                let result = get_price(&symbol, &category).await;
+                (symbol, result)
             }
+            .boxed()
+        }));
+    join_all(lookups).await.into_iter().collect()
+}
 
-            return Err(format!(
-                "Failed to get crypto price (possibly due to API limit or invalid symbol: {})",
-                symbol
-            ));
-        } */
-        "US-Stock" | "US-ETF" => {
-            get_price_with_fetchers(
-                symbol,
-                get_price_from_redstone,
-                get_price_from_yahoo,
-                |s| format!(
-                    "Failed to get US stock price (possibly due to API limit or invalid symbol: {})",
-                    s
-                ),
-            )
-            .await
-        }
-
-        "TW-Stock" | "TW-ETF" => {
-            get_price_with_fetchers(
-                symbol,
-                get_price_from_twse,
-                get_price_from_yahoo,
-                |s| format!(
-                    "Failed to get Taiwan stock price (possibly due to API limit or invalid symbol: {})",
-                    s
-                ),
-            )
+/// Fetch the current price the way the live TUI sees it: the latest Pyth update
+/// for holdings the TUI streams from Pyth (crypto, US equities, forex,
+/// commodities), falling back to [`get_price`]; other categories go straight
+/// to [`get_price`].
+///
+/// For `Forex`, `symbol` is the currency code and the result is the `USD/{ccy}`
+/// rate, matching the price-map key the TUI uses.
+pub async fn get_live_price(symbol: &str, category: &str) -> Result<f64, String> {
+    if !matches!(
+        category,
+        "Crypto" | "US-Stock" | "US-ETF" | "Forex" | "Commodity"
+    ) {
+        return get_price(symbol, category).await;
+    }
+    match Pyth.quote(symbol, category).await {
+        Ok(price) => Ok(price),
+        Err(pyth_err) => get_price(symbol, category)
             .await
-        }
-
-        _ => Err(format!("Unknown asset category: {}", category)),
+            .map_err(|e| format!("{}; {}", pyth_err, e)),
     }
 }
 
 /// Fetch historical daily close prices for a holding between `from` and `to`
-/// (unix epoch seconds). Pyth Benchmarks is the primary source for crypto, US
-/// equities/ETFs and forex; Taiwan equities fall back to Yahoo since Pyth does
-/// not cover them. Returns `(timestamp, close)` pairs.
+/// (unix epoch seconds) from the category's history chain in `sources.toml`.
+/// By default Pyth Benchmarks serves crypto, US equities/ETFs, forex and
+/// commodities; Taiwan equities, which Pyth does not cover, come from TWSE's
+/// `STOCK_DAY` report with Yahoo as fallback (Yahoo alone for TPEx listings).
+/// Returns `(timestamp, close)` pairs.
 pub async fn get_history(
     symbol: &str,
     category: &str,
     from: i64,
     to: i64,
 ) -> Result<Vec<(i64, f64)>, String> {
-    match category {
-        "Crypto" | "US-Stock" | "US-ETF" | "Forex" => {
-            let tv_symbol = pyth_tv_symbol(symbol, category)
-                .ok_or_else(|| format!("No Pyth symbol mapping for {} ({})", symbol, category))?;
-            get_history_from_pyth(&tv_symbol, from, to).await
-        }
-
-        "TW-Stock" | "TW-ETF" => {
-            // Pyth has no Taiwan equities; Yahoo needs the .TW suffix.
-            let yahoo_symbol = format!("{}.TW", symbol);
-            get_history_from_yahoo_range(&yahoo_symbol, from, to, "1d").await
-        }
-
-        _ => Err(format!("Unknown asset category: {}", category)),
-    }
+    let config = sources::current();
+    let chain: &[Source] = config
+        .history_chain(category)
+        .ok_or_else(|| format!("Unknown asset category: {}", category))?;
+    history_from_chain(chain, symbol, category, from, to).await
 }
 
 #[cfg(test)]
@@ -132,10 +201,12 @@ mod tests {
             assert!(price > 0.0);
             let price = get_price("0050", "TW-ETF").await.unwrap();
             assert!(price > 0.0);
+            let price = get_price("6488", "TW-OTC").await.unwrap();
+            assert!(price > 0.0);
+            let price = get_price("eth", "Crypto").await.unwrap();
+            assert!(price > 0.0);
         }
 
-        let price = get_price("eth", "Crypto").await;
-        assert!(price.is_err()); // Crypto fetching is currently disabled
         let price = get_price("AAPL", "Unknown").await;
         assert!(price.is_err());
     }
@@ -168,85 +239,86 @@ mod tests {
 
     // --- Fallback-chain unit tests (deterministic, no network) ---
 
-    // US-Stock / US-ETF: Redstone → Yahoo
+    /// A scripted source: returns a fixed price, fails, or panics if called.
+    enum Stub {
+        Price(f64),
+        Fail,
+        Unreachable,
+    }
+
+    impl PriceSource for Stub {
+        fn name(&self) -> &'static str {
+            "stub"
+        }
+
+        async fn quote(&self, _symbol: &str, _category: &str) -> Result<f64, String> {
+            match self {
+                Stub::Price(price) => Ok(*price),
+                Stub::Fail => Err("unavailable".into()),
+                Stub::Unreachable => panic!("later sources must not be called"),
+            }
+        }
+    }
 
     #[tokio::test]
-    async fn test_us_primary_succeeds_secondary_not_called() {
-        let result = get_price_with_fetchers(
-            "AAPL",
-            async |_s| Ok::<f64, String>(150.0),
-            async |_s| -> Result<f64, String> {
-                panic!("secondary must not be called when primary succeeds")
-            },
-            |s| format!("error: {}", s),
-        )
-        .await;
+    async fn test_first_source_succeeds_rest_not_called() {
+        let chain = [Stub::Price(150.0), Stub::Unreachable, Stub::Unreachable];
+        let result = quote_from_chain(&chain, "AAPL", "US-Stock").await;
         assert_eq!(result, Ok(150.0));
     }
 
     #[tokio::test]
-    async fn test_us_primary_fails_secondary_called_and_succeeds() {
-        let result = get_price_with_fetchers(
-            "AAPL",
-            async |_s| -> Result<f64, String> { Err("redstone unavailable".into()) },
-            async |_s| Ok::<f64, String>(200.0),
-            |s| format!("error: {}", s),
-        )
-        .await;
+    async fn test_falls_through_to_first_working_source() {
+        let chain = [Stub::Fail, Stub::Price(200.0), Stub::Unreachable];
+        let result = quote_from_chain(&chain, "AAPL", "US-Stock").await;
         assert_eq!(result, Ok(200.0));
+
+        let chain = [Stub::Fail, Stub::Fail, Stub::Price(610.0)];
+        let result = quote_from_chain(&chain, "2330", "TW-Stock").await;
+        assert_eq!(result, Ok(610.0));
     }
 
     #[tokio::test]
-    async fn test_us_both_sources_fail_returns_err() {
-        let result = get_price_with_fetchers(
-            "AAPL",
-            async |_s| -> Result<f64, String> { Err("redstone unavailable".into()) },
-            async |_s| -> Result<f64, String> { Err("yahoo unavailable".into()) },
-            |s| format!("Failed to get US stock price for {}", s),
-        )
-        .await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("AAPL"));
+    async fn test_all_sources_fail_returns_err_with_each_error() {
+        let chain = [Stub::Fail, Stub::Fail, Stub::Fail];
+        let err = quote_from_chain(&chain, "2330", "TW-Stock")
+            .await
+            .unwrap_err();
+        assert!(err.contains("2330"));
+        assert_eq!(err.matches("stub: unavailable").count(), 3);
     }
 
-    // TW-Stock / TW-ETF: TWSE → Yahoo
-
     #[tokio::test]
-    async fn test_tw_primary_succeeds_secondary_not_called() {
-        let result = get_price_with_fetchers(
-            "2330",
-            async |_s| Ok::<f64, String>(600.0),
-            async |_s| -> Result<f64, String> {
-                panic!("secondary must not be called when primary succeeds")
-            },
-            |s| format!("error: {}", s),
-        )
-        .await;
-        assert_eq!(result, Ok(600.0));
+    async fn test_resume_chain_skips_batched_source_and_keeps_its_error() {
+        let chain = [Stub::Unreachable, Stub::Price(612.0)];
+        let errors = vec!["twse: timed out".to_string()];
+        let result = resume_chain(&chain, 1, errors.clone(), "2330", "TW-Stock").await;
+        assert_eq!(result, Ok(612.0));
+
+        let chain = [Stub::Unreachable];
+        let err = resume_chain(&chain, 1, errors, "2330", "TW-Stock")
+            .await
+            .unwrap_err();
+        assert!(err.contains("twse: timed out"));
     }
 
     #[tokio::test]
-    async fn test_tw_primary_fails_secondary_called_and_succeeds() {
-        let result = get_price_with_fetchers(
-            "2330",
-            async |_s| -> Result<f64, String> { Err("twse unavailable".into()) },
-            async |_s| Ok::<f64, String>(610.0),
-            |s| format!("error: {}", s),
-        )
-        .await;
-        assert_eq!(result, Ok(610.0));
+    async fn test_empty_chain_is_disabled() {
+        let err = quote_from_chain::<Stub>(&[], "eth", "Crypto")
+            .await
+            .unwrap_err();
+        assert!(err.contains("No price sources enabled for Crypto"));
+        let err = history_from_chain::<Stub>(&[], "eth", "Crypto", 0, 1)
+            .await
+            .unwrap_err();
+        assert!(err.contains("No history sources enabled"));
     }
 
     #[tokio::test]
-    async fn test_tw_both_sources_fail_returns_err() {
-        let result = get_price_with_fetchers(
-            "2330",
-            async |_s| -> Result<f64, String> { Err("twse unavailable".into()) },
-            async |_s| -> Result<f64, String> { Err("yahoo unavailable".into()) },
-            |s| format!("Failed to get Taiwan stock price for {}", s),
-        )
-        .await;
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("2330"));
+    async fn test_history_default_is_unsupported() {
+        let err = history_from_chain(&[Stub::Price(1.0)], "eth", "Crypto", 0, 1)
+            .await
+            .unwrap_err();
+        assert!(err.contains("history is not supported"));
     }
 }
@@ -1,102 +1,169 @@
 //! Portfolio snapshot history: computing category values, recording snapshots
 //! to the JSONL store, loading them back and exporting to CSV.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 
 use chrono::Utc;
-
-use crate::types::{Portfolio, PortfolioSnapshot};
+use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
+
+use crate::calendar;
+use crate::fx;
+use crate::types::{Portfolio, PortfolioItem, PortfolioSnapshot, PriceMap};
+use crate::valuation::{self, item_price_keys, item_usd_value};
+
+/// The bucket a category is reported under: Forex holdings are merged into a
+/// synthetic `Cash` category to match the live allocation display.
+fn category_bucket(category: &str) -> String {
+    if category == "Forex" {
+        "Cash".to_string()
+    } else {
+        category.to_string()
+    }
+}
 
 /// Compute the USD value of each portfolio category given a price map.
 ///
-/// This is the single source of truth for the "value an item in USD" logic and
-/// is reused by the live allocation view, the snapshot recorder and the
-/// historical back-fill. Forex holdings are merged into a synthetic `Cash`
+/// Each holding is valued by [`valuation::item_usd_value`]; this is reused by
+/// the live allocation view, the snapshot recorder and the historical
+/// back-fill. Forex holdings are merged into a synthetic `Cash`
 /// category to match the live allocation display.
 ///
-/// Returns `(category -> usd value, total usd value)`. Missing prices count as 0.
+/// Returns `(category -> usd value, total usd value)`, in exact decimal
+/// arithmetic. Missing prices count as 0, and so does a category whose value
+/// would overflow a `Decimal`, whether summing its holdings or adding it to the
+/// total.
 pub fn compute_category_values(
     portfolio: &Portfolio,
     map: &HashMap<String, f64>,
-) -> (HashMap<String, f64>, f64) {
-    let mut categories: HashMap<String, f64> = HashMap::new();
-    let mut total = 0.0;
-
-    for (category, items) in portfolio.group_by_category() {
-        let mut category_value = 0.0;
-
-        for item in &items {
-            let usd_value = if category == "TW-Stock" || category == "TW-ETF" {
-                match (map.get(&item.symbol), map.get("USD/TWD")) {
-                    (Some(price), Some(rate)) if *rate != 0.0 => price * item.quantity / rate,
-                    _ => 0.0,
-                }
-            } else if category == "Forex" {
-                if item.symbol == "USD" {
-                    item.quantity
-                } else {
-                    match map.get(&format!("USD/{}", item.symbol)) {
-                        Some(rate) if *rate != 0.0 => item.quantity / rate,
-                        _ => 0.0,
-                    }
-                }
-            } else {
-                // Crypto, US-Stock, US-ETF are already priced in USD.
-                map.get(&item.symbol)
-                    .map(|p| p * item.quantity)
-                    .unwrap_or(0.0)
-            };
-
-            category_value += usd_value;
+) -> (HashMap<String, Decimal>, Decimal) {
+    let mut categories: HashMap<String, Decimal> = HashMap::new();
+    let mut total = Decimal::ZERO;
+
+    // Sorted so the same category is dropped on every call if the total overflows.
+    let mut groups: Vec<_> = portfolio.group_by_category().into_iter().collect();
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+    for (category, items) in groups {
+        let Some(category_value) = items.iter().try_fold(Decimal::ZERO, |sum, item| {
+            sum.checked_add(item_usd_value(item, map).unwrap_or_default())
+        }) else {
+            continue;
+        };
+        if category_value <= Decimal::ZERO {
+            continue;
         }
 
-        if category_value > 0.0 {
-            // Merge cash-like holdings under a single "Cash" bucket.
-            let key = if category == "Forex" {
-                "Cash".to_string()
-            } else {
-                category
-            };
-            *categories.entry(key).or_insert(0.0) += category_value;
-            total += category_value;
+        let bucket = category_bucket(&category);
+        let bucket_value = categories
+            .get(&bucket)
+            .copied()
+            .unwrap_or_default()
+            .checked_add(category_value);
+        if let (Some(bucket_value), Some(new_total)) =
+            (bucket_value, total.checked_add(category_value))
+        {
+            categories.insert(bucket, bucket_value);
+            total = new_total;
         }
     }
 
     (categories, total)
 }
 
-/// The price-map keys required to fully value `portfolio`.
+/// Unrealized profit and loss of one or more holdings, valued in USD.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UnrealizedPnl {
+    /// What the holdings cost, converted to USD at the current rate.
+    pub cost_usd: Decimal,
+    /// What the same holdings are worth now, in USD.
+    pub value_usd: Decimal,
+}
+
+impl UnrealizedPnl {
+    /// Absolute gain (positive) or loss (negative) in USD.
+    pub fn amount(&self) -> Decimal {
+        self.value_usd - self.cost_usd
+    }
+
+    /// Gain or loss as a percentage of the cost basis (0 when there is no cost).
+    pub fn percent(&self) -> Decimal {
+        if self.cost_usd > Decimal::ZERO {
+            self.amount() / self.cost_usd * Decimal::ONE_HUNDRED
+        } else {
+            Decimal::ZERO
+        }
+    }
+}
+
+impl std::ops::AddAssign for UnrealizedPnl {
+    fn add_assign(&mut self, other: Self) {
+        self.cost_usd += other.cost_usd;
+        self.value_usd += other.value_usd;
+    }
+}
+
+/// Unrealized P&L of a single holding, or `None` if it has no `avg_cost` or a
+/// price/rate needed to value it is missing.
 ///
-/// `compute_category_values` silently treats a missing price as zero, so a
-/// snapshot built before every one of these keys is present would understate
-/// the total (and skew allocation ratios). Callers use [`is_complete`] to skip
-/// such partial snapshots instead of persisting the distortion.
-pub fn required_price_keys(portfolio: &Portfolio) -> Vec<String> {
-    let mut keys = Vec::new();
-    let mut needs_twd = false;
+/// The cost basis is converted to USD at the current rate, so for a holding
+/// whose cost and quote currency match, the percentage equals the local-currency
+/// return.
+pub fn holding_pnl(item: &PortfolioItem, map: &HashMap<String, f64>) -> Option<UnrealizedPnl> {
+    let avg_cost = item.avg_cost?;
+    let value_usd = item_usd_value(item, map)?;
+    let rate = valuation::usd_rate(map, &valuation::cost_currency(item))?;
+    Some(UnrealizedPnl {
+        cost_usd: (avg_cost * item.quantity).checked_div(rate)?,
+        value_usd,
+    })
+}
+
+/// Aggregate unrealized P&L per category (using the same buckets as
+/// [`compute_category_values`]) and for the whole portfolio.
+///
+/// Only holdings with a known cost basis and a current price contribute, so the
+/// percentages compare like with like. Categories without any such holding are
+/// absent from the map; the total is `None` if no holding qualifies.
+pub fn compute_unrealized_pnl(
+    portfolio: &Portfolio,
+    map: &HashMap<String, f64>,
+) -> (HashMap<String, UnrealizedPnl>, Option<UnrealizedPnl>) {
+    let mut categories: HashMap<String, UnrealizedPnl> = HashMap::new();
+    let mut total: Option<UnrealizedPnl> = None;
 
     for item in portfolio.iter() {
-        match item.category.as_str() {
-            "TW-Stock" | "TW-ETF" => {
-                keys.push(item.symbol.clone());
-                needs_twd = true; // Taiwan equities are priced in TWD.
-            }
-            "Forex" => {
-                if item.symbol != "USD" {
-                    keys.push(format!("USD/{}", item.symbol));
-                }
-            }
-            // Crypto, US-Stock, US-ETF are priced directly in USD.
-            _ => keys.push(item.symbol.clone()),
+        if let Some(pnl) = holding_pnl(item, map) {
+            *categories
+                .entry(category_bucket(&item.category))
+                .or_default() += pnl;
+            *total.get_or_insert_with(UnrealizedPnl::default) += pnl;
         }
     }
 
-    if needs_twd {
-        keys.push("USD/TWD".to_string());
+    (categories, total)
+}
+
+/// The category whose staleness threshold applies to `key` for `item`: the
+/// `USD/{ccy}` rates are forex feeds whatever holding needs them.
+fn key_category<'a>(key: &str, item: &'a PortfolioItem) -> &'a str {
+    if key.starts_with("USD/") {
+        "Forex"
+    } else {
+        &item.category
     }
+}
 
+/// The price-map keys required to fully value `portfolio`.
+///
+/// `compute_category_values` silently treats a missing price as zero, so a
+/// snapshot built before every one of these keys is present would understate
+/// the total (and skew allocation ratios). Callers use [`is_complete`] to skip
+/// such partial snapshots instead of persisting the distortion.
+pub fn required_price_keys(portfolio: &Portfolio) -> Vec<String> {
+    let mut keys: Vec<String> = portfolio.iter().flat_map(item_price_keys).collect();
     keys.sort();
     keys.dedup();
     keys
@@ -110,14 +177,119 @@ pub fn is_complete(portfolio: &Portfolio, map: &HashMap<String, f64>) -> bool {
         .all(|key| map.get(key).is_some_and(|v| *v != 0.0))
 }
 
+/// How old (seconds) a live price in `category` may get before it is flagged
+/// stale. Streamed feeds tick every few seconds while their market trades;
+/// Taiwan prices are polled, so they get more slack.
+pub fn stale_after_secs(category: &str) -> i64 {
+    match category {
+        "Crypto" => 60,
+        "Forex" | "US-Stock" | "US-ETF" | "Commodity" => 300,
+        _ => 900,
+    }
+}
+
+/// Whether the price under `key` is older than the threshold for `category` as
+/// of `now`. A missing price is not stale (it is incomplete instead).
+///
+/// A Taiwan price only ages while the TWSE is in session (see
+/// [`calendar::twse`]): the last quote of a session is the close and stays
+/// current through the night, weekends and holidays. A rate polled from
+/// ExchangeRate-API (see [`fx`]) ages against [`fx::REST_STALE_SECS`].
+pub fn is_stale(prices: &PriceMap, key: &str, category: &str, now: i64) -> bool {
+    let threshold = stale_after_secs(category);
+    match category {
+        "TW-Stock" | "TW-ETF" | "TW-OTC" => prices.get(key).is_some_and(|entry| {
+            calendar::twse().trading_secs_between(entry.updated_at, now) > threshold
+        }),
+        // A rate polled from ExchangeRate-API is only refreshed hourly.
+        "Forex" if prices.get(key).is_some_and(|e| e.source == fx::REST_SOURCE) => prices
+            .age(key, now)
+            .is_some_and(|age| age > fx::REST_STALE_SECS),
+        _ => prices.age(key, now).is_some_and(|age| age > threshold),
+    }
+}
+
+/// Seconds between the feed's publish time for the price under `key` and
+/// `now`, or `None` if the price has no publish time (non-Pyth sources).
+pub fn publish_lag(prices: &PriceMap, key: &str, now: i64) -> Option<i64> {
+    prices
+        .get(key)
+        .and_then(|entry| entry.publish_time)
+        .map(|published| (now - published).max(0))
+}
+
+/// Whether the price under `key` was published longer ago than the threshold
+/// for `category`, even if it was received recently. Unlike [`is_stale`] (no
+/// update arriving: a broken feed) this means the feed is alive but its market
+/// is not trading, e.g. a US equity outside the session.
+pub fn is_lagging(prices: &PriceMap, key: &str, category: &str, now: i64) -> bool {
+    publish_lag(prices, key, now).is_some_and(|lag| lag > stale_after_secs(category))
+}
+
+/// The keys needed to value `portfolio` whose price is stale as of `now`,
+/// sorted.
+pub fn stale_price_keys(portfolio: &Portfolio, prices: &PriceMap, now: i64) -> Vec<String> {
+    let mut keys: Vec<String> = portfolio
+        .iter()
+        .flat_map(|item| {
+            item_price_keys(item)
+                .into_iter()
+                .filter(|key| is_stale(prices, key, key_category(key, item), now))
+        })
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// The category buckets (as reported by [`compute_category_values`]) with at
+/// least one holding valued from a stale price as of `now`.
+pub fn stale_categories(portfolio: &Portfolio, prices: &PriceMap, now: i64) -> HashSet<String> {
+    portfolio
+        .iter()
+        .filter(|item| {
+            item_price_keys(item)
+                .iter()
+                .any(|key| is_stale(prices, key, key_category(key, item), now))
+        })
+        .map(|item| category_bucket(&item.category))
+        .collect()
+}
+
+/// [`is_complete`] for the live price map, additionally requiring (when
+/// `reject_stale` is set) that none of the needed prices is stale as of `now`,
+/// so a stalled feed cannot freeze its old value into the history.
+pub fn is_complete_live(
+    portfolio: &Portfolio,
+    prices: &PriceMap,
+    reject_stale: bool,
+    now: i64,
+) -> bool {
+    is_complete(portfolio, &prices.values())
+        && (!reject_stale || stale_price_keys(portfolio, prices, now).is_empty())
+}
+
 /// Build a snapshot of the portfolio from the current price map.
 pub fn take_snapshot(portfolio: &Portfolio, map: &HashMap<String, f64>) -> PortfolioSnapshot {
+    snapshot_at(Utc::now().timestamp(), portfolio, map)
+}
+
+/// Build a snapshot of the portfolio valued with `map` at `timestamp`. Prices
+/// that are not finite numbers are left out.
+pub fn snapshot_at(
+    timestamp: i64,
+    portfolio: &Portfolio,
+    map: &HashMap<String, f64>,
+) -> PortfolioSnapshot {
     let (category_values, total_value_usd) = compute_category_values(portfolio, map);
     PortfolioSnapshot {
-        timestamp: Utc::now().timestamp(),
+        timestamp,
         total_value_usd,
         category_values,
-        prices: map.clone(),
+        prices: map
+            .iter()
+            .filter_map(|(key, price)| Some((key.clone(), Decimal::from_f64(*price)?)))
+            .collect(),
     }
 }
 
@@ -208,7 +380,7 @@ pub fn merge_snapshots(
     let mut by_day: HashMap<i64, PortfolioSnapshot> = HashMap::new();
 
     // Incoming first so existing entries overwrite them on collision.
-    for snap in incoming.into_iter().chain(existing.into_iter()) {
+    for snap in incoming.into_iter().chain(existing) {
         let day = snap.timestamp.div_euclid(86_400);
         by_day.insert(day, snap);
     }
@@ -218,9 +390,9 @@ pub fn merge_snapshots(
     merged
 }
 
-/// Export the history as CSV (timestamp, total_value_usd, then one column per
+/// Render the history as CSV (timestamp, total_value_usd, then one column per
 /// category) for analysis in external tools.
-pub fn export_csv(history: &[PortfolioSnapshot], path: &str) -> Result<(), String> {
+pub fn to_csv(history: &[PortfolioSnapshot]) -> String {
     // Collect the union of category names for a stable header.
     let mut categories: Vec<String> = history
         .iter()
@@ -239,27 +411,41 @@ pub fn export_csv(history: &[PortfolioSnapshot], path: &str) -> Result<(), Strin
     for snap in history {
         out.push_str(&format!("{},{:.4}", snap.timestamp, snap.total_value_usd));
         for cat in &categories {
-            let v = snap.category_values.get(cat).copied().unwrap_or(0.0);
+            let v = snap.category_values.get(cat).copied().unwrap_or_default();
             out.push_str(&format!(",{:.4}", v));
         }
         out.push('\n');
     }
 
+    out
+}
+
+/// Export the history as CSV (see [`to_csv`]) to `path`.
+pub fn export_csv(history: &[PortfolioSnapshot], path: &str) -> Result<(), String> {
     if let Some(parent) = Path::new(path).parent() {
         fs::create_dir_all(parent).map_err(|e| format!("Failed to create dir: {}", e))?;
     }
-    fs::write(path, out).map_err(|e| format!("Failed to write CSV: {}", e))
+    fs::write(path, to_csv(history)).map_err(|e| format!("Failed to write CSV: {}", e))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_decimal::prelude::ToPrimitive;
 
-    fn item(symbol: &str, category: &str, quantity: f64) -> crate::types::PortfolioItem {
-        crate::types::PortfolioItem {
+    fn item(symbol: &str, category: &str, quantity: f64) -> PortfolioItem {
+        PortfolioItem {
             symbol: symbol.to_string(),
             category: category.to_string(),
-            quantity,
+            quantity: Decimal::from_f64(quantity).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn item_at_cost(symbol: &str, category: &str, quantity: f64, avg_cost: f64) -> PortfolioItem {
+        PortfolioItem {
+            avg_cost: Decimal::from_f64(avg_cost),
+            ..item(symbol, category, quantity)
         }
     }
 
@@ -278,11 +464,90 @@ mod tests {
         map.insert("USD/TWD".to_string(), 30.0); // -> 200 USD (stock), 100 USD (cash)
 
         let (cats, total) = compute_category_values(&portfolio, &map);
-        assert!((cats["US-Stock"] - 2000.0).abs() < 1e-6);
-        assert!((cats["TW-Stock"] - 200.0).abs() < 1e-6);
+        assert_eq!(cats["US-Stock"], Decimal::from(2000));
+        assert_eq!(cats["TW-Stock"], Decimal::from(200));
         // Cash = 100 USD + 3000 TWD / 30 = 100 + 100 = 200
-        assert!((cats["Cash"] - 200.0).abs() < 1e-6);
-        assert!((total - 2400.0).abs() < 1e-6);
+        assert_eq!(cats["Cash"], Decimal::from(200));
+        assert_eq!(total, Decimal::from(2400));
+    }
+
+    #[test]
+    fn test_commodity_valued_in_usd_with_its_own_bucket() {
+        let portfolio = Portfolio(vec![
+            item("XAU", "Commodity", 3.2),
+            item("AAPL", "US-Stock", 1.0),
+        ]);
+        let map = HashMap::from([("XAU".to_string(), 2_500.0), ("AAPL".to_string(), 200.0)]);
+
+        assert_eq!(required_price_keys(&portfolio), vec!["AAPL", "XAU"]);
+        let (cats, total) = compute_category_values(&portfolio, &map);
+        assert_eq!(cats["Commodity"], Decimal::from(8_000));
+        assert_eq!(total, Decimal::from(8_200));
+        assert_eq!(stale_after_secs("Commodity"), 300);
+    }
+
+    #[test]
+    fn test_exchange_listings_valued_in_their_currency() {
+        let portfolio = Portfolio(vec![item_at_cost("7203", "Stock.JP", 100.0, 2_500.0)]);
+        let mut map = HashMap::from([("7203".to_string(), 3_000.0)]);
+
+        assert_eq!(required_price_keys(&portfolio), vec!["7203", "USD/JPY"]);
+        assert!(item_usd_value(&portfolio.0[0], &map).is_none());
+
+        map.insert("USD/JPY".to_string(), 150.0); // 300,000 JPY = 2,000 USD
+        let (cats, total) = compute_category_values(&portfolio, &map);
+        assert_eq!(cats["Stock.JP"], Decimal::from(2_000));
+        assert_eq!(total, Decimal::from(2_000));
+        // The cost basis defaults to JPY as well: 250,000 JPY = 1,666.67 USD.
+        let pnl = holding_pnl(&portfolio.0[0], &map).unwrap();
+        assert_eq!(pnl.percent(), Decimal::from(20));
+    }
+
+    #[test]
+    fn test_unrealized_pnl_per_holding_category_and_total() {
+        let portfolio = Portfolio(vec![
+            item_at_cost("AAPL", "US-Stock", 10.0, 150.0), // cost 1500, value 2000
+            item_at_cost("2330", "TW-Stock", 10.0, 660.0), // cost 6600 TWD = 220 USD
+            item("MSFT", "US-Stock", 1.0),                 // no cost basis
+            item("USD", "Forex", 100.0),
+        ]);
+
+        let mut map = HashMap::new();
+        map.insert("AAPL".to_string(), 200.0);
+        map.insert("MSFT".to_string(), 400.0);
+        map.insert("2330".to_string(), 600.0); // value 6000 TWD = 200 USD
+        map.insert("USD/TWD".to_string(), 30.0);
+
+        let aapl = holding_pnl(&portfolio.0[0], &map).unwrap();
+        assert_eq!(aapl.amount(), Decimal::from(500));
+        assert!((aapl.percent().to_f64().unwrap() - 100.0 / 3.0).abs() < 1e-6);
+
+        let tsmc = holding_pnl(&portfolio.0[1], &map).unwrap();
+        assert_eq!(tsmc.amount(), Decimal::from(-20));
+        assert!((tsmc.percent().to_f64().unwrap() + 100.0 / 11.0).abs() < 1e-6);
+
+        let (cats, total) = compute_unrealized_pnl(&portfolio, &map);
+        // MSFT has no cost and is excluded rather than counted as pure gain.
+        assert_eq!(cats["US-Stock"].value_usd, Decimal::from(2000));
+        assert!(!cats.contains_key("Cash"));
+        let total = total.unwrap();
+        assert_eq!(total.cost_usd, Decimal::from(1720));
+        assert_eq!(total.amount(), Decimal::from(480));
+    }
+
+    #[test]
+    fn test_unrealized_pnl_needs_cost_currency_rate() {
+        let holding = PortfolioItem {
+            cost_currency: Some("EUR".to_string()),
+            ..item_at_cost("AAPL", "US-Stock", 1.0, 100.0)
+        };
+        let mut map = HashMap::new();
+        map.insert("AAPL".to_string(), 200.0);
+        assert!(holding_pnl(&holding, &map).is_none());
+
+        map.insert("USD/EUR".to_string(), 0.5); // 100 EUR = 200 USD
+        let pnl = holding_pnl(&holding, &map).unwrap();
+        assert!(pnl.amount().is_zero());
     }
 
     #[test]
@@ -308,6 +573,61 @@ mod tests {
         assert!(!is_complete(&portfolio, &map));
     }
 
+    #[test]
+    fn test_stale_prices_flagged_per_category() {
+        let portfolio = Portfolio(vec![
+            item("BTC", "Crypto", 1.0),
+            item("2330", "TW-Stock", 10.0),
+        ]);
+        let now = 10_000;
+        let mut prices = PriceMap::default();
+        prices.insert("BTC".to_string(), 60_000.0, "pyth", now - 120);
+        prices.insert("2330".to_string(), 600.0, "twse", now - 120);
+        prices.insert("USD/TWD".to_string(), 30.0, "pyth", now - 10);
+
+        // Two minutes is stale for a crypto stream, not for a polled TW price.
+        assert_eq!(stale_price_keys(&portfolio, &prices, now), vec!["BTC"]);
+        assert_eq!(
+            stale_categories(&portfolio, &prices, now),
+            HashSet::from(["Crypto".to_string()])
+        );
+        assert!(is_complete_live(&portfolio, &prices, false, now));
+        assert!(!is_complete_live(&portfolio, &prices, true, now));
+
+        prices.insert("BTC".to_string(), 61_000.0, "pyth", now);
+        assert!(is_complete_live(&portfolio, &prices, true, now));
+
+        // A stale rate taints every holding valued through it.
+        prices.insert("USD/TWD".to_string(), 30.0, "pyth", now - 3_600);
+        assert_eq!(stale_price_keys(&portfolio, &prices, now), vec!["USD/TWD"]);
+        assert!(stale_categories(&portfolio, &prices, now).contains("TW-Stock"));
+
+        // A rate on the hourly REST route is not stale after an hour.
+        prices.insert("USD/TWD".to_string(), 30.0, fx::REST_SOURCE, now - 3_600);
+        assert!(stale_price_keys(&portfolio, &prices, now).is_empty());
+    }
+
+    #[test]
+    fn test_taiwan_prices_only_age_in_session() {
+        use chrono::TimeZone;
+        use chrono_tz::Asia::Taipei;
+
+        let at = |day, hour| {
+            Taipei
+                .with_ymd_and_hms(2024, 1, day, hour, 0, 0)
+                .unwrap()
+                .timestamp()
+        };
+        let mut prices = PriceMap::default();
+        // The last poll of Friday 2024-01-19's session, at 13:25.
+        prices.insert("2330".to_string(), 600.0, "rest", at(19, 13) + 1_500);
+
+        assert!(!is_stale(&prices, "2330", "TW-Stock", at(22, 8)));
+        assert!(is_stale(&prices, "2330", "TW-Stock", at(22, 10)));
+        // A streamed feed that went quiet over the weekend is stale.
+        assert!(is_stale(&prices, "2330", "Crypto", at(22, 8)));
+    }
+
     #[test]
     fn test_roundtrip_persistence() {
         let dir = std::env::temp_dir().join(format!("price_hist_{}", std::process::id()));
@@ -315,18 +635,22 @@ mod tests {
         let path_str = path.to_str().unwrap();
         let _ = fs::remove_file(path_str);
 
+        let value: Decimal = "1234.5".parse().unwrap();
+        let price: Decimal = "123.45".parse().unwrap();
+        // More significant digits than an f64 can carry.
+        let fine: Decimal = "0.123456789012345678901".parse().unwrap();
         let snaps = vec![
             PortfolioSnapshot {
                 timestamp: 1_700_000_000,
-                total_value_usd: 1234.5,
-                category_values: HashMap::from([("US-Stock".to_string(), 1234.5)]),
-                prices: HashMap::from([("AAPL".to_string(), 123.45)]),
+                total_value_usd: value,
+                category_values: HashMap::from([("US-Stock".to_string(), value)]),
+                prices: HashMap::from([("AAPL".to_string(), price)]),
             },
             PortfolioSnapshot {
                 timestamp: 1_700_086_400,
-                total_value_usd: 1234.5,
-                category_values: HashMap::from([("US-Stock".to_string(), 1234.5)]),
-                prices: HashMap::from([("AAPL".to_string(), 123.45)]),
+                total_value_usd: value,
+                category_values: HashMap::from([("US-Stock".to_string(), value)]),
+                prices: HashMap::from([("AAPL".to_string(), price), ("PEPE".to_string(), fine)]),
             },
         ];
         save_all(path_str, &snaps).unwrap();
@@ -334,7 +658,19 @@ mod tests {
         let loaded = load_history(path_str);
         assert_eq!(loaded.len(), 2);
         assert_eq!(loaded[0].timestamp, 1_700_000_000);
-        assert!((loaded[1].total_value_usd - 1234.5).abs() < 1e-6);
+        assert_eq!(loaded[1].total_value_usd, value);
+        assert_eq!(loaded[1].prices["AAPL"], price);
+        assert_eq!(loaded[1].prices["PEPE"].serialize(), fine.serialize());
+        let written = fs::read_to_string(path_str).unwrap();
+        assert!(written.contains(r#""PEPE":"0.123456789012345678901""#));
+
+        // Files written before values were decimals load unchanged.
+        let legacy = r#"{"timestamp":1700172800,"total_value_usd":1234.5,"category_values":{"US-Stock":1234.5},"prices":{"AAPL":123.45}}"#;
+        fs::write(path_str, format!("{}\n", legacy)).unwrap();
+        let loaded = load_history(path_str);
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].total_value_usd, value);
+        assert_eq!(loaded[0].prices["AAPL"], price);
 
         let _ = fs::remove_dir_all(&dir);
     }
@@ -343,7 +679,7 @@ mod tests {
     fn test_downsample_thins_old_keeps_recent() {
         let mk = |ts: i64| PortfolioSnapshot {
             timestamp: ts,
-            total_value_usd: ts as f64, // unique marker per snapshot
+            total_value_usd: ts.into(), // unique marker per snapshot
             category_values: HashMap::new(),
             prices: HashMap::new(),
         };
@@ -353,11 +689,11 @@ mod tests {
         // Old day (day 2): one snapshot -> kept.
         // Recent (within 7 days of now): two snapshots -> both kept.
         let recent_a = now - 2 * 86_400;
-        let recent_b = now - 1 * 86_400 + 100;
+        let recent_b = now - 86_400 + 100;
         let input = vec![
-            mk(1 * 86_400 + 10),
-            mk(1 * 86_400 + 20),
-            mk(1 * 86_400 + 30),
+            mk(86_400 + 10),
+            mk(86_400 + 20),
+            mk(86_400 + 30),
             mk(2 * 86_400 + 5),
             mk(recent_b),
             mk(recent_a),
@@ -365,25 +701,22 @@ mod tests {
 
         let out = downsample(input, now);
         let ts: Vec<i64> = out.iter().map(|s| s.timestamp).collect();
-        assert_eq!(
-            ts,
-            vec![1 * 86_400 + 30, 2 * 86_400 + 5, recent_a, recent_b]
-        );
+        assert_eq!(ts, vec![86_400 + 30, 2 * 86_400 + 5, recent_a, recent_b]);
     }
 
     #[test]
     fn test_merge_dedupes_by_day() {
-        let mk = |ts: i64, v: f64| PortfolioSnapshot {
+        let mk = |ts: i64, v: i64| PortfolioSnapshot {
             timestamp: ts,
-            total_value_usd: v,
+            total_value_usd: v.into(),
             category_values: HashMap::new(),
             prices: HashMap::new(),
         };
         // Two snapshots on the same UTC day; existing should win.
-        let existing = vec![mk(1_700_000_000, 100.0)];
-        let incoming = vec![mk(1_700_003_600, 999.0), mk(1_700_200_000, 50.0)];
+        let existing = vec![mk(1_700_000_000, 100)];
+        let incoming = vec![mk(1_700_003_600, 999), mk(1_700_200_000, 50)];
         let merged = merge_snapshots(existing, incoming);
         assert_eq!(merged.len(), 2);
-        assert!((merged[0].total_value_usd - 100.0).abs() < 1e-6);
+        assert_eq!(merged[0].total_value_usd, Decimal::from(100));
     }
 }
@@ -0,0 +1,562 @@
+//! Transaction ledger: a dated record of buys, sells, deposits, withdrawals,
+//! dividends and fees from which the current (or any past) [`Portfolio`] is
+//! derived, instead of hand-editing quantities in `portfolio.toml`.
+//!
+//! The ledger lives in `<config dir>/transactions.toml` as an array of tables:
+//!
+//! ```toml
+//! [[transaction]]
+//! date = 2024-01-15
+//! kind = "buy"
+//! category = "US-Stock"
+//! symbol = "AAPL"
+//! quantity = 10
+//! price = 185.2
+//! fee = 1.0
+//!
+//! [[transaction]]
+//! date = 2024-02-01
+//! kind = "deposit"
+//! symbol = "TWD"
+//! quantity = 30000
+//! ```
+//!
+//! Every transaction moves exactly one holding:
+//!
+//! | kind         | holding moved                    | fields used                     |
+//! |--------------|----------------------------------|---------------------------------|
+//! | `buy`/`sell` | `category`/`symbol`              | `quantity`, `price`, `fee`      |
+//! | `deposit`    | Forex cash `symbol`              | `quantity`                      |
+//! | `withdrawal` | Forex cash `symbol`              | `quantity` (at most the balance)|
+//! | `dividend`   | Forex cash in `currency`         | `amount` (paid by `symbol`)     |
+//! | `fee`        | Forex cash in `currency`         | `amount` (at most the balance)  |
+//!
+//! Trades do not debit or credit cash; record the cash side as a deposit or
+//! withdrawal if it should be tracked. Fees are the exception: like a
+//! withdrawal, a fee is paid out of cash, so a ledger that records fees must
+//! deposit enough cash in their `currency` first, or it fails to load. The
+//! cost basis of each holding follows the average-cost method: buys add
+//! `quantity * price + fee`, sells remove a proportional share.
+//!
+//! `quantity`, `price`, `amount` and `fee` are read as exact decimals. They
+//! may be written as TOML numbers or as strings (`quantity = "0.1"`), and a
+//! value that is not a number is a parse error.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+
+use crate::serde_util::deserialize_date;
+use crate::types::{Portfolio, PortfolioItem};
+use crate::valuation::quote_currency;
+
+/// What a ledger entry records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionKind {
+    /// Acquire `quantity` units of a holding at `price`.
+    Buy,
+    /// Dispose of `quantity` units of a holding at `price`.
+    Sell,
+    /// Cash moved into the portfolio.
+    Deposit,
+    /// Cash moved out of the portfolio.
+    Withdrawal,
+    /// Cash income paid by a holding.
+    Dividend,
+    /// A standalone charge (custody, transfer, ...) not tied to a trade.
+    Fee,
+}
+
+/// One dated ledger entry. Which optional fields are required depends on
+/// [`TransactionKind`]; see the module docs.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Transaction {
+    /// Trade or settlement date. Accepts a TOML date or a `"YYYY-MM-DD"` string.
+    #[serde(deserialize_with = "deserialize_date")]
+    pub date: NaiveDate,
+    /// What happened.
+    pub kind: TransactionKind,
+    /// Asset category of `symbol`; required for buys, sells and dividends.
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Asset symbol traded, or currency code for deposits and withdrawals.
+    pub symbol: String,
+    /// Units bought, sold, deposited or withdrawn.
+    #[serde(default)]
+    pub quantity: Decimal,
+    /// Price per unit for buys and sells, in `currency`.
+    #[serde(default)]
+    pub price: Option<Decimal>,
+    /// Cash amount for dividends and fees, in `currency`.
+    #[serde(default)]
+    pub amount: Option<Decimal>,
+    /// Currency of `price`, `amount` and `fee`. Defaults to the quote currency
+    /// of the holding (USD for fees).
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// Trade commission, added to the cost basis of a buy.
+    #[serde(default)]
+    pub fee: Decimal,
+}
+
+impl Transaction {
+    /// The currency this transaction's monetary fields are expressed in.
+    pub fn currency(&self) -> String {
+        if let Some(ccy) = &self.currency {
+            return ccy.to_uppercase();
+        }
+        match (self.kind, &self.category) {
+            (TransactionKind::Fee, _) | (_, None) => "USD".to_string(),
+            (_, Some(category)) => quote_currency(&self.symbol, category),
+        }
+    }
+
+    /// Check that the fields required by `kind` are present and sensible.
+    fn validate(&self) -> Result<(), String> {
+        let what = format!("{} {:?} {}", self.date, self.kind, self.symbol);
+        match self.kind {
+            TransactionKind::Buy | TransactionKind::Sell => {
+                if self.category.is_none() {
+                    return Err(format!("{}: missing category", what));
+                }
+                if self.quantity <= Decimal::ZERO {
+                    return Err(format!("{}: quantity must be positive", what));
+                }
+                if self.price.is_none_or(|p| p < Decimal::ZERO) {
+                    return Err(format!("{}: missing or negative price", what));
+                }
+            }
+            TransactionKind::Deposit | TransactionKind::Withdrawal => {
+                if self.quantity <= Decimal::ZERO {
+                    return Err(format!("{}: quantity must be positive", what));
+                }
+            }
+            TransactionKind::Dividend | TransactionKind::Fee => {
+                if self.kind == TransactionKind::Dividend && self.category.is_none() {
+                    return Err(format!("{}: missing category", what));
+                }
+                if self.amount.is_none_or(|a| a < Decimal::ZERO) {
+                    return Err(format!("{}: missing or negative amount", what));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LedgerFile {
+    #[serde(default)]
+    transaction: Vec<Transaction>,
+}
+
+/// A validated, date-ordered list of transactions.
+#[derive(Debug, Clone, Default)]
+pub struct Ledger {
+    transactions: Vec<Transaction>,
+}
+
+/// Running state of one holding while folding the ledger, in exact decimals
+/// so repeated fractional trades sum to exactly what was written.
+#[derive(Default)]
+struct Position {
+    quantity: Decimal,
+    /// Remaining cost basis, in `cost_currency`.
+    cost: Decimal,
+    cost_currency: Option<String>,
+}
+
+impl Ledger {
+    /// Build a ledger from `transactions`, sorting them by date (stable, so
+    /// same-day entries keep their file order) and validating every entry and
+    /// the running balances.
+    pub fn new(mut transactions: Vec<Transaction>) -> Result<Ledger, String> {
+        transactions.sort_by_key(|t| t.date);
+        for tx in &transactions {
+            tx.validate()?;
+        }
+        let ledger = Ledger { transactions };
+        ledger.fold(None)?;
+        Ok(ledger)
+    }
+
+    /// All transactions, oldest first.
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// The current holdings implied by every transaction in the ledger.
+    pub fn portfolio(&self) -> Portfolio {
+        self.fold(None)
+            .expect("ledger balances validated on construction")
+    }
+
+    /// The holdings at the end of `date` (transactions dated on `date` count).
+    pub fn portfolio_as_of(&self, date: NaiveDate) -> Portfolio {
+        self.fold(Some(date))
+            .expect("ledger balances validated on construction")
+    }
+
+    /// Every `(category, symbol)` the ledger ever held, with zero quantity.
+    /// Used to decide which historical price series a back-fill must fetch,
+    /// including holdings that have since been sold.
+    pub fn instruments(&self) -> Portfolio {
+        let mut seen: BTreeMap<(String, String), ()> = BTreeMap::new();
+        for tx in &self.transactions {
+            seen.insert(Self::holding_key(tx), ());
+        }
+        Portfolio(
+            seen.into_keys()
+                .map(|(category, symbol)| PortfolioItem {
+                    symbol,
+                    category,
+                    ..Default::default()
+                })
+                .collect(),
+        )
+    }
+
+    /// The `(category, symbol)` holding a transaction moves.
+    fn holding_key(tx: &Transaction) -> (String, String) {
+        match tx.kind {
+            TransactionKind::Buy | TransactionKind::Sell => {
+                (tx.category.clone().unwrap_or_default(), tx.symbol.clone())
+            }
+            TransactionKind::Deposit | TransactionKind::Withdrawal => {
+                ("Forex".to_string(), tx.symbol.to_uppercase())
+            }
+            TransactionKind::Dividend | TransactionKind::Fee => {
+                ("Forex".to_string(), tx.currency())
+            }
+        }
+    }
+
+    /// Replay transactions up to and including `until` (all if `None`) and
+    /// return the non-zero holdings. Errors if a sell, withdrawal or fee exceeds
+    /// the balance held at that point.
+    fn fold(&self, until: Option<NaiveDate>) -> Result<Portfolio, String> {
+        let mut positions: HashMap<(String, String), Position> = HashMap::new();
+
+        for tx in &self.transactions {
+            if until.is_some_and(|d| tx.date > d) {
+                break;
+            }
+            let key = Self::holding_key(tx);
+            let pos = positions.entry(key.clone()).or_default();
+            let quantity = tx.quantity;
+
+            match tx.kind {
+                TransactionKind::Buy => {
+                    let ccy = tx.currency();
+                    match &pos.cost_currency {
+                        Some(existing) if *existing != ccy => {
+                            return Err(format!(
+                                "{} buy {}: currency {} differs from earlier trades in {}",
+                                tx.date, tx.symbol, ccy, existing
+                            ));
+                        }
+                        _ => pos.cost_currency = Some(ccy),
+                    }
+                    pos.quantity += quantity;
+                    pos.cost += quantity * tx.price.unwrap_or_default() + tx.fee;
+                }
+                TransactionKind::Sell | TransactionKind::Withdrawal => {
+                    if quantity > pos.quantity {
+                        return Err(format!(
+                            "{} {:?} {}: {} exceeds the {} held",
+                            tx.date, tx.kind, key.1, quantity, pos.quantity
+                        ));
+                    }
+                    // Average cost: the units leaving take a proportional share
+                    // of the basis with them.
+                    if pos.quantity > Decimal::ZERO {
+                        pos.cost -= pos.cost * quantity / pos.quantity;
+                    }
+                    pos.quantity -= quantity;
+                }
+                TransactionKind::Deposit => pos.quantity += quantity,
+                TransactionKind::Dividend => pos.quantity += tx.amount.unwrap_or_default(),
+                TransactionKind::Fee => {
+                    let amount = tx.amount.unwrap_or_default();
+                    if amount > pos.quantity {
+                        return Err(format!(
+                            "{} {:?} {}: {} exceeds the {} held",
+                            tx.date, tx.kind, key.1, amount, pos.quantity
+                        ));
+                    }
+                    pos.quantity -= amount;
+                }
+            }
+        }
+
+        let mut items: Vec<PortfolioItem> = positions
+            .into_iter()
+            .filter(|(_, pos)| !pos.quantity.is_zero())
+            .map(|((category, symbol), pos)| {
+                let avg_cost = pos
+                    .cost_currency
+                    .as_ref()
+                    .filter(|_| pos.quantity > Decimal::ZERO)
+                    .map(|_| pos.cost / pos.quantity);
+                PortfolioItem {
+                    symbol,
+                    category,
+                    quantity: pos.quantity,
+                    avg_cost,
+                    cost_currency: pos.cost_currency,
+                }
+            })
+            .collect();
+        items.sort_by(|a, b| (&a.category, &a.symbol).cmp(&(&b.category, &b.symbol)));
+
+        Ok(Portfolio(items))
+    }
+}
+
+/// Parse ledger TOML text into a validated [`Ledger`].
+pub fn parse_ledger(content: &str) -> Result<Ledger, String> {
+    let file: LedgerFile =
+        toml::from_str(content).map_err(|e| format!("Failed to parse TOML: {}", e))?;
+    Ledger::new(file.transaction)
+}
+
+/// Read the ledger at `path`. A missing file is not an error: it yields
+/// `Ok(None)`, meaning holdings come from `portfolio.toml` instead.
+pub fn read_ledger(path: &str) -> Result<Option<Ledger>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read file: {}", e)),
+    };
+    parse_ledger(&content).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn find<'a>(p: &'a Portfolio, symbol: &str) -> Option<&'a PortfolioItem> {
+        p.iter().find(|i| i.symbol == symbol)
+    }
+
+    const SAMPLE: &str = r#"
+        [[transaction]]
+        date = 2024-01-10
+        kind = "buy"
+        category = "US-Stock"
+        symbol = "AAPL"
+        quantity = 10
+        price = 100
+        fee = 10
+
+        [[transaction]]
+        date = "2024-03-01"
+        kind = "sell"
+        category = "US-Stock"
+        symbol = "AAPL"
+        quantity = 4
+        price = 150
+
+        [[transaction]]
+        date = 2024-02-01
+        kind = "buy"
+        category = "TW-Stock"
+        symbol = "2330"
+        quantity = 1000
+        price = 600
+
+        [[transaction]]
+        date = 2024-01-05
+        kind = "deposit"
+        symbol = "usd"
+        quantity = 5000
+
+        [[transaction]]
+        date = 2024-02-15
+        kind = "dividend"
+        category = "US-Stock"
+        symbol = "AAPL"
+        amount = 2.4
+
+        [[transaction]]
+        date = 2024-02-20
+        kind = "fee"
+        symbol = "custody"
+        amount = 0.4
+    "#;
+
+    #[test]
+    fn current_portfolio_uses_average_cost() {
+        let ledger = parse_ledger(SAMPLE).unwrap();
+        let p = ledger.portfolio();
+
+        let aapl = find(&p, "AAPL").unwrap();
+        assert_eq!(aapl.category, "US-Stock");
+        assert_eq!(aapl.quantity, Decimal::from(6));
+        // Basis 1010 for 10 units; selling 4 leaves 606 for 6 -> 101 per unit.
+        assert_eq!(aapl.avg_cost.unwrap(), Decimal::from(101));
+        assert_eq!(aapl.cost_currency.as_deref(), Some("USD"));
+
+        let tsmc = find(&p, "2330").unwrap();
+        assert_eq!(tsmc.cost_currency.as_deref(), Some("TWD"));
+
+        // 5000 deposited + 2.4 dividend - 0.4 fee, no cost basis for cash.
+        let usd = find(&p, "USD").unwrap();
+        assert_eq!(usd.category, "Forex");
+        assert_eq!(usd.quantity, Decimal::from(5002));
+        assert!(usd.avg_cost.is_none());
+    }
+
+    #[test]
+    fn portfolio_as_of_replays_only_earlier_transactions() {
+        let ledger = parse_ledger(SAMPLE).unwrap();
+
+        assert!(ledger.portfolio_as_of(date("2024-01-01")).0.is_empty());
+
+        let jan = ledger.portfolio_as_of(date("2024-01-10"));
+        assert_eq!(find(&jan, "AAPL").unwrap().quantity, Decimal::from(10));
+        assert!(find(&jan, "2330").is_none());
+
+        let feb = ledger.portfolio_as_of(date("2024-02-29"));
+        assert_eq!(find(&feb, "AAPL").unwrap().quantity, Decimal::from(10));
+        assert!(find(&feb, "2330").is_some());
+    }
+
+    #[test]
+    fn fully_sold_holdings_drop_out_but_remain_instruments() {
+        let ledger = parse_ledger(
+            r#"
+            [[transaction]]
+            date = 2024-01-10
+            kind = "buy"
+            category = "Crypto"
+            symbol = "ETH"
+            quantity = 0.5
+            price = 2000
+
+            [[transaction]]
+            date = 2024-01-20
+            kind = "sell"
+            category = "Crypto"
+            symbol = "ETH"
+            quantity = 0.5
+            price = 2500
+            "#,
+        )
+        .unwrap();
+        assert!(ledger.portfolio().0.is_empty());
+        let instruments = ledger.instruments();
+        assert_eq!(instruments.0.len(), 1);
+        assert_eq!(instruments.0[0].symbol, "ETH");
+    }
+
+    #[test]
+    fn overselling_is_rejected() {
+        let err = parse_ledger(
+            r#"
+            [[transaction]]
+            date = 2024-01-10
+            kind = "sell"
+            category = "US-Stock"
+            symbol = "AAPL"
+            quantity = 1
+            price = 100
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.contains("exceeds"), "{}", err);
+    }
+
+    #[test]
+    fn fees_beyond_the_cash_balance_are_rejected() {
+        let err = parse_ledger(
+            r#"
+            [[transaction]]
+            date = 2024-01-05
+            kind = "deposit"
+            symbol = "usd"
+            quantity = 10
+
+            [[transaction]]
+            date = 2024-01-10
+            kind = "fee"
+            symbol = "custody"
+            amount = 25
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.contains("exceeds"), "{}", err);
+    }
+
+    #[test]
+    fn amounts_are_exact_and_accept_strings() {
+        let ledger = parse_ledger(
+            r#"
+            [[transaction]]
+            date = 2024-01-10
+            kind = "buy"
+            category = "Crypto"
+            symbol = "ETH"
+            quantity = "0.123456789012345678901"
+            price = 0.1
+            fee = "0.2"
+            "#,
+        )
+        .unwrap();
+        let p = ledger.portfolio();
+        let eth = find(&p, "ETH").unwrap();
+        assert_eq!(eth.quantity, "0.123456789012345678901".parse().unwrap());
+
+        let err = parse_ledger(
+            r#"
+            [[transaction]]
+            date = 2024-01-10
+            kind = "deposit"
+            symbol = "usd"
+            quantity = "ten"
+            "#,
+        )
+        .unwrap_err();
+        assert!(err.contains("Failed to parse TOML"), "{}", err);
+    }
+
+    #[test]
+    fn trades_require_category_and_price() {
+        let missing_category = r#"
+            [[transaction]]
+            date = 2024-01-10
+            kind = "buy"
+            symbol = "AAPL"
+            quantity = 1
+            price = 100
+        "#;
+        assert!(parse_ledger(missing_category).is_err());
+
+        let missing_price = r#"
+            [[transaction]]
+            date = 2024-01-10
+            kind = "buy"
+            category = "US-Stock"
+            symbol = "AAPL"
+            quantity = 1
+        "#;
+        assert!(parse_ledger(missing_price).is_err());
+    }
+
+    #[test]
+    fn missing_ledger_file_is_not_an_error() {
+        assert!(read_ledger("test/does_not_exist.toml").unwrap().is_none());
+        let ledger = read_ledger("test/transactions.toml").unwrap().unwrap();
+        assert!(!ledger.transactions().is_empty());
+    }
+}
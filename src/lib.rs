@@ -1,16 +1,34 @@
 //! Library crate for the `price` portfolio tracker.
 //!
 //! It fetches asset prices and historical series from several providers
-//! ([`api`]), reads the user's holdings and settings from TOML files
-//! ([`config`], [`paths`], [`types`]), records periodic snapshots
-//! ([`history`]), and renders a live terminal UI ([`tui`], [`stream`]).
+//! ([`api`], with listings abroad mapped through [`exchange`]), reads the
+//! user's holdings and settings from TOML files ([`config`], [`paths`],
+//! [`types`]) or derives them from a transaction [`ledger`], values them in
+//! USD or any display currency ([`valuation`], with rates routed by [`fx`]),
+//! follows exchange trading hours ([`calendar`]), records periodic snapshots
+//! ([`history`]), reports realized gains ([`gains`]), and renders a live
+//! terminal UI ([`tui`], [`stream`]) or answers one-shot queries from the
+//! command line ([`cli`]). The live state can
+//! also be served as JSON over HTTP ([`server`]), alongside Prometheus
+//! [`metrics`].
 
 pub mod api;
+pub mod calendar;
+pub mod cli;
 pub mod config;
+pub mod exchange;
+pub mod fx;
+pub mod gains;
 pub mod get;
 pub mod history;
+pub mod ledger;
 pub mod logging;
+pub mod metrics;
 pub mod paths;
+mod serde_util;
+pub mod server;
+pub mod sources;
 pub mod stream;
 pub mod tui;
 pub mod types;
+pub mod valuation;
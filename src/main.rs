@@ -1,20 +1,17 @@
 //! Binary entry point for the `price` portfolio tracker.
 //!
-//! Installs the rustls crypto provider, initialises file logging, loads the
-//! portfolio and display currency from the config files, and hands off to the
-//! streaming TUI.
+//! Installs the rustls crypto provider, initialises file logging and parses the
+//! command line. With no subcommand (or `tui`) it loads the holdings (from the
+//! transaction ledger if present, otherwise the portfolio file) and display
+//! currency and hands off to the streaming TUI (or, for `daemon`, runs the same
+//! background tasks headless), optionally serving the JSON API from
+//! [`price::server`]; other subcommands run non-interactively (see
+//! [`price::cli`]).
 
-use config::read_portfolio;
-
-mod api;
-mod config;
-mod get;
-mod history;
-mod logging;
-mod paths;
-mod stream;
-mod tui;
-mod types;
+use price::cli::{self, Command};
+use price::stream::{self, RunOptions};
+use price::{config, logging, paths};
+use tokio::net::TcpListener;
 
 #[tokio::main]
 async fn main() {
@@ -25,7 +22,66 @@ async fn main() {
         .expect("failed to install rustls CryptoProvider");
     // Route diagnostics to a log file before the TUI takes over the terminal.
     logging::init();
-    let portfolio = read_portfolio(&paths::portfolio_file());
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let (daemon, serve, reject_stale, us_close_only) = match command {
+        Command::Tui {
+            serve,
+            reject_stale,
+            us_close_only,
+        } => (false, serve, reject_stale, us_close_only),
+        Command::Daemon {
+            serve,
+            reject_stale,
+            us_close_only,
+        } => (true, serve, reject_stale, us_close_only),
+        command => {
+            if let Err(e) = cli::run(command).await {
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+    };
+
+    let (portfolio, ledger) =
+        match config::read_holdings(&paths::portfolio_file(), &paths::transactions_file()) {
+            Ok(holdings) => holdings,
+            Err(e) => {
+                eprintln!("error: failed to load holdings: {}", e);
+                std::process::exit(1);
+            }
+        };
     let target_forex = config::read_target_forex_or_default(&paths::target_forex_file());
-    stream::stream(5, portfolio, target_forex).await;
+    // Bind before starting so a taken port is reported here rather than lost
+    // behind the TUI.
+    let listener = match serve {
+        Some(addr) => match TcpListener::bind(addr).await {
+            Ok(listener) => Some(listener),
+            Err(e) => {
+                eprintln!("error: cannot listen on {}: {}", addr, e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let options = RunOptions {
+        cycle: 5,
+        reject_stale,
+        us_close_only,
+        listener,
+    };
+    if daemon {
+        stream::daemon(portfolio, ledger, target_forex, options).await;
+    } else {
+        stream::stream(portfolio, ledger, target_forex, options).await;
+    }
 }
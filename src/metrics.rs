@@ -0,0 +1,244 @@
+//! Process-wide feed-health counters and the Prometheus text exposition served
+//! at `/metrics` (see [`crate::server`]).
+//!
+//! The price tasks record into a global registry as they run (stream
+//! reconnects, REST fallbacks and failures); [`render`] combines those with the
+//! portfolio valuation and quote ages from the live price map, so the numbers
+//! match the TUI and the JSON routes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+
+use crate::history;
+use crate::types::{Portfolio, PriceMap};
+use crate::valuation;
+
+#[derive(Default)]
+struct Registry {
+    /// Price key -> times its Pyth stream had to reconnect.
+    stream_reconnects: HashMap<String, u64>,
+    /// Symbol -> times the first REST source failed and the next one was tried.
+    fetch_fallbacks: HashMap<String, u64>,
+    /// Symbol -> times every REST source failed.
+    fetch_failures: HashMap<String, u64>,
+}
+
+static REGISTRY: Lazy<Mutex<Registry>> = Lazy::new(|| Mutex::new(Registry::default()));
+
+fn with_registry(f: impl FnOnce(&mut Registry)) {
+    // A poisoned lock only means another thread panicked mid-update; the
+    // counters are still usable.
+    let mut guard = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut guard);
+}
+
+/// Count a reconnect of the live stream feeding `key`.
+pub fn record_stream_reconnect(key: &str) {
+    with_registry(|r| *r.stream_reconnects.entry(key.to_string()).or_default() += 1);
+}
+
+/// Count a fall back from the first REST source in the chain for `symbol`.
+pub fn record_fetch_fallback(symbol: &str) {
+    with_registry(|r| *r.fetch_fallbacks.entry(symbol.to_string()).or_default() += 1);
+}
+
+/// Count a REST lookup for `symbol` where every source failed.
+pub fn record_fetch_failure(symbol: &str) {
+    with_registry(|r| *r.fetch_failures.entry(symbol.to_string()).or_default() += 1);
+}
+
+/// Render every series in the Prometheus text format (version 0.0.4).
+///
+/// `prices` is the live price map, `portfolio` the current holdings and
+/// `target_forex` the display currencies; the value in each is omitted until
+/// its `USD/{ccy}` rate is known. Quote ages are relative to `now`
+/// (epoch seconds).
+pub fn render(
+    portfolio: &Portfolio,
+    prices: &PriceMap,
+    target_forex: &[String],
+    now: i64,
+) -> String {
+    let mut out = String::new();
+    let values = prices.values();
+    let map = &values;
+    let (categories, total_usd) = history::compute_category_values(portfolio, map);
+
+    header(
+        &mut out,
+        "price_portfolio_value_usd",
+        "gauge",
+        "Total portfolio value in USD.",
+    );
+    let _ = writeln!(out, "price_portfolio_value_usd {}", total_usd);
+
+    header(
+        &mut out,
+        "price_portfolio_value",
+        "gauge",
+        "Total portfolio value in each display currency.",
+    );
+    for currency in target_forex {
+        if let Some(total) = valuation::from_usd(total_usd, currency, map) {
+            let _ = writeln!(
+                out,
+                "price_portfolio_value{{currency=\"{}\"}} {}",
+                escape(&currency.to_uppercase()),
+                total
+            );
+        }
+    }
+
+    header(
+        &mut out,
+        "price_category_value_usd",
+        "gauge",
+        "Portfolio value per category in USD.",
+    );
+    for (category, value) in sorted(&categories) {
+        let _ = writeln!(
+            out,
+            "price_category_value_usd{{category=\"{}\"}} {}",
+            escape(category),
+            value
+        );
+    }
+
+    header(
+        &mut out,
+        "price_quote",
+        "gauge",
+        "Latest price per key (asset symbol, or USD/{ccy} forex rate).",
+    );
+    for (key, price) in sorted(map) {
+        let _ = writeln!(out, "price_quote{{key=\"{}\"}} {}", escape(key), price);
+    }
+
+    header(
+        &mut out,
+        "price_last_update_age_seconds",
+        "gauge",
+        "Seconds since each price key was last updated.",
+    );
+    for (key, entry) in sorted(&prices.0) {
+        let _ = writeln!(
+            out,
+            "price_last_update_age_seconds{{key=\"{}\"}} {}",
+            escape(key),
+            (now - entry.updated_at).max(0)
+        );
+    }
+
+    let guard = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+
+    counter(
+        &mut out,
+        "price_stream_reconnects_total",
+        "Reconnects of the live Pyth stream per price key.",
+        "key",
+        &guard.stream_reconnects,
+    );
+    counter(
+        &mut out,
+        "price_fetch_fallbacks_total",
+        "REST lookups that fell back past the first source, per symbol.",
+        "symbol",
+        &guard.fetch_fallbacks,
+    );
+    counter(
+        &mut out,
+        "price_fetch_failures_total",
+        "REST lookups where every source failed, per symbol.",
+        "symbol",
+        &guard.fetch_failures,
+    );
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, label: &str, values: &HashMap<String, u64>) {
+    header(out, name, "counter", help);
+    for (key, count) in sorted(values) {
+        let _ = writeln!(out, "{}{{{}=\"{}\"}} {}", name, label, escape(key), count);
+    }
+}
+
+/// Iterate a map in key order so scrapes are stable and diffable.
+fn sorted<V>(map: &HashMap<String, V>) -> BTreeMap<&String, &V> {
+    map.iter().collect()
+}
+
+/// Escape a label value per the exposition format.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PortfolioItem;
+    use rust_decimal::Decimal;
+    use rust_decimal::prelude::FromPrimitive;
+
+    fn item(symbol: &str, category: &str, quantity: f64) -> PortfolioItem {
+        PortfolioItem {
+            symbol: symbol.into(),
+            category: category.into(),
+            quantity: Decimal::from_f64(quantity).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_values_and_counters() {
+        let portfolio = Portfolio(vec![
+            item("AAPL", "US-Stock", 2.0),
+            item("TWD", "Forex", 3_000.0),
+        ]);
+        let mut prices = PriceMap::default();
+        prices.insert("AAPL".to_string(), 150.0, "pyth", 1_000);
+        prices.insert("USD/TWD".to_string(), 30.0, "pyth", 1_090);
+        // Keys unique to this test: the registry is shared across tests.
+        record_stream_reconnect("METRICS-TEST");
+        record_stream_reconnect("METRICS-TEST");
+        record_fetch_fallback("METRICS-TEST");
+        record_fetch_failure("METRICS-TEST");
+
+        let text = render(&portfolio, &prices, &["twd".to_string()], 1_100);
+        assert!(text.contains("price_portfolio_value_usd 400\n"));
+        assert!(text.contains("price_portfolio_value{currency=\"TWD\"} 12000\n"));
+        assert!(text.contains("price_category_value_usd{category=\"Cash\"} 100\n"));
+        assert!(text.contains("price_category_value_usd{category=\"US-Stock\"} 300\n"));
+        assert!(text.contains("price_quote{key=\"USD/TWD\"} 30\n"));
+        assert!(text.contains("price_last_update_age_seconds{key=\"AAPL\"} 100\n"));
+        assert!(text.contains("price_last_update_age_seconds{key=\"USD/TWD\"} 10\n"));
+        assert!(text.contains("price_stream_reconnects_total{key=\"METRICS-TEST\"} 2\n"));
+        assert!(text.contains("price_fetch_fallbacks_total{symbol=\"METRICS-TEST\"} 1\n"));
+        assert!(text.contains("price_fetch_failures_total{symbol=\"METRICS-TEST\"} 1\n"));
+        assert!(text.contains("# TYPE price_stream_reconnects_total counter\n"));
+    }
+
+    #[test]
+    fn test_render_omits_unknown_target_rate() {
+        let portfolio = Portfolio(vec![item("AAPL", "US-Stock", 1.0)]);
+        let prices = PriceMap::from_values(HashMap::from([("AAPL".to_string(), 10.0)]), "pyth", 0);
+        let text = render(&portfolio, &prices, &["EUR".to_string()], 0);
+        assert!(text.contains("price_portfolio_value_usd 10\n"));
+        assert!(!text.contains("price_portfolio_value{"));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+}
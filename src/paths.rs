@@ -1,14 +1,15 @@
 //! Runtime file locations, resolved from environment variables with sensible
 //! defaults so the program can be deployed outside its source tree.
 //!
-//! | Variable           | Default  | Controls                                  |
-//! |--------------------|----------|-------------------------------------------|
-//! | `PRICE_CONFIG_DIR` | `config` | portfolio / target-forex / api-key files  |
-//! | `PRICE_DATA_DIR`   | `data`   | history, CSV export and (default) log     |
-//! | `PRICE_LOG`        | —        | overrides the log file path outright      |
+//! | Variable           | Default  | Controls                                     |
+//! |--------------------|----------|----------------------------------------------|
+//! | `PRICE_CONFIG_DIR` | `config` | portfolio / ledger / target / keys / sources |
+//! | `PRICE_DATA_DIR`   | `data`   | history, exports, feed/holiday caches, log   |
+//! | `PRICE_LOG`        | —        | overrides the log file path outright         |
 //!
-//! The Pyth feed table is compiled into the binary (see `api::pyth`) and needs
-//! no path.
+//! A Pyth feed table and a TWSE holiday list are compiled into the binary (see
+//! `api::pyth` and `calendar`), so the synced caches and the override files are
+//! all optional.
 
 use std::env;
 
@@ -30,19 +31,39 @@ pub fn portfolio_file() -> String {
     format!("{}/portfolio.toml", config_dir())
 }
 
+/// Path to the transaction ledger: `<config dir>/transactions.toml`. When it
+/// exists, holdings are derived from it instead of `portfolio.toml`.
+pub fn transactions_file() -> String {
+    format!("{}/transactions.toml", config_dir())
+}
+
 /// Path to the display-currency file: `<config dir>/target_forex.toml`.
 pub fn target_forex_file() -> String {
     format!("{}/target_forex.toml", config_dir())
 }
 
-// Only referenced by the AlphaVantage/ExchangeRate sources, which are currently
-// disabled; kept so they resolve the path consistently once re-enabled.
 /// Path to the API key file: `<config dir>/api_key.toml`.
-#[allow(dead_code)]
 pub fn api_key_file() -> String {
     format!("{}/api_key.toml", config_dir())
 }
 
+/// Path to the provider-chain file: `<config dir>/sources.toml`.
+pub fn sources_file() -> String {
+    format!("{}/sources.toml", config_dir())
+}
+
+/// Path to the user's extra TWSE closures (e.g. typhoon days):
+/// `<config dir>/twse_holidays.toml`.
+pub fn twse_holidays_file() -> String {
+    format!("{}/twse_holidays.toml", config_dir())
+}
+
+/// Path to the TWSE holiday schedule cached by `price calendar sync`:
+/// `<data dir>/twse_holidays.json`.
+pub fn twse_holidays_cache_file() -> String {
+    format!("{}/twse_holidays.json", data_dir())
+}
+
 /// Path to the snapshot history file: `<data dir>/history.jsonl`.
 pub fn history_file() -> String {
     format!("{}/history.jsonl", data_dir())
@@ -53,6 +74,22 @@ pub fn history_csv_file() -> String {
     format!("{}/history.csv", data_dir())
 }
 
+/// Path to the realized-gains report: `<data dir>/realized_gains.csv`.
+pub fn realized_gains_csv_file() -> String {
+    format!("{}/realized_gains.csv", data_dir())
+}
+
+/// Path to the user's Pyth feed-id overrides: `<config dir>/pyth_overrides.toml`.
+pub fn pyth_overrides_file() -> String {
+    format!("{}/pyth_overrides.toml", config_dir())
+}
+
+/// Path to the Pyth feed catalog cached by `price feeds sync`:
+/// `<data dir>/pyth_feeds.json`.
+pub fn pyth_feeds_file() -> String {
+    format!("{}/pyth_feeds.json", data_dir())
+}
+
 /// Log file path: `PRICE_LOG` if set, otherwise `<data dir>/price.log`.
 pub fn log_file() -> String {
     env::var("PRICE_LOG").unwrap_or_else(|_| format!("{}/price.log", data_dir()))
@@ -0,0 +1,66 @@
+//! Serde helpers: lenient dates for the TOML files the user edits by hand, and
+//! exact decimals for the history store.
+
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// Accept either a native TOML date (`2024-01-15`) or a string
+/// (`"2024-01-15"`); a time component, if any, is ignored.
+pub(crate) fn deserialize_date<'de, D>(deserializer: D) -> Result<NaiveDate, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let raw = match toml::Value::deserialize(deserializer)? {
+        toml::Value::String(s) => s,
+        toml::Value::Datetime(d) => d.to_string(),
+        other => {
+            return Err(serde::de::Error::custom(format!(
+                "expected a date, found {}",
+                other.type_str()
+            )));
+        }
+    };
+    let day = raw.get(..10).unwrap_or(&raw);
+    NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(serde::de::Error::custom)
+}
+
+/// `#[serde(with)]` for a [`Decimal`] written as a string (`"0.1"`), which
+/// keeps every digit where a JSON number would go through `f64`. Reads a
+/// string or a number, so values written as numbers still load.
+pub(crate) mod decimal_str {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        value: &Decimal,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Decimal, D::Error> {
+        <Decimal as Deserialize>::deserialize(deserializer)
+    }
+}
+
+/// [`decimal_str`] for every value of a map.
+pub(crate) mod decimal_str_map {
+    use super::*;
+
+    pub(crate) fn serialize<S: Serializer>(
+        map: &HashMap<String, Decimal>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(map.iter().map(|(key, value)| (key, value.to_string())))
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<String, Decimal>, D::Error> {
+        HashMap::deserialize(deserializer)
+    }
+}
@@ -0,0 +1,399 @@
+//! Optional embedded HTTP server exposing the live state as JSON, so dashboards
+//! and scripts can read prices, totals and history without scraping the TUI.
+//!
+//! | Route                     | Body                                              |
+//! |---------------------------|---------------------------------------------------|
+//! | `GET /health`             | liveness plus price / snapshot counts             |
+//! | `GET /prices`             | the live price map with update time and source    |
+//! | `GET /portfolio`          | holdings with USD value and P&L, plus totals      |
+//! | `GET /allocation`         | USD value and share of each category              |
+//! | `GET /history?from=&to=`  | recorded snapshots (decimal strings), optionally bounded |
+//! | `GET /metrics`            | Prometheus text exposition (see [`crate::metrics`]) |
+//!
+//! `from` / `to` accept Unix seconds or a `YYYY-MM-DD` date (both inclusive).
+//! The server is off unless `--serve` is given and binds to localhost unless a
+//! full address is supplied.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use axum::extract::{Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio::net::TcpListener;
+
+use crate::history;
+use crate::metrics;
+use crate::stream::{SharedHistory, SharedPortfolio, SharedPriceMap, SharedTargetForex};
+use crate::types::PriceMap;
+use crate::valuation;
+
+/// The shared state the routes read from; the same handles the background
+/// tasks write to.
+#[derive(Clone)]
+pub struct ApiState {
+    pub prices: SharedPriceMap,
+    pub portfolio: SharedPortfolio,
+    pub history: SharedHistory,
+    pub target_forex: SharedTargetForex,
+}
+
+/// Parse the `--serve` argument: a bare port binds to `127.0.0.1`, anything
+/// else must be a full `ip:port` socket address.
+pub fn parse_bind_addr(value: &str) -> Result<SocketAddr, String> {
+    if let Ok(port) = value.parse::<u16>() {
+        return Ok(SocketAddr::from(([127, 0, 0, 1], port)));
+    }
+    value
+        .parse()
+        .map_err(|_| format!("Invalid --serve address: {}", value))
+}
+
+/// Build the router over `state`.
+pub fn router(state: ApiState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/prices", get(prices))
+        .route("/portfolio", get(portfolio))
+        .route("/allocation", get(allocation))
+        .route("/history", get(history_range))
+        .route("/metrics", get(metrics_text))
+        .with_state(state)
+}
+
+/// Serve the API on an already-bound `listener` until the task is dropped.
+/// Errors are logged rather than propagated: the API is an add-on and must not
+/// take the tracker down with it.
+pub async fn serve(listener: TcpListener, state: ApiState) {
+    if let Ok(addr) = listener.local_addr() {
+        crate::log_line!("[http] listening on http://{}", addr);
+    }
+    if let Err(e) = axum::serve(listener, router(state)).await {
+        crate::log_line!("[http] server stopped: {}", e);
+    }
+}
+
+/// A JSON error body with the given status.
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+async fn health(State(state): State<ApiState>) -> Json<Value> {
+    let prices = state.prices.lock().await.clone();
+    let portfolio = state.portfolio.read().await.clone();
+    let snapshots = state.history.lock().await.len();
+    Json(json!({
+        "status": "ok",
+        "prices": prices.len(),
+        "holdings": portfolio.iter().count(),
+        "complete": history::is_complete(&portfolio, &prices.values()),
+        "stale": history::stale_price_keys(&portfolio, &prices, Utc::now().timestamp()),
+        "snapshots": snapshots,
+    }))
+}
+
+async fn prices(State(state): State<ApiState>) -> Json<PriceMap> {
+    Json(state.prices.lock().await.clone())
+}
+
+async fn portfolio(State(state): State<ApiState>) -> Json<Value> {
+    let prices = state.prices.lock().await.clone();
+    let portfolio = state.portfolio.read().await.clone();
+    let targets = state.target_forex.read().await.clone();
+    let now = Utc::now().timestamp();
+    let map = prices.values();
+
+    let holdings: Vec<Value> = portfolio
+        .iter()
+        .map(|item| {
+            let quote = if item.category == "Forex" {
+                None
+            } else {
+                prices.get(&item.symbol)
+            };
+            let pnl = history::holding_pnl(item, &map);
+            json!({
+                "category": item.category,
+                "symbol": item.symbol,
+                "quantity": item.quantity,
+                "price": quote.map(|q| q.price),
+                "updated_at": quote.map(|q| q.updated_at),
+                "source": quote.map(|q| q.source.as_str()),
+                "stale": quote.is_some()
+                    && history::is_stale(&prices, &item.symbol, &item.category, now),
+                "currency": valuation::quote_currency(&item.symbol, &item.category),
+                "value_usd": valuation::item_usd_value(item, &map),
+                "avg_cost": item.avg_cost,
+                "unrealized_pnl_usd": pnl.map(|p| p.amount()),
+                "unrealized_pnl_percent": pnl.map(|p| p.percent()),
+            })
+        })
+        .collect();
+
+    let (_, total_usd) = history::compute_category_values(&portfolio, &map);
+    let (_, pnl) = history::compute_unrealized_pnl(&portfolio, &map);
+    Json(json!({
+        "holdings": holdings,
+        "total_usd": total_usd,
+        "currency": targets[0],
+        "total": valuation::from_usd(total_usd, &targets[0], &map),
+        "totals": targets
+            .iter()
+            .map(|ccy| (ccy.clone(), valuation::from_usd(total_usd, ccy, &map)))
+            .collect::<HashMap<_, _>>(),
+        "complete": history::is_complete(&portfolio, &map),
+        "unrealized_pnl_usd": pnl.map(|p| p.amount()),
+        "unrealized_pnl_percent": pnl.map(|p| p.percent()),
+    }))
+}
+
+async fn allocation(State(state): State<ApiState>) -> Json<Value> {
+    let prices = state.prices.lock().await.clone();
+    let portfolio = state.portfolio.read().await.clone();
+    let map = prices.values();
+    let stale = history::stale_categories(&portfolio, &prices, Utc::now().timestamp());
+
+    let (values, total_usd) = history::compute_category_values(&portfolio, &map);
+    let categories: HashMap<String, Value> = values
+        .into_iter()
+        .map(|(category, value)| {
+            let ratio = if total_usd > Decimal::ZERO {
+                value / total_usd
+            } else {
+                Decimal::ZERO
+            };
+            let is_stale = stale.contains(&category);
+            (
+                category,
+                json!({ "value_usd": value, "ratio": ratio, "stale": is_stale }),
+            )
+        })
+        .collect();
+    Json(json!({ "total_usd": total_usd, "categories": categories }))
+}
+
+async fn metrics_text(State(state): State<ApiState>) -> Response {
+    let prices = state.prices.lock().await.clone();
+    let portfolio = state.portfolio.read().await.clone();
+    let targets = state.target_forex.read().await.clone();
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(&portfolio, &prices, &targets, Utc::now().timestamp()),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct HistoryQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+async fn history_range(
+    State(state): State<ApiState>,
+    Query(query): Query<HistoryQuery>,
+) -> Response {
+    let from = match query.from.as_deref().map(|v| parse_bound(v, false)) {
+        Some(Err(e)) => return error(StatusCode::BAD_REQUEST, e),
+        Some(Ok(ts)) => ts,
+        None => i64::MIN,
+    };
+    let to = match query.to.as_deref().map(|v| parse_bound(v, true)) {
+        Some(Err(e)) => return error(StatusCode::BAD_REQUEST, e),
+        Some(Ok(ts)) => ts,
+        None => i64::MAX,
+    };
+
+    let snapshots: Vec<_> = state
+        .history
+        .lock()
+        .await
+        .iter()
+        .filter(|s| s.timestamp >= from && s.timestamp <= to)
+        .cloned()
+        .collect();
+    Json(snapshots).into_response()
+}
+
+/// Parse a history bound: Unix seconds, or a date meaning its first (or, for
+/// an `end` bound, last) second in UTC.
+fn parse_bound(value: &str, end: bool) -> Result<i64, String> {
+    if let Ok(ts) = value.parse::<i64>() {
+        return Ok(ts);
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| {
+        format!(
+            "Invalid time bound (want Unix seconds or YYYY-MM-DD): {}",
+            value
+        )
+    })?;
+    let start = date
+        .and_hms_opt(0, 0, 0)
+        .expect("midnight is valid")
+        .and_utc()
+        .timestamp();
+    Ok(if end { start + 86_399 } else { start })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Portfolio, PortfolioItem, PortfolioSnapshot};
+    use rust_decimal::prelude::FromPrimitive;
+    use std::sync::Arc;
+    use tokio::sync::{Mutex, RwLock};
+
+    fn item(symbol: &str, category: &str, quantity: f64) -> PortfolioItem {
+        PortfolioItem {
+            symbol: symbol.into(),
+            category: category.into(),
+            quantity: Decimal::from_f64(quantity).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn snapshot(timestamp: i64) -> PortfolioSnapshot {
+        PortfolioSnapshot {
+            timestamp,
+            total_value_usd: Decimal::ONE,
+            category_values: HashMap::new(),
+            prices: HashMap::new(),
+        }
+    }
+
+    /// Start the server on an ephemeral localhost port and return its base URL.
+    async fn start() -> String {
+        let mut aapl = item("AAPL", "US-Stock", 2.0);
+        aapl.avg_cost = Some(100.into());
+        let portfolio = Portfolio(vec![
+            aapl,
+            item("2330", "TW-Stock", 10.0),
+            item("TWD", "Forex", 3_000.0),
+        ]);
+        let prices = HashMap::from([
+            ("AAPL".to_string(), 150.0),
+            ("2330".to_string(), 600.0),
+            ("USD/TWD".to_string(), 30.0),
+        ]);
+        let state = ApiState {
+            prices: Arc::new(Mutex::new(PriceMap::from_values(
+                prices,
+                "test",
+                Utc::now().timestamp(),
+            ))),
+            portfolio: Arc::new(RwLock::new(portfolio)),
+            history: Arc::new(Mutex::new(vec![
+                snapshot(1_700_000_000),
+                snapshot(1_700_086_400),
+                snapshot(1_700_172_800),
+            ])),
+            target_forex: Arc::new(RwLock::new(vec!["TWD".to_string(), "JPY".to_string()])),
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, state));
+        format!("http://{}", addr)
+    }
+
+    async fn get_json(url: &str) -> (u16, Value) {
+        // Bypass any proxy from the environment: the server is on localhost.
+        let client = reqwest::Client::builder().no_proxy().build().unwrap();
+        let response = client.get(url).send().await.unwrap();
+        let status = response.status().as_u16();
+        (status, response.json().await.unwrap())
+    }
+
+    #[test]
+    fn test_parse_bind_addr() {
+        assert_eq!(
+            parse_bind_addr("7878").unwrap(),
+            "127.0.0.1:7878".parse().unwrap()
+        );
+        assert_eq!(
+            parse_bind_addr("0.0.0.0:9000").unwrap(),
+            "0.0.0.0:9000".parse().unwrap()
+        );
+        assert!(parse_bind_addr("localhost").is_err());
+    }
+
+    #[test]
+    fn test_parse_bound() {
+        assert_eq!(parse_bound("1700000000", false), Ok(1_700_000_000));
+        assert_eq!(parse_bound("2023-11-14", false), Ok(1_699_920_000));
+        assert_eq!(parse_bound("2023-11-14", true), Ok(1_699_920_000 + 86_399));
+        assert!(parse_bound("yesterday", false).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_routes_serve_live_state() {
+        let base = start().await;
+
+        let (status, health) = get_json(&format!("{}/health", base)).await;
+        assert_eq!(status, 200);
+        assert_eq!(health["status"], "ok");
+        assert_eq!(health["complete"], true);
+        assert_eq!(health["snapshots"], 3);
+        assert_eq!(health["stale"], json!([]));
+
+        let (_, prices) = get_json(&format!("{}/prices", base)).await;
+        assert_eq!(prices["AAPL"]["price"], 150.0);
+        assert_eq!(prices["AAPL"]["source"], "test");
+        assert_eq!(prices["USD/TWD"]["price"], 30.0);
+
+        // 2 * 150 + 10 * 600 / 30 + 3000 / 30 = 300 + 200 + 100.
+        let (_, portfolio) = get_json(&format!("{}/portfolio", base)).await;
+        assert_eq!(portfolio["total_usd"], 600.0);
+        assert_eq!(portfolio["currency"], "TWD");
+        assert_eq!(portfolio["total"], 18_000.0);
+        assert_eq!(portfolio["totals"]["TWD"], 18_000.0);
+        // No USD/JPY rate yet: listed, but without a value.
+        assert_eq!(portfolio["totals"]["JPY"], Value::Null);
+        assert_eq!(portfolio["holdings"][0]["unrealized_pnl_usd"], 100.0);
+        assert_eq!(portfolio["holdings"][0]["stale"], false);
+        assert_eq!(portfolio["holdings"][1]["value_usd"], 200.0);
+
+        let text = reqwest::Client::builder()
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("{}/metrics", base))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(text.contains("price_portfolio_value{currency=\"TWD\"} 18000\n"));
+
+        let (_, allocation) = get_json(&format!("{}/allocation", base)).await;
+        assert_eq!(allocation["total_usd"], 600.0);
+        assert_eq!(allocation["categories"]["US-Stock"]["value_usd"], 300.0);
+        let ratio = allocation["categories"]["Cash"]["ratio"].as_f64().unwrap();
+        assert!((ratio - 100.0 / 600.0).abs() < 1e-12);
+    }
+
+    #[tokio::test]
+    async fn test_history_range_filters_and_rejects_bad_bounds() {
+        let base = start().await;
+
+        let (_, all) = get_json(&format!("{}/history", base)).await;
+        assert_eq!(all.as_array().unwrap().len(), 3);
+
+        let (_, some) = get_json(&format!("{}/history?from=1700086400", base)).await;
+        assert_eq!(some.as_array().unwrap().len(), 2);
+
+        let (_, one) = get_json(&format!("{}/history?from=2023-11-15&to=2023-11-15", base)).await;
+        assert_eq!(one.as_array().unwrap().len(), 1);
+        assert_eq!(one[0]["timestamp"], 1_700_086_400);
+
+        let (status, body) = get_json(&format!("{}/history?to=soon", base)).await;
+        assert_eq!(status, 400);
+        assert!(body["error"].as_str().unwrap().contains("soon"));
+    }
+}
@@ -0,0 +1,327 @@
+//! Per-category provider chains for REST quotes and history back-fill, read
+//! from `sources.toml` in the config dir.
+//!
+//! Each category maps to an ordered list of provider names; a lookup tries them
+//! in turn until one succeeds (see [`crate::get`]). The file only needs the
+//! categories it changes:
+//!
+//! ```toml
+//! [quote]
+//! US-Stock = ["yahoo", "alphavantage"]   # reorder / add a provider
+//! Forex = ["exchangerate", "yahoo"]
+//! Crypto = []                            # disable REST quotes for crypto
+//!
+//! [history]
+//! TW-Stock = ["yahoo"]
+//! ```
+//!
+//! Exchange-listed equities (`Stock.JP`, `Stock.UK`, ...) share the `Stock`
+//! chain unless their own category is listed (quoted, as `"Stock.JP"`).
+//!
+//! Provider names: `pyth`, `redstone`, `binance`, `yahoo`, `twse`,
+//! `alphavantage`, `exchangerate`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::api::PriceSource;
+use crate::api::alpha_vantage::AlphaVantage;
+use crate::api::binance::Binance;
+use crate::api::exchangerate::ExchangeRate;
+use crate::api::pyth::Pyth;
+use crate::api::redstone::RedStone;
+use crate::api::twse::Twse;
+use crate::api::yahoo::Yahoo;
+use crate::exchange;
+use crate::paths;
+
+/// One of the providers in `src/api`, selectable by name in `sources.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Pyth,
+    RedStone,
+    Binance,
+    Yahoo,
+    Twse,
+    AlphaVantage,
+    ExchangeRate,
+}
+
+impl FromStr for Source {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pyth" => Ok(Source::Pyth),
+            "redstone" => Ok(Source::RedStone),
+            "binance" => Ok(Source::Binance),
+            "yahoo" => Ok(Source::Yahoo),
+            "twse" => Ok(Source::Twse),
+            "alphavantage" | "alpha_vantage" => Ok(Source::AlphaVantage),
+            "exchangerate" => Ok(Source::ExchangeRate),
+            _ => Err(format!("Unknown price source: {}", s)),
+        }
+    }
+}
+
+impl PriceSource for Source {
+    fn name(&self) -> &'static str {
+        match self {
+            Source::Pyth => Pyth.name(),
+            Source::RedStone => RedStone.name(),
+            Source::Binance => Binance.name(),
+            Source::Yahoo => Yahoo.name(),
+            Source::Twse => Twse.name(),
+            Source::AlphaVantage => AlphaVantage.name(),
+            Source::ExchangeRate => ExchangeRate.name(),
+        }
+    }
+
+    async fn quote(&self, symbol: &str, category: &str) -> Result<f64, String> {
+        match self {
+            Source::Pyth => Pyth.quote(symbol, category).await,
+            Source::RedStone => RedStone.quote(symbol, category).await,
+            Source::Binance => Binance.quote(symbol, category).await,
+            Source::Yahoo => Yahoo.quote(symbol, category).await,
+            Source::Twse => Twse.quote(symbol, category).await,
+            Source::AlphaVantage => AlphaVantage.quote(symbol, category).await,
+            Source::ExchangeRate => ExchangeRate.quote(symbol, category).await,
+        }
+    }
+
+    async fn history(
+        &self,
+        symbol: &str,
+        category: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<(i64, f64)>, String> {
+        match self {
+            Source::Pyth => Pyth.history(symbol, category, from, to).await,
+            Source::RedStone => RedStone.history(symbol, category, from, to).await,
+            Source::Binance => Binance.history(symbol, category, from, to).await,
+            Source::Yahoo => Yahoo.history(symbol, category, from, to).await,
+            Source::Twse => Twse.history(symbol, category, from, to).await,
+            Source::AlphaVantage => AlphaVantage.history(symbol, category, from, to).await,
+            Source::ExchangeRate => ExchangeRate.history(symbol, category, from, to).await,
+        }
+    }
+}
+
+/// The provider chain for every category, for quotes and for history.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceConfig {
+    pub quote: HashMap<String, Vec<Source>>,
+    pub history: HashMap<String, Vec<Source>>,
+}
+
+impl Default for SourceConfig {
+    /// The built-in chains: RedStone then Binance (crypto) or Yahoo (US
+    /// equities), TWSE then Yahoo for Taiwan, Yahoo for forex and Pyth for
+    /// commodities; history from Pyth Benchmarks, or for Taiwan, which Pyth
+    /// does not cover, TWSE's daily report then Yahoo (Yahoo alone for TPEx).
+    /// Exchange-listed equities use Yahoo for both.
+    fn default() -> Self {
+        use Source::*;
+        let chains = |entries: &[(&str, &[Source])]| {
+            entries
+                .iter()
+                .map(|(category, chain)| (category.to_string(), chain.to_vec()))
+                .collect()
+        };
+        SourceConfig {
+            quote: chains(&[
+                ("Crypto", &[RedStone, Binance]),
+                ("US-Stock", &[RedStone, Yahoo]),
+                ("US-ETF", &[RedStone, Yahoo]),
+                ("TW-Stock", &[Twse, Yahoo]),
+                ("TW-ETF", &[Twse, Yahoo]),
+                ("TW-OTC", &[Twse, Yahoo]),
+                ("Forex", &[Yahoo]),
+                ("Commodity", &[Pyth]),
+                ("Stock", &[Yahoo]),
+            ]),
+            history: chains(&[
+                ("Crypto", &[Pyth]),
+                ("US-Stock", &[Pyth]),
+                ("US-ETF", &[Pyth]),
+                ("Forex", &[Pyth]),
+                ("Commodity", &[Pyth]),
+                ("TW-Stock", &[Twse, Yahoo]),
+                ("TW-ETF", &[Twse, Yahoo]),
+                ("TW-OTC", &[Yahoo]),
+                ("Stock", &[Yahoo]),
+            ]),
+        }
+    }
+}
+
+impl SourceConfig {
+    /// The quote chain for `category`, or `None` for a category nothing is
+    /// configured for.
+    pub fn quote_chain(&self, category: &str) -> Option<&[Source]> {
+        chain_for(&self.quote, category)
+    }
+
+    /// The history chain for `category`, or `None` for a category nothing is
+    /// configured for.
+    pub fn history_chain(&self, category: &str) -> Option<&[Source]> {
+        chain_for(&self.history, category)
+    }
+}
+
+/// The chain listed for `category`, falling back to the shared `Stock` chain
+/// for an exchange-listed category that is not listed itself.
+fn chain_for<'a>(chains: &'a HashMap<String, Vec<Source>>, category: &str) -> Option<&'a [Source]> {
+    chains
+        .get(category)
+        .or_else(|| {
+            exchange::is_exchange_category(category)
+                .then(|| chains.get(exchange::CATEGORY_PREFIX.trim_end_matches('.')))
+                .flatten()
+        })
+        .map(Vec::as_slice)
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct SourcesFile {
+    #[serde(default)]
+    quote: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    history: HashMap<String, Vec<String>>,
+}
+
+/// Parse `sources.toml` contents on top of the defaults: each category listed
+/// replaces its default chain, and an empty list disables it. Returns an `Err`
+/// string for invalid TOML or an unknown provider name.
+pub fn parse_sources(content: &str) -> Result<SourceConfig, String> {
+    let file: SourcesFile =
+        toml::from_str(content).map_err(|e| format!("Failed to parse TOML: {}", e))?;
+    let mut config = SourceConfig::default();
+    for (table, target) in [
+        (file.quote, &mut config.quote),
+        (file.history, &mut config.history),
+    ] {
+        for (category, names) in table {
+            let chain = names
+                .iter()
+                .map(|name| name.parse())
+                .collect::<Result<Vec<Source>, String>>()?;
+            target.insert(category, chain);
+        }
+    }
+    Ok(config)
+}
+
+/// Read the provider chains from the file at `path`.
+///
+/// A missing file is not an error: it yields the defaults. Returns an `Err`
+/// string if the file exists but cannot be read or parsed.
+pub fn read_sources(path: &str) -> Result<SourceConfig, String> {
+    match fs::read_to_string(path) {
+        Ok(content) => parse_sources(&content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SourceConfig::default()),
+        Err(e) => Err(format!("Failed to read file: {}", e)),
+    }
+}
+
+static SOURCES: Lazy<RwLock<SourceConfig>> = Lazy::new(|| {
+    let path = paths::sources_file();
+    RwLock::new(read_sources(&path).unwrap_or_else(|e| {
+        crate::log_line!("[config] {}: {}; using default sources", path, e);
+        SourceConfig::default()
+    }))
+});
+
+/// The provider chains currently in effect (loaded from `sources.toml` on first
+/// use).
+pub fn current() -> SourceConfig {
+    SOURCES.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Re-read `sources.toml`, keeping the chains in effect if it is invalid.
+pub fn reload() -> Result<(), String> {
+    let config = read_sources(&paths::sources_file())?;
+    *SOURCES.write().unwrap_or_else(|e| e.into_inner()) = config;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sources_overrides_listed_categories_only() {
+        let config = parse_sources(
+            r#"
+            [quote]
+            US-Stock = ["yahoo", "alphavantage"]
+            Forex = ["ExchangeRate", "yahoo"]
+            Crypto = []
+
+            [history]
+            TW-Stock = ["pyth", "yahoo"]
+            "#,
+        )
+        .unwrap();
+        let defaults = SourceConfig::default();
+
+        assert_eq!(
+            config.quote_chain("US-Stock"),
+            Some(&[Source::Yahoo, Source::AlphaVantage][..])
+        );
+        assert_eq!(
+            config.quote_chain("Forex"),
+            Some(&[Source::ExchangeRate, Source::Yahoo][..])
+        );
+        assert_eq!(config.quote_chain("Crypto"), Some(&[][..]));
+        assert_eq!(config.quote_chain("TW-ETF"), defaults.quote_chain("TW-ETF"));
+        assert_eq!(
+            config.history_chain("TW-Stock"),
+            Some(&[Source::Pyth, Source::Yahoo][..])
+        );
+        assert_eq!(config.history_chain("US-ETF"), Some(&[Source::Pyth][..]));
+        assert_eq!(config.quote_chain("Unknown"), None);
+    }
+
+    #[test]
+    fn test_exchange_categories_share_the_stock_chain() {
+        let config = parse_sources(
+            r#"
+            [quote]
+            "Stock.JP" = ["alphavantage"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.quote_chain("Stock.JP"),
+            Some(&[Source::AlphaVantage][..])
+        );
+        assert_eq!(config.quote_chain("Stock.UK"), Some(&[Source::Yahoo][..]));
+        assert_eq!(config.history_chain("Stock.JP"), Some(&[Source::Yahoo][..]));
+        assert_eq!(config.quote_chain("Stockholm"), None);
+    }
+
+    #[test]
+    fn test_parse_sources_rejects_unknown_names() {
+        let err = parse_sources("[quote]\nCrypto = [\"coingecko\"]").unwrap_err();
+        assert!(err.contains("coingecko"));
+        assert!(parse_sources("[quotes]\nCrypto = []").is_err());
+    }
+
+    #[test]
+    fn test_read_sources_missing_file_uses_defaults() {
+        let config = read_sources("/nonexistent/sources.toml").unwrap();
+        assert_eq!(config, SourceConfig::default());
+        assert_eq!(
+            config.quote_chain("Crypto"),
+            Some(&[Source::RedStone, Source::Binance][..])
+        );
+    }
+}
@@ -1,9 +1,9 @@
 //! The running application: spawns the live price streams and background tasks,
-//! drives the TUI display loop, and polls Taiwan-market prices and config
-//! hot-reloads.
+//! drives the TUI display loop (or runs them headless as a daemon), and polls
+//! Taiwan-market and exchange-listed prices, REST backups for stalled crypto
+//! streams and config hot-reloads.
 
 use chrono::prelude::*;
-use chrono_tz::Asia::Taipei;
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
@@ -12,31 +12,58 @@ use crossterm::{
     event::{self, Event, KeyCode},
     execute,
 };
-use futures::stream::{FuturesUnordered, StreamExt};
 use ratatui::{Terminal, backend::CrosstermBackend};
-use std::collections::{BTreeMap, HashMap, HashSet};
+use rust_decimal::Decimal;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::net::TcpListener;
 use tokio::sync::{Mutex, RwLock};
+use tokio::task::AbortHandle;
 
-use crate::api::pyth::{get_pyth_feed_id, spawn_price_stream, stream_into_map};
-use crate::api::twse::get_close_price_from_twse;
+use crate::api::exchangerate;
+use crate::api::pyth::{PythStreams, get_pyth_feed_id};
+use crate::api::twse::get_quotes_from_twse;
+use crate::calendar::{self, UsSession};
 use crate::config;
-use crate::get::{get_history, get_price};
+use crate::exchange;
+use crate::fx::{self, FxRoute};
+use crate::gains::{self, LotMethod};
+use crate::get::{get_history, get_price, get_prices};
 use crate::history;
+use crate::ledger::{self, Ledger};
 use crate::paths;
+use crate::server::{self, ApiState};
+use crate::sources;
 use crate::tui::{self, ViewMode};
-use crate::types::{Portfolio, PortfolioSnapshot};
+use crate::types::{Portfolio, PortfolioSnapshot, PriceMap};
+use crate::valuation;
 
-type SharedPriceMap = Arc<tokio::sync::Mutex<HashMap<String, f64>>>;
-type SharedHistory = Arc<tokio::sync::Mutex<Vec<PortfolioSnapshot>>>;
-/// Portfolio and display currency are wrapped in `RwLock` so the config
+pub(crate) type SharedPriceMap = Arc<tokio::sync::Mutex<PriceMap>>;
+pub(crate) type SharedHistory = Arc<tokio::sync::Mutex<Vec<PortfolioSnapshot>>>;
+/// Portfolio and display currencies are wrapped in `RwLock` so the config
 /// hot-reload watcher can swap in fresh values while reader tasks keep running.
-type SharedPortfolio = Arc<RwLock<Portfolio>>;
-type SharedTargetForex = Arc<RwLock<String>>;
-/// Keys (forex pairs and `category:symbol`) for which a price stream/seed has
-/// already been started, so reloads only subscribe to genuinely new holdings.
-type SubscribedSet = Arc<Mutex<HashSet<String>>>;
+pub(crate) type SharedPortfolio = Arc<RwLock<Portfolio>>;
+pub(crate) type SharedTargetForex = Arc<RwLock<Vec<String>>>;
+/// Active subscriptions keyed by forex pair or `category:symbol`, so each
+/// reload starts only what is new and stops what is no longer needed.
+type Subscriptions = Arc<Mutex<HashMap<String, Subscription>>>;
+
+/// What a holding or forex pair needs to keep its price live: the price-map
+/// key it feeds, the leg feeds streamed to derive it (for a forex rate on an
+/// inverse or cross route), how a forex rate is obtained and the background
+/// tasks (REST seeds and pollers, rate derivation) started for it, aborted when
+/// the subscription is dropped.
+struct Subscription {
+    price_key: String,
+    feeds: Vec<String>,
+    route: Option<FxRoute>,
+    tasks: Vec<AbortHandle>,
+}
+
+/// The last regular-session price of each US equity, for
+/// [`RunOptions::us_close_only`].
+type UsCloses = Arc<Mutex<HashMap<String, f64>>>;
 
 /// How often a live snapshot of the portfolio is recorded (seconds).
 const SNAPSHOT_INTERVAL_SECS: u64 = 300;
@@ -44,85 +71,198 @@ const SNAPSHOT_INTERVAL_SECS: u64 = 300;
 const BACKFILL_WINDOW_SECS: i64 = 365 * 86_400;
 /// How often the config files are checked for changes (seconds).
 const CONFIG_POLL_SECS: u64 = 2;
+/// How often crypto prices are checked for a stalled Pyth stream (seconds). A
+/// price not updated within one interval is refreshed over REST, which keeps
+/// it inside the crypto staleness threshold.
+const CRYPTO_BACKUP_POLL_SECS: u64 = 30;
+/// How often exchange-listed (`Stock.{code}`) holdings, which have no live
+/// stream, are re-quoted over REST (seconds).
+const EXCHANGE_POLL_SECS: u64 = 60;
+/// How often a forex rate on an inverse or cross route is re-derived from its
+/// streamed legs (seconds).
+const FX_DERIVE_SECS: u64 = 1;
+
+/// Check if the Taiwan Stock Exchange (TWSE) is currently in session: a
+/// trading day in the [`calendar`], 09:00–13:30 Asia/Taipei.
+fn is_twse_market_open() -> bool {
+    calendar::twse().is_open_at(Utc::now())
+}
 
-/// Returns `true` if `dt` (interpreted in its own timezone) falls within TWSE
-/// trading hours: Monday–Friday, 09:00–13:29 inclusive.
-///
-/// Callers are responsible for supplying a `DateTime` already converted to
-/// Asia/Taipei. In production `is_twse_market_open` does this; in tests a
-/// fixed Taipei `DateTime` is injected directly, making tests deterministic
-/// without mocking system time.
-fn is_twse_market_open_at<Tz: chrono::TimeZone>(dt: chrono::DateTime<Tz>) -> bool {
-    if !matches!(
-        dt.weekday(),
-        chrono::Weekday::Mon
-            | chrono::Weekday::Tue
-            | chrono::Weekday::Wed
-            | chrono::Weekday::Thu
-            | chrono::Weekday::Fri
-    ) {
-        return false;
-    }
-
-    let current_time = dt.hour() * 60 + dt.minute();
-    let open_time = 9 * 60; // 09:00
-    let close_time = 13 * 60 + 30; // 13:30
+/// The state [`stream`] and [`daemon`] share between their background tasks,
+/// the display loop and the JSON API.
+struct Runtime {
+    prices: SharedPriceMap,
+    history: SharedHistory,
+    portfolio: SharedPortfolio,
+    target_forex: SharedTargetForex,
+    subscriptions: Subscriptions,
+    us_closes: UsCloses,
+}
 
-    current_time >= open_time && current_time < close_time
+impl Runtime {
+    /// Fresh state for `portfolio` and `target_forex`, with the history loaded
+    /// from disk and no prices or subscriptions yet.
+    fn new(portfolio: Portfolio, target_forex: Vec<String>) -> Self {
+        Runtime {
+            prices: Arc::new(Mutex::new(PriceMap::default())),
+            history: Arc::new(Mutex::new(history::load_history(&paths::history_file()))),
+            portfolio: Arc::new(RwLock::new(portfolio)),
+            target_forex: Arc::new(RwLock::new(target_forex)),
+            subscriptions: Arc::new(Mutex::new(HashMap::new())),
+            us_closes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
 }
 
-/// Check if Taiwan Stock Exchange (TWSE) market is currently open.
-/// TWSE trading hours: 09:00–13:30 (Monday–Friday) in Asia/Taipei.
-fn is_twse_market_open() -> bool {
-    is_twse_market_open_at(Local::now().with_timezone(&Taipei))
+/// Settings for [`stream`] and [`daemon`] that come from the command line.
+pub struct RunOptions {
+    /// Taiwan-market polling interval in seconds.
+    pub cycle: u64,
+    /// Skip live snapshots while any price they need is stale (see
+    /// [`history::is_complete_live`]) rather than recording the old value.
+    pub reject_stale: bool,
+    /// Outside the NYSE regular session, value US equities in live snapshots
+    /// at their regular-session close rather than the extended-hours price.
+    pub us_close_only: bool,
+    /// Where to serve the JSON API (see [`crate::server`]), if anywhere.
+    pub listener: Option<TcpListener>,
 }
 
 /// Run the application: start the background price/snapshot/reload tasks, set up
 /// the terminal, run the display loop until the user quits, then restore the
 /// terminal.
 ///
-/// `cycle` is the Taiwan-market polling interval in seconds, `portfolio` the
-/// initial holdings and `target_forex` the initial display currency. Returns
-/// when the user exits the display loop.
-pub async fn stream(cycle: u64, portfolio: Portfolio, target_forex: String) {
-    let prices: SharedPriceMap = Arc::new(Mutex::new(HashMap::new()));
-    let history: SharedHistory =
-        Arc::new(Mutex::new(history::load_history(&paths::history_file())));
-    let portfolio: SharedPortfolio = Arc::new(RwLock::new(portfolio));
-    let target_forex: SharedTargetForex = Arc::new(RwLock::new(target_forex));
-    let subscribed: SubscribedSet = Arc::new(Mutex::new(HashSet::new()));
+/// `portfolio` is the initial holdings, `ledger` the transaction ledger they
+/// were derived from (if any) and `target_forex` the initial display currencies.
+/// Returns when the user exits the display loop.
+pub async fn stream(
+    portfolio: Portfolio,
+    ledger: Option<Ledger>,
+    target_forex: Vec<String>,
+    mut options: RunOptions,
+) {
+    let runtime = Runtime::new(portfolio, target_forex);
     // Start background tasks
-    start_background_tasks(
-        &prices,
-        &history,
-        &portfolio,
-        &target_forex,
-        &subscribed,
-        cycle,
-    )
-    .await;
+    start_background_tasks(&runtime, ledger, &options).await;
+    if let Some(listener) = options.listener.take() {
+        spawn_server(listener, &runtime);
+    }
     // Setup terminal
     let mut terminal = setup_terminal();
     // Main display loop
-    run_display_loop(&mut terminal, &prices, &history, &portfolio, &target_forex).await;
+    run_display_loop(
+        &mut terminal,
+        &runtime.prices,
+        &runtime.history,
+        &runtime.portfolio,
+        &runtime.target_forex,
+        &runtime.subscriptions,
+    )
+    .await;
     // Cleanup
     restore_terminal(&mut terminal);
 }
 
-async fn start_background_tasks(
-    prices: &SharedPriceMap,
-    history: &SharedHistory,
-    portfolio: &SharedPortfolio,
-    target_forex: &SharedTargetForex,
-    subscribed: &SubscribedSet,
-    cycle: u64,
+/// Run headless: the same background tasks as [`stream`] (live prices, polling,
+/// back-fill, snapshots, config hot-reload) but without touching the terminal,
+/// so the history keeps growing on a server with no one watching.
+///
+/// Runs until SIGINT or SIGTERM, then records a final snapshot (if every price
+/// is known) and writes the history to disk before returning.
+pub async fn daemon(
+    portfolio: Portfolio,
+    ledger: Option<Ledger>,
+    target_forex: Vec<String>,
+    mut options: RunOptions,
 ) {
-    // Subscribe to every price/forex stream the initial portfolio needs. Take a
-    // snapshot of the shared config first so we don't hold the lock across the
-    // network calls inside `ensure_subscriptions`.
+    let runtime = Runtime::new(portfolio, target_forex);
+    crate::log_line!("[daemon] starting");
+    start_background_tasks(&runtime, ledger, &options).await;
+    if let Some(listener) = options.listener.take() {
+        spawn_server(listener, &runtime);
+    }
+
+    shutdown_signal().await;
+    crate::log_line!("[daemon] shutting down");
+
+    let Runtime {
+        prices,
+        history,
+        portfolio,
+        us_closes,
+        ..
+    } = &runtime;
+    let closes = options.us_close_only.then_some(us_closes);
+    if !record_snapshot(history, prices, portfolio, options.reject_stale, closes).await {
+        // Nothing new to add, but still leave the file matching memory (e.g.
+        // a back-fill that finished since the last snapshot).
+        let guard = history.lock().await;
+        if let Err(e) = history::save_all(&paths::history_file(), &guard) {
+            crate::log_line!("[daemon] failed to flush history: {}", e);
+        }
+    }
+    crate::log_line!("[daemon] history flushed; exiting");
+}
+
+/// Serve the JSON API on `listener` from the same shared state the background
+/// tasks update.
+fn spawn_server(listener: TcpListener, runtime: &Runtime) {
+    let state = ApiState {
+        prices: runtime.prices.clone(),
+        portfolio: runtime.portfolio.clone(),
+        history: runtime.history.clone(),
+        target_forex: runtime.target_forex.clone(),
+    };
+    tokio::spawn(server::serve(listener, state));
+}
+
+/// Resolve on the first SIGINT (Ctrl-C) or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+                return;
+            }
+            Err(e) => crate::log_line!("[daemon] cannot listen for SIGTERM: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        crate::log_line!("[daemon] cannot listen for SIGINT: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
+
+async fn start_background_tasks(runtime: &Runtime, ledger: Option<Ledger>, options: &RunOptions) {
+    let Runtime {
+        prices,
+        history,
+        portfolio,
+        target_forex,
+        subscriptions,
+        us_closes,
+    } = runtime;
+    let cycle = options.cycle;
+    let reject_stale = options.reject_stale;
+    // Subscribe to every price/forex stream the initial portfolio needs; every
+    // Pyth feed shares one multiplexed connection. Take a snapshot of the
+    // shared config first so the config locks are not held while subscribing.
+    let streams = PythStreams::spawn(prices.clone());
     let initial_portfolio = portfolio.read().await.clone();
     let initial_target = target_forex.read().await.clone();
-    ensure_subscriptions(&initial_portfolio, &initial_target, prices, subscribed).await;
+    reconcile_subscriptions(
+        &initial_portfolio,
+        &initial_target,
+        prices,
+        &streams,
+        subscriptions,
+    )
+    .await;
 
     // Start polling stream
     let polling_prices = prices.clone();
@@ -131,98 +271,344 @@ async fn start_background_tasks(
         polling_stream(polling_prices, cycle, polling_portfolio).await;
     });
 
+    // Back up crypto streams that stall (or never start) with REST prices.
+    let backup_prices = prices.clone();
+    let backup_portfolio = portfolio.clone();
+    tokio::spawn(async move {
+        crypto_backup_stream(backup_prices, backup_portfolio).await;
+    });
+
     // Back-fill historical daily data once at startup, using the holdings known
-    // at launch. Symbols added later via hot-reload are not back-filled (they
-    // accumulate live snapshots instead).
+    // at launch (or, with a ledger, the holdings as they stood on each day).
+    // Symbols added later via hot-reload are not back-filled (they accumulate
+    // live snapshots instead).
     let backfill_history = history.clone();
     let backfill_portfolio = initial_portfolio;
     tokio::spawn(async move {
-        backfill_history_task(backfill_history, backfill_portfolio).await;
+        backfill_history_task(backfill_history, backfill_portfolio, ledger).await;
     });
 
     // Record periodic live snapshots into the history.
     let snapshot_history = history.clone();
     let snapshot_prices = prices.clone();
     let snapshot_portfolio = portfolio.clone();
+    let snapshot_closes = options.us_close_only.then(|| us_closes.clone());
     tokio::spawn(async move {
-        snapshot_recorder(snapshot_history, snapshot_prices, snapshot_portfolio).await;
+        snapshot_recorder(
+            snapshot_history,
+            snapshot_prices,
+            snapshot_portfolio,
+            reject_stale,
+            snapshot_closes,
+        )
+        .await;
     });
 
     // Watch the config files and hot-reload portfolio / target currency.
     let watch_portfolio = portfolio.clone();
     let watch_target = target_forex.clone();
     let watch_prices = prices.clone();
-    let watch_subscribed = subscribed.clone();
+    let watch_subscriptions = subscriptions.clone();
     tokio::spawn(async move {
         watch_config(
             watch_portfolio,
             watch_target,
             watch_prices,
-            watch_subscribed,
+            streams,
+            watch_subscriptions,
         )
         .await;
     });
 }
 
-/// Start any price/forex streams required by `portfolio` (valued in
-/// `target_forex`) that have not been started yet. Idempotent: a key is only
-/// acted on the first time it is seen, so this can be called on every reload to
-/// pick up newly added holdings without duplicating existing subscriptions.
-async fn ensure_subscriptions(
+/// The subscriptions `portfolio` (valued in the `target_forex` currencies) needs, keyed as in
+/// [`Subscriptions`]: `(category, symbol, price key)` for every forex pair,
+/// streamed crypto / US / commodity holding and polled Taiwan or
+/// exchange-listed holding.
+fn wanted_subscriptions(
     portfolio: &Portfolio,
-    target_forex: &str,
-    prices: &SharedPriceMap,
-    subscribed: &SubscribedSet,
-) {
+    target_forex: &[String],
+) -> BTreeMap<String, (String, String, String)> {
+    let mut wanted = BTreeMap::new();
     // Forex rates needed to value the portfolio in USD plus the display
-    // currency. USD is the base currency, so USD/USD is skipped.
-    for forex_symbol in required_forex_pairs(portfolio, target_forex) {
-        if mark_new(subscribed, &forex_symbol).await {
-            crate::log_line!("Subscribing to forex rate: {}", forex_symbol);
-            start_forex_stream(prices.clone(), &forex_symbol).await;
+    // currencies. USD is the base currency, so USD/USD is skipped.
+    for pair in required_forex_pairs(portfolio, target_forex) {
+        wanted.insert(pair.clone(), ("Forex".to_string(), pair.clone(), pair));
+    }
+    for category in [
+        "Crypto",
+        "US-Stock",
+        "US-ETF",
+        "Commodity",
+        "TW-Stock",
+        "TW-ETF",
+        "TW-OTC",
+    ] {
+        for item in portfolio.get(category).into_iter().flatten() {
+            wanted.insert(
+                format!("{}:{}", category, item.symbol),
+                (
+                    category.to_string(),
+                    item.symbol.clone(),
+                    item.symbol.clone(),
+                ),
+            );
+        }
+    }
+    for item in portfolio.iter() {
+        if exchange::is_exchange_category(&item.category) {
+            wanted.insert(
+                format!("{}:{}", item.category, item.symbol),
+                (
+                    item.category.clone(),
+                    item.symbol.clone(),
+                    item.symbol.clone(),
+                ),
+            );
         }
     }
+    wanted
+}
 
-    // Live crypto / US equity streams.
-    for category in ["Crypto", "US-Stock", "US-ETF"] {
-        if let Some(items) = portfolio.get(category) {
-            for item in items {
-                let key = format!("{}:{}", category, item.symbol);
-                if mark_new(subscribed, &key).await {
-                    spawn_price_stream(&item.symbol, category, prices.clone());
-                }
+/// Bring the running subscriptions in line with `portfolio` (valued in the
+/// `target_forex` currencies). Called at startup and on every reload.
+///
+/// Holdings and pairs that are no longer needed (a coin removed from the
+/// portfolio, the `USD/{target}` of a dropped display currency) have their tasks
+/// aborted, their Pyth feeds dropped from the shared stream and their prices
+/// evicted. New ones are added to the stream, forex pairs along the route
+/// [`fx::resolve`] picks; crypto is also seeded over REST
+/// so a coin without a Pyth feed (or a slow first tick) is not valued at zero,
+/// new Taiwan holdings are seeded together with their cached close (refreshed
+/// by `polling_stream`) and each exchange-listed holding gets its own REST
+/// poller.
+async fn reconcile_subscriptions(
+    portfolio: &Portfolio,
+    target_forex: &[String],
+    prices: &SharedPriceMap,
+    streams: &PythStreams,
+    subscriptions: &Subscriptions,
+) {
+    let wanted = wanted_subscriptions(portfolio, target_forex);
+    let mut active = subscriptions.lock().await;
+
+    let stale: Vec<String> = active
+        .keys()
+        .filter(|key| !wanted.contains_key(*key))
+        .cloned()
+        .collect();
+    for key in stale {
+        let Some(subscription) = active.remove(&key) else {
+            continue;
+        };
+        for task in &subscription.tasks {
+            task.abort();
+        }
+        crate::log_line!("Unsubscribing from {}", key);
+        // Another holding may still feed the same key (e.g. a symbol listed in
+        // two categories, or a leg two crossed rates share); leave its stream
+        // and price alone.
+        for feed in std::iter::once(subscription.price_key).chain(subscription.feeds) {
+            if wanted.values().any(|(_, _, k)| *k == feed)
+                || active.values().any(|other| other.feeds.contains(&feed))
+            {
+                continue;
             }
+            streams.unsubscribe(&feed);
+            prices.lock().await.remove(&feed);
         }
     }
 
-    // Seed cached close prices for Taiwan holdings (refreshed by polling_stream).
-    for category in ["TW-Stock", "TW-ETF"] {
-        if let Some(items) = portfolio.get(category) {
-            for item in items {
-                let key = format!("{}:{}", category, item.symbol);
-                if mark_new(subscribed, &key).await {
-                    match get_close_price_from_twse(&item.symbol).await {
-                        Ok(price) => {
-                            prices.lock().await.insert(item.symbol.clone(), price);
-                        }
-                        Err(e) => {
-                            crate::log_line!(
-                                "Failed to seed close price for {}: {}",
-                                item.symbol,
-                                e
-                            );
+    // New Taiwan holdings, seeded together in one batched request.
+    let mut seeds = Vec::new();
+    for (key, (category, symbol, price_key)) in wanted {
+        if active.contains_key(&key) {
+            continue;
+        }
+        let mut feeds = Vec::new();
+        let mut route = None;
+        let mut tasks = Vec::new();
+        match category.as_str() {
+            "Forex" => {
+                let currency = symbol.trim_start_matches("USD/");
+                let fx_route = fx::resolve(currency, fx::has_pyth_feed);
+                crate::log_line!("Subscribing to forex rate: {} ({})", symbol, fx_route);
+                match &fx_route {
+                    FxRoute::Direct => subscribe_feed(streams, &price_key, &symbol, "Forex"),
+                    FxRoute::Rest => {
+                        let task = tokio::spawn(poll_fx_rate(
+                            prices.clone(),
+                            price_key.clone(),
+                            currency.to_string(),
+                        ));
+                        tasks.push(task.abort_handle());
+                    }
+                    FxRoute::Inverse | FxRoute::Cross { .. } => {
+                        let legs = fx_route.legs(currency);
+                        for leg in &legs {
+                            subscribe_feed(streams, &leg.pair, &leg.pair, "Forex");
+                            feeds.push(leg.pair.clone());
                         }
+                        let task = tokio::spawn(derive_fx_rate(
+                            prices.clone(),
+                            price_key.clone(),
+                            legs,
+                            fx_route.source(),
+                        ));
+                        tasks.push(task.abort_handle());
                     }
                 }
+                route = Some(fx_route);
+            }
+            "Crypto" | "US-Stock" | "US-ETF" | "Commodity" => {
+                subscribe_feed(streams, &price_key, &symbol, &category);
+                if category == "Crypto" {
+                    let task = tokio::spawn(backup_crypto_price(prices.clone(), symbol));
+                    tasks.push(task.abort_handle());
+                }
+            }
+            category if exchange::is_exchange_category(category) => {
+                let task = tokio::spawn(poll_exchange_price(
+                    prices.clone(),
+                    symbol,
+                    category.to_string(),
+                ));
+                tasks.push(task.abort_handle());
+            }
+            _ => seeds.push((key.clone(), symbol, category)),
+        }
+        active.insert(
+            key,
+            Subscription {
+                price_key,
+                feeds,
+                route,
+                tasks,
+            },
+        );
+    }
+    if !seeds.is_empty() {
+        tokio::spawn(seed_close_prices(
+            prices.clone(),
+            subscriptions.clone(),
+            seeds,
+        ));
+    }
+}
+
+/// Add the Pyth feed for `(symbol, category)` to the shared stream, writing
+/// into the price map under `key`; a holding without a feed is only logged.
+fn subscribe_feed(streams: &PythStreams, key: &str, symbol: &str, category: &str) {
+    match get_pyth_feed_id(symbol, category) {
+        Ok(id) => {
+            streams.subscribe(key, &id);
+        }
+        Err(e) => crate::log_line!("[pyth] no live feed for {} ({}): {}", symbol, category, e),
+    }
+}
+
+/// Seed new Taiwan holdings, given as `(subscription key, symbol, category)`,
+/// with their cached close prices from one batched MIS request (TWSE, or TPEx
+/// for `TW-OTC`). A holding unsubscribed while the request was in flight is
+/// left out.
+async fn seed_close_prices(
+    prices: SharedPriceMap,
+    subscriptions: Subscriptions,
+    seeds: Vec<(String, String, String)>,
+) {
+    let holdings: Vec<(String, String)> = seeds
+        .iter()
+        .map(|(_, symbol, category)| (symbol.clone(), category.clone()))
+        .collect();
+    let mut quotes = get_quotes_from_twse(&holdings).await;
+    let active = subscriptions.lock().await;
+    let now = Utc::now().timestamp();
+    for (key, symbol, _) in seeds {
+        if !active.contains_key(&key) {
+            continue;
+        }
+        let close = match quotes.remove(&symbol) {
+            Some(Ok(quote)) => quote
+                .prev_close
+                .ok_or_else(|| "[TWSE] Failed to parse previous close as float".to_string()),
+            Some(Err(e)) => Err(e),
+            None => Err("[TWSE] Cannot find stock data".to_string()),
+        };
+        match close {
+            Ok(price) => prices.lock().await.insert(symbol, price, "twse", now),
+            Err(e) => crate::log_line!("Failed to seed close price for {}: {}", symbol, e),
+        }
+    }
+}
+
+/// Quote an exchange-listed holding over REST now and every
+/// [`EXCHANGE_POLL_SECS`] after. Runs until its subscription is dropped.
+async fn poll_exchange_price(prices: SharedPriceMap, symbol: String, category: String) {
+    let mut interval = tokio::time::interval(Duration::from_secs(EXCHANGE_POLL_SECS));
+    loop {
+        interval.tick().await;
+        match get_price(&symbol, &category).await {
+            Ok(price) => {
+                let now = Utc::now().timestamp();
+                prices
+                    .lock()
+                    .await
+                    .insert(symbol.clone(), price, "rest", now);
+            }
+            Err(e) => crate::log_line!("Failed to get price for {} ({}): {}", symbol, category, e),
+        }
+    }
+}
+
+/// Write the rate `legs` multiply out to under `key` (see [`fx::derive_rate`])
+/// every [`FX_DERIVE_SECS`], tagged with the route's `source`. Runs until its
+/// subscription is dropped.
+async fn derive_fx_rate(
+    prices: SharedPriceMap,
+    key: String,
+    legs: Vec<fx::Leg>,
+    source: &'static str,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(FX_DERIVE_SECS));
+    loop {
+        interval.tick().await;
+        let mut map = prices.lock().await;
+        if let Some((rate, updated_at)) = fx::derive_rate(&legs, &map) {
+            map.insert(key.clone(), rate, source, updated_at);
+        }
+    }
+}
+
+/// Poll the `USD/{currency}` rate, which Pyth cannot provide, from
+/// ExchangeRate-API now and every [`fx::REST_POLL_SECS`] after. Runs until its
+/// subscription is dropped.
+async fn poll_fx_rate(prices: SharedPriceMap, key: String, currency: String) {
+    let mut interval = tokio::time::interval(Duration::from_secs(fx::REST_POLL_SECS));
+    loop {
+        interval.tick().await;
+        match exchangerate::get_rate(valuation::BASE_CURRENCY, &currency).await {
+            Ok(rate) => {
+                let now = Utc::now().timestamp();
+                prices
+                    .lock()
+                    .await
+                    .insert(key.clone(), rate, fx::REST_SOURCE, now);
             }
+            Err(e) => crate::log_line!("[fx] failed to poll {}: {}", key, e),
         }
     }
 }
 
-/// Record `key` as subscribed, returning `true` only if it was not already
-/// present (i.e. this is the first time we've seen it).
-async fn mark_new(subscribed: &SubscribedSet, key: &str) -> bool {
-    subscribed.lock().await.insert(key.to_string())
+/// Each subscribed forex rate with the route it is obtained through, in key
+/// order.
+async fn fx_routes(subscriptions: &Subscriptions) -> Vec<(String, FxRoute)> {
+    let active = subscriptions.lock().await;
+    let mut routes: Vec<(String, FxRoute)> = active
+        .values()
+        .filter_map(|sub| Some((sub.price_key.clone(), sub.route.clone()?)))
+        .collect();
+    routes.sort_by(|a, b| a.0.cmp(&b.0));
+    routes
 }
 
 /// Most-recent modification time of `path`, or `None` if it can't be read.
@@ -231,38 +617,77 @@ fn file_mtime(path: &str) -> Option<std::time::SystemTime> {
 }
 
 /// Poll the config files and, when one changes on disk, swap the new values
-/// into the shared state and subscribe to any newly required streams. Reader
-/// tasks (display loop, polling, snapshots) observe the change automatically.
+/// into the shared state and reconcile the subscriptions (starting new streams,
+/// stopping and evicting unneeded ones). Reader tasks (display loop, polling,
+/// snapshots) observe the change automatically.
+///
+/// While `transactions.toml` exists it is the source of truth for holdings and
+/// edits to `portfolio.toml` are ignored; deleting the ledger falls back to
+/// `portfolio.toml`.
 async fn watch_config(
     portfolio: SharedPortfolio,
     target_forex: SharedTargetForex,
     prices: SharedPriceMap,
-    subscribed: SubscribedSet,
+    streams: PythStreams,
+    subscriptions: Subscriptions,
 ) {
     let mut interval = tokio::time::interval(Duration::from_secs(CONFIG_POLL_SECS));
     interval.tick().await; // Skip the immediate first tick.
 
     // Resolve the paths once; the env vars they derive from don't change at runtime.
     let portfolio_path = paths::portfolio_file();
+    let ledger_path = paths::transactions_file();
     let target_path = paths::target_forex_file();
+    let sources_path = paths::sources_file();
+    let holidays_paths = [
+        paths::twse_holidays_file(),
+        paths::twse_holidays_cache_file(),
+    ];
 
     let mut portfolio_mtime = file_mtime(&portfolio_path);
+    let mut ledger_mtime = file_mtime(&ledger_path);
     let mut target_mtime = file_mtime(&target_path);
+    let mut sources_mtime = file_mtime(&sources_path);
+    let mut holidays_mtimes = holidays_paths.each_ref().map(|path| file_mtime(path));
 
     loop {
         interval.tick().await;
         let mut changed = false;
 
+        let new_ledger_mtime = file_mtime(&ledger_path);
+        let ledger_changed = new_ledger_mtime != ledger_mtime;
+        if ledger_changed {
+            ledger_mtime = new_ledger_mtime;
+            match ledger::read_ledger(&ledger_path) {
+                Ok(Some(ledger)) => {
+                    crate::log_line!("[config] transactions.toml reloaded");
+                    *portfolio.write().await = ledger.portfolio();
+                    changed = true;
+                }
+                // The ledger was removed: holdings come from portfolio.toml again.
+                Ok(None) => portfolio_mtime = None,
+                Err(e) => crate::log_line!("[config] failed to reload transactions.toml: {}", e),
+            }
+        }
+
         let new_portfolio_mtime = file_mtime(&portfolio_path);
         if new_portfolio_mtime != portfolio_mtime {
             portfolio_mtime = new_portfolio_mtime;
-            match config::try_read_portfolio(&portfolio_path) {
-                Ok(new_portfolio) => {
-                    crate::log_line!("[config] portfolio.toml reloaded");
-                    *portfolio.write().await = new_portfolio;
-                    changed = true;
+            if ledger_mtime.is_some() {
+                if !ledger_changed {
+                    crate::log_line!(
+                        "[config] portfolio.toml changed but transactions.toml is in use; ignoring"
+                    );
+                }
+            } else {
+                match config::try_read_portfolio(&portfolio_path) {
+                    Ok(new_portfolio) => {
+                        crate::log_line!("[config] portfolio.toml reloaded");
+                        *portfolio.write().await = new_portfolio;
+                        changed = true;
+                    }
+                    Err(e) => crate::log_line!("[config] failed to reload portfolio.toml: {}", e),
                 }
-                Err(e) => crate::log_line!("[config] failed to reload portfolio.toml: {}", e),
             }
         }
 
@@ -270,72 +695,85 @@ async fn watch_config(
         if new_target_mtime != target_mtime {
             target_mtime = new_target_mtime;
             let new_target = config::read_target_forex_or_default(&target_path);
-            crate::log_line!("[config] target_forex.toml reloaded -> {}", new_target);
+            crate::log_line!(
+                "[config] target_forex.toml reloaded -> {}",
+                new_target.join(", ")
+            );
             *target_forex.write().await = new_target;
             changed = true;
         }
 
+        // Provider chains are read on every lookup, so no resubscribe is needed.
+        let new_sources_mtime = file_mtime(&sources_path);
+        if new_sources_mtime != sources_mtime {
+            sources_mtime = new_sources_mtime;
+            match sources::reload() {
+                Ok(()) => crate::log_line!("[config] sources.toml reloaded"),
+                Err(e) => crate::log_line!("[config] failed to reload sources.toml: {}", e),
+            }
+        }
+
+        // The TWSE calendar is consulted on every check, like the chains above.
+        let new_holidays_mtimes = holidays_paths.each_ref().map(|path| file_mtime(path));
+        if new_holidays_mtimes != holidays_mtimes {
+            holidays_mtimes = new_holidays_mtimes;
+            match calendar::reload_twse() {
+                Ok(()) => crate::log_line!("[config] TWSE holidays reloaded"),
+                Err(e) => crate::log_line!("[config] failed to reload TWSE holidays: {}", e),
+            }
+        }
+
         if changed {
-            // Snapshot the latest config (releasing the locks) before the
-            // network calls in `ensure_subscriptions`.
+            // Snapshot the latest config (releasing the locks) before
+            // reconciling the subscriptions.
             let current_portfolio = portfolio.read().await.clone();
             let current_target = target_forex.read().await.clone();
-            ensure_subscriptions(&current_portfolio, &current_target, &prices, &subscribed).await;
+            reconcile_subscriptions(
+                &current_portfolio,
+                &current_target,
+                &prices,
+                &streams,
+                &subscriptions,
+            )
+            .await;
         }
     }
 }
 
-/// Reconstruct daily historical snapshots from API back-fill using the current
-/// holdings, then merge with any existing on-disk history and persist.
-async fn backfill_history_task(history: SharedHistory, portfolio: Portfolio) {
+/// Reconstruct daily historical snapshots from API back-fill, then merge with
+/// any existing on-disk history and persist.
+///
+/// Without a ledger the current `portfolio` quantities are applied to every past
+/// day. With one, each day is valued using the holdings the ledger says were
+/// held at the end of that day, and every instrument ever traded is fetched.
+pub(crate) async fn backfill_history_task(
+    history: SharedHistory,
+    portfolio: Portfolio,
+    ledger: Option<Ledger>,
+) {
     let to = Utc::now().timestamp();
     let from = to - BACKFILL_WINDOW_SECS;
 
+    let instruments = match &ledger {
+        Some(ledger) => ledger.instruments(),
+        None => portfolio.clone(),
+    };
+
     // (price-map key, fetch symbol, category)
     let mut requests: Vec<(String, String, String)> = Vec::new();
-    let mut has_tw = false;
-    let mut has_twd_forex = false;
+    // Every holding's quote currency, whose rate it is valued through.
+    let mut currencies = std::collections::BTreeSet::new();
 
-    for item in portfolio.iter() {
-        match item.category.as_str() {
-            "Forex" => {
-                if item.symbol == "USD" {
-                    continue; // USD is the base currency; no rate needed.
-                }
-                if item.symbol == "TWD" {
-                    has_twd_forex = true;
-                }
-                requests.push((
-                    format!("USD/{}", item.symbol),
-                    item.symbol.clone(),
-                    "Forex".to_string(),
-                ));
-            }
-            "TW-Stock" | "TW-ETF" => {
-                has_tw = true;
-                requests.push((
-                    item.symbol.clone(),
-                    item.symbol.clone(),
-                    item.category.clone(),
-                ));
-            }
-            _ => {
-                requests.push((
-                    item.symbol.clone(),
-                    item.symbol.clone(),
-                    item.category.clone(),
-                ));
-            }
+    for item in instruments.iter() {
+        if let Some(key) = valuation::price_key(item) {
+            requests.push((key.to_string(), item.symbol.clone(), item.category.clone()));
         }
+        currencies.insert(valuation::quote_currency(&item.symbol, &item.category));
     }
-
-    // TW holdings need a USD/TWD rate even if TWD isn't held as cash.
-    if has_tw && !has_twd_forex {
-        requests.push((
-            "USD/TWD".to_string(),
-            "TWD".to_string(),
-            "Forex".to_string(),
-        ));
+    for currency in currencies {
+        if let Some(key) = valuation::rate_key(&currency) {
+            requests.push((key, currency, "Forex".to_string()));
+        }
     }
 
     // Bucket every fetched close into a per-UTC-day price map.
@@ -352,22 +790,24 @@ async fn backfill_history_task(history: SharedHistory, portfolio: Portfolio) {
         }
     }
 
-    // Rebuild a snapshot for each day using current quantities.
+    // Rebuild a snapshot for each day using that day's quantities.
     let mut backfilled = Vec::new();
     for (day, map) in day_maps {
+        let day_portfolio = match (&ledger, DateTime::from_timestamp(day * 86_400, 0)) {
+            (Some(ledger), Some(date)) => ledger.portfolio_as_of(date.date_naive()),
+            _ => portfolio.clone(),
+        };
+        // Nothing was held yet (before the first ledger entry).
+        if day_portfolio.0.is_empty() {
+            continue;
+        }
         // Days that lack a close for some holdings (e.g. weekends, when TWSE is
         // shut but crypto still trades) would value the missing assets at zero,
         // so skip them rather than recording an artificially low total.
-        if !history::is_complete(&portfolio, &map) {
+        if !history::is_complete(&day_portfolio, &map) {
             continue;
         }
-        let (category_values, total) = history::compute_category_values(&portfolio, &map);
-        backfilled.push(PortfolioSnapshot {
-            timestamp: day * 86_400,
-            total_value_usd: total,
-            category_values,
-            prices: map,
-        });
+        backfilled.push(history::snapshot_at(day * 86_400, &day_portfolio, &map));
     }
 
     // Merge with existing history (existing wins per day) and persist.
@@ -381,91 +821,140 @@ async fn backfill_history_task(history: SharedHistory, portfolio: Portfolio) {
 }
 
 /// Append a live snapshot of the portfolio at a fixed interval.
+///
+/// With `us_closes` (see [`RunOptions::us_close_only`]) it also wakes at each
+/// NYSE closing bell to record the US equities' regular-session closes.
 async fn snapshot_recorder(
     history: SharedHistory,
     prices: SharedPriceMap,
     portfolio: SharedPortfolio,
+    reject_stale: bool,
+    us_closes: Option<UsCloses>,
 ) {
     let mut interval = tokio::time::interval(Duration::from_secs(SNAPSHOT_INTERVAL_SECS));
     interval.tick().await; // Skip the immediate first tick.
 
     loop {
-        interval.tick().await;
+        let now = Utc::now().timestamp();
+        let next_close = us_closes
+            .as_ref()
+            .and_then(|_| calendar::nyse().next_regular_close(now));
+        let closing_bell = async {
+            match next_close {
+                Some(close) => tokio::time::sleep(Duration::from_secs((close - now) as u64)).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = interval.tick() => {
+                record_snapshot(&history, &prices, &portfolio, reject_stale, us_closes.as_ref())
+                    .await;
+            }
+            _ = closing_bell => {
+                if let Some(closes) = &us_closes {
+                    let mut values = prices.lock().await.values();
+                    let portfolio = portfolio.read().await;
+                    apply_us_closes(&portfolio, &mut values, &mut *closes.lock().await, true);
+                }
+            }
+        }
+    }
+}
 
-        let map = { prices.lock().await.clone() };
-        let portfolio = portfolio.read().await.clone();
-        // Only record once every holding has a price; a partial map would
-        // understate the total and produce spurious dips in the history.
-        if !history::is_complete(&portfolio, &map) {
+/// Track and apply regular-session closes of the US equities in `portfolio`:
+/// during the session (`in_session`) each live price in `values` is saved to
+/// `closes`; outside it, each price in `values` is replaced by the saved close,
+/// if there is one.
+fn apply_us_closes(
+    portfolio: &Portfolio,
+    values: &mut HashMap<String, f64>,
+    closes: &mut HashMap<String, f64>,
+    in_session: bool,
+) {
+    for item in portfolio.iter() {
+        if !matches!(item.category.as_str(), "US-Stock" | "US-ETF") {
             continue;
         }
-        let snapshot = history::take_snapshot(&portfolio, &map);
-
-        // Add the snapshot, then downsample so both the in-memory Vec and the
-        // on-disk file stay bounded (recent high-res + one-per-day for older
-        // data) instead of growing forever. The file is rewritten rather than
-        // appended; after downsampling it is small, so this is cheap.
-        let mut guard = history.lock().await;
-        guard.push(snapshot);
-        let bounded = history::downsample(std::mem::take(&mut *guard), Utc::now().timestamp());
-        if let Err(e) = history::save_all(&paths::history_file(), &bounded) {
-            crate::log_line!("[snapshot] failed to persist: {}", e);
+        if in_session {
+            if let Some(price) = values.get(&item.symbol) {
+                closes.insert(item.symbol.clone(), *price);
+            }
+        } else if let Some(close) = closes.get(&item.symbol) {
+            values.insert(item.symbol.clone(), *close);
         }
-        *guard = bounded;
     }
 }
 
+/// Take a snapshot from the current prices, add it to `history` and persist.
+/// Returns `false` (recording nothing) if some holding has no price yet or,
+/// with `reject_stale`, a price it needs is stale. With `us_closes`, US
+/// equities are valued at their regular-session close outside the session.
+async fn record_snapshot(
+    history: &SharedHistory,
+    prices: &SharedPriceMap,
+    portfolio: &SharedPortfolio,
+    reject_stale: bool,
+    us_closes: Option<&UsCloses>,
+) -> bool {
+    let prices = { prices.lock().await.clone() };
+    let portfolio = portfolio.read().await.clone();
+    // Only record once every holding has a price; a partial map would
+    // understate the total and produce spurious dips in the history.
+    if !history::is_complete_live(&portfolio, &prices, reject_stale, Utc::now().timestamp()) {
+        return false;
+    }
+    let mut values = prices.values();
+    if let Some(closes) = us_closes {
+        let in_session = calendar::nyse().session_at(Utc::now()) == UsSession::Regular;
+        apply_us_closes(
+            &portfolio,
+            &mut values,
+            &mut *closes.lock().await,
+            in_session,
+        );
+    }
+    let snapshot = history::take_snapshot(&portfolio, &values);
+
+    // Add the snapshot, then downsample so both the in-memory Vec and the
+    // on-disk file stay bounded (recent high-res + one-per-day for older
+    // data) instead of growing forever. The file is rewritten rather than
+    // appended; after downsampling it is small, so this is cheap.
+    let mut guard = history.lock().await;
+    guard.push(snapshot);
+    let bounded = history::downsample(std::mem::take(&mut *guard), Utc::now().timestamp());
+    if let Err(e) = history::save_all(&paths::history_file(), &bounded) {
+        crate::log_line!("[snapshot] failed to persist: {}", e);
+    }
+    *guard = bounded;
+    true
+}
+
 /// Determine every forex pair (as `USD/{ccy}`) whose live rate is required to
 /// value the portfolio in USD and to render the display currency.
 ///
 /// Dependencies:
-/// - Each non-USD Forex cash holding needs its own `USD/{ccy}` rate.
-/// - Taiwan equities (TW-Stock/TW-ETF) are priced in TWD, so they depend on
-///   `USD/TWD` even when no TWD cash is held.
-/// - The display currency needs `USD/{target}` for the converted total line.
+/// - Each holding needs the rate of its quote currency (see
+///   [`valuation::quote_currency`]): Forex cash its own currency, Taiwan
+///   equities `USD/TWD` even when no TWD cash is held, and so on.
+/// - Each display currency needs `USD/{target}` for its converted total line.
+/// - A holding whose cost basis is in another currency needs that currency's
+///   rate to convert its cost for the unrealized P&L.
 ///
 /// USD is the base currency (`USD/USD` is trivially 1.0 and has no Pyth feed),
 /// so it is never included.
-fn required_forex_pairs(portfolio: &Portfolio, target_forex: &str) -> Vec<String> {
-    let mut currencies: std::collections::BTreeSet<String> = std::collections::BTreeSet::new();
-
-    for item in portfolio.iter() {
-        match item.category.as_str() {
-            "Forex" => {
-                currencies.insert(item.symbol.to_uppercase());
-            }
-            "TW-Stock" | "TW-ETF" => {
-                currencies.insert("TWD".to_string());
-            }
-            _ => {}
-        }
-    }
-
-    currencies.insert(target_forex.to_uppercase());
+pub(crate) fn required_forex_pairs(portfolio: &Portfolio, target_forex: &[String]) -> Vec<String> {
+    let currencies: std::collections::BTreeSet<String> = portfolio
+        .iter()
+        .flat_map(valuation::item_currencies)
+        .chain(target_forex.iter().map(|ccy| ccy.to_uppercase()))
+        .collect();
 
     currencies
-        .into_iter()
-        .filter(|ccy| ccy != "USD")
-        .map(|ccy| format!("USD/{}", ccy))
+        .iter()
+        .filter_map(|ccy| valuation::rate_key(ccy))
         .collect()
 }
 
-async fn start_forex_stream(prices: SharedPriceMap, forex_symbol: &str) {
-    let id = match get_pyth_feed_id(forex_symbol, "Forex") {
-        Ok(id) => id,
-        Err(e) => {
-            crate::log_line!("[forex] cannot subscribe to {}: {}", forex_symbol, e);
-            return;
-        }
-    };
-    let forex_symbol = forex_symbol.to_string();
-
-    tokio::spawn(async move {
-        // Reconnects automatically; forex rates must not silently go stale.
-        stream_into_map(id, forex_symbol, prices).await;
-    });
-}
-
 /// Switch into a dedicated full-screen buffer so the TUI never draws over (or
 /// leaves residue in) the user's normal terminal scrollback.
 fn setup_terminal() -> Terminal<CrosstermBackend<std::io::Stdout>> {
@@ -502,12 +991,16 @@ async fn run_display_loop<B: ratatui::backend::Backend>(
     history: &SharedHistory,
     portfolio: &SharedPortfolio,
     target_forex: &SharedTargetForex,
+    subscriptions: &Subscriptions,
 ) {
     let mut view_mode = ViewMode::Live;
+    // Index into the display currencies the per-holding lines are shown in.
+    let mut display_index = 0;
 
     loop {
         // Handle key presses: 'q' quits, Tab toggles between the main (live)
-        // page and the history page; 'h'/'l' remain as explicit shortcuts.
+        // page and the history page; 'h'/'l' remain as explicit shortcuts and
+        // 'c' cycles the display currency.
         //
         // `event::poll`/`event::read` can fail with an I/O error (stdin closed,
         // terminal disconnected, or a non-interactive environment). Treat that as
@@ -519,12 +1012,18 @@ async fn run_display_loop<B: ratatui::backend::Backend>(
                     KeyCode::Tab => view_mode = view_mode.toggle(),
                     KeyCode::Char('h') => view_mode = ViewMode::History,
                     KeyCode::Char('l') => view_mode = ViewMode::Live,
+                    KeyCode::Char('c') => display_index += 1,
                     KeyCode::Char('e') => {
                         let snapshot = { history.lock().await.clone() };
                         if let Err(e) = history::export_csv(&snapshot, &paths::history_csv_file()) {
                             crate::log_line!("[export] {}", e);
                         }
                     }
+                    KeyCode::Char('r') => {
+                        // Needs FX history from the network, so run it off the
+                        // display loop.
+                        tokio::spawn(export_realized_gains());
+                    }
                     _ => {}
                 },
                 Ok(_) => {}
@@ -536,8 +1035,13 @@ async fn run_display_loop<B: ratatui::backend::Backend>(
 
         let portfolio = portfolio.read().await.clone();
         let target_forex = target_forex.read().await.clone();
+        let fx_routes = fx_routes(subscriptions).await;
+        // The list can shrink on reload, so wrap the index on every frame.
+        let currencies = tui::display_currencies(&target_forex);
+        let display_currency = &currencies[display_index % currencies.len()];
         let map = prices.lock().await;
-        let (lines, total_value) = build_portfolio_display(&map, &portfolio);
+        let now = Utc::now().timestamp();
+        let (lines, total_value) = build_portfolio_display(&map, &portfolio, display_currency, now);
 
         // Borrow the history under its lock for the synchronous draw instead of
         // cloning the whole (unbounded) Vec every frame. The draw holds no
@@ -552,6 +1056,8 @@ async fn run_display_loop<B: ratatui::backend::Backend>(
             total_value,
             &map,
             &target_forex,
+            display_currency,
+            &fx_routes,
             &portfolio,
             &history_guard,
             view_mode,
@@ -561,10 +1067,115 @@ async fn run_display_loop<B: ratatui::backend::Backend>(
     }
 }
 
-fn build_portfolio_display(
+/// Write the FIFO realized-gains report for the current ledger, logging the
+/// outcome. Does nothing useful without `transactions.toml`.
+async fn export_realized_gains() {
+    let path = paths::realized_gains_csv_file();
+    match ledger::read_ledger(&paths::transactions_file()) {
+        Ok(Some(ledger)) => match gains::export_report(&ledger, LotMethod::Fifo, &path).await {
+            Ok(n) => crate::log_line!("[gains] wrote {} sales to {}", n, path),
+            Err(e) => crate::log_line!("[gains] {}", e),
+        },
+        Ok(None) => crate::log_line!("[gains] no transactions.toml; nothing to report"),
+        Err(e) => crate::log_line!("[gains] failed to read transactions.toml: {}", e),
+    }
+}
+
+/// ` [age]` for the price under `key`, or ` [STALE age]` once it is older than
+/// the threshold for `category`, followed by ` [LAG age]` when the feed's
+/// publish time is that far behind; empty if there is no price.
+fn age_marker(prices: &PriceMap, key: &str, category: &str, now: i64) -> String {
+    let mut marker = match prices.age(key, now) {
+        Some(age) if history::is_stale(prices, key, category, now) => {
+            format!(" [STALE {}]", tui::format_age(age))
+        }
+        Some(age) => format!(" [{}]", tui::format_age(age)),
+        None => return String::new(),
+    };
+    if history::is_lagging(prices, key, category, now)
+        && let Some(lag) = history::publish_lag(prices, key, now)
+    {
+        marker.push_str(&format!(" [LAG {}]", tui::format_age(lag)));
+    }
+    marker
+}
+
+/// ` ±conf` for a price whose feed reports a confidence interval; empty
+/// otherwise.
+fn conf_marker(prices: &PriceMap, key: &str) -> String {
+    match prices.get(key).and_then(|entry| entry.conf) {
+        Some(conf) if conf >= 0.01 => format!(" ±{:.2}", conf),
+        Some(conf) => format!(" ±{:.6}", conf),
+        None => String::new(),
+    }
+}
+
+/// ` [PRE]`, ` [REG]`, ` [POST]` or ` [CLOSED]`: the NYSE session at `now` for
+/// a US equity, so an extended-hours or frozen price is not read as a
+/// regular-session one; empty for other categories.
+fn session_marker(category: &str, now: i64) -> String {
+    match (category, DateTime::from_timestamp(now, 0)) {
+        ("US-Stock" | "US-ETF", Some(now)) => {
+            format!(" [{}]", calendar::nyse().session_at(now))
+        }
+        _ => String::new(),
+    }
+}
+
+/// `  (Converted to {display}): {value} / {rate} = {converted}` for `value` in
+/// `currency`, with the age of each rate it went through, or a warning naming
+/// the missing rate; `None` when `currency` is already the display currency.
+fn conversion_line(
+    prices: &PriceMap,
     map: &HashMap<String, f64>,
+    value: Decimal,
+    currency: &str,
+    display_currency: &str,
+    now: i64,
+) -> Option<String> {
+    if currency.eq_ignore_ascii_case(display_currency) {
+        return None;
+    }
+    let rate_keys: Vec<String> = [currency, display_currency]
+        .into_iter()
+        .filter_map(valuation::rate_key)
+        .collect();
+    let Some(rate) = valuation::cross_rate(map, display_currency, currency) else {
+        let missing = rate_keys
+            .iter()
+            .find(|key| valuation::price(map, key).is_none_or(|rate| rate.is_zero()))
+            .map_or(display_currency, String::as_str);
+        return Some(format!("  [Warning] {} rate not available", missing));
+    };
+    let ages: String = rate_keys
+        .iter()
+        .map(|key| age_marker(prices, key, "Forex", now))
+        .collect();
+    let converted = value.checked_div(rate)?;
+    Some(format!(
+        "  (Converted to {}): {}{:.2} / {:.4} = {}{:.2}{}",
+        display_currency.to_uppercase(),
+        valuation::currency_unit(currency),
+        value,
+        rate,
+        valuation::currency_unit(display_currency),
+        converted,
+        ages
+    ))
+}
+
+/// One text block per holding (price x quantity = value, conversion into
+/// `display_currency`, P&L) with each streamed quote's confidence band, each
+/// quote's age as of `now`, stale or lagging quotes marked and each US
+/// equity's session, plus the USD total.
+fn build_portfolio_display(
+    prices: &PriceMap,
     portfolio: &Portfolio,
-) -> (Vec<String>, f64) {
+    display_currency: &str,
+    now: i64,
+) -> (Vec<String>, Decimal) {
+    let values = prices.values();
+    let map = &values;
     // Delegate USD valuation to the single source of truth so the total here
     // always matches the allocation bar chart (which also calls this function).
     let (_, total_value) = history::compute_category_values(portfolio, map);
@@ -587,30 +1198,35 @@ fn build_portfolio_display(
         for item in items {
             let symbol = &item.symbol;
             let amount = item.quantity;
-            if let Some(price) = map.get(symbol) {
-                let asset_value = price * amount;
-
-                if category == "TW-Stock" || category == "TW-ETF" {
-                    lines.push(format!(
-                        "{}: NT${:.2} x {:.4} = NT${:.2}",
-                        symbol, price, amount, asset_value
-                    ));
-
-                    match map.get("USD/TWD") {
-                        Some(rate) if *rate != 0.0 => {
-                            let usd_value = asset_value / rate;
-                            lines.push(format!(
-                                "  (Converted to USD): ${:.2} / {:.4} = ${:.2}",
-                                asset_value, rate, usd_value
-                            ));
-                        }
-                        _ => lines.push("  [Warning] USD/TWD rate not available".to_string()),
-                    }
-                } else {
-                    lines.push(format!(
-                        "{}: ${:.2} x {:.4} = ${:.2}",
-                        symbol, price, amount, asset_value
-                    ));
+            if let Some(price) = valuation::price(map, symbol)
+                && let Some(asset_value) = price.checked_mul(amount)
+            {
+                let currency = valuation::quote_currency(symbol, &category);
+                let unit = valuation::currency_unit(&currency);
+                lines.push(format!(
+                    "{}: {}{:.2}{} x {:.4} = {}{:.2}{}{}",
+                    symbol,
+                    unit,
+                    price,
+                    conf_marker(prices, symbol),
+                    amount,
+                    unit,
+                    asset_value,
+                    age_marker(prices, symbol, &category, now),
+                    session_marker(&category, now)
+                ));
+
+                lines.extend(conversion_line(
+                    prices,
+                    map,
+                    asset_value,
+                    &currency,
+                    display_currency,
+                    now,
+                ));
+
+                if let Some(pnl) = history::holding_pnl(&item, map) {
+                    lines.push(format!("  (Unrealized P&L): {}", tui::format_pnl(&pnl)));
                 }
             }
         }
@@ -624,35 +1240,34 @@ fn build_portfolio_display(
         for item in forex_items {
             let symbol = &item.symbol;
             let quantity = item.quantity;
+            let unit = valuation::currency_unit(symbol);
             lines.push(format!(
-                "{}: ${:.2} x {:.4} = ${:.2}",
-                symbol, 1.0, quantity, quantity
+                "{}: {}{:.2} x {:.4} = {}{:.2}",
+                symbol, unit, 1.0, quantity, unit, quantity
+            ));
+            lines.extend(conversion_line(
+                prices,
+                map,
+                quantity,
+                symbol,
+                display_currency,
+                now,
             ));
-
-            if symbol != "USD" {
-                let forex_key = format!("USD/{}", symbol);
-                match map.get(&forex_key) {
-                    Some(forex_price) if *forex_price != 0.0 => {
-                        let converted_value = quantity / forex_price;
-                        lines.push(format!(
-                            "  (Converted to USD): ${:.2} / {:.4} = ${:.2}",
-                            quantity, forex_price, converted_value
-                        ));
-                    }
-                    _ => lines.push(format!("  Cannot get forex rate for {}", symbol)),
-                }
-            }
         }
     }
 
     (lines, total_value)
 }
 
-/// Poll Taiwan-market (`TW-Stock`, `TW-ETF`) prices every `cycle` seconds and
+/// Poll Taiwan-market (`TW-Stock`, `TW-ETF`, `TW-OTC`) prices every `cycle` seconds and
 /// write them into `prices`.
 ///
+/// Each cycle fetches every holding with [`get_prices`], so holdings on the
+/// TWSE chain share one batched MIS request instead of one request each.
+///
 /// Loops forever. The first immediate tick is skipped, and cycles where the TWSE
-/// market is closed are skipped (cached prices remain). Holdings are re-read from
+/// market is closed, including calendar holidays, are skipped (cached prices
+/// remain). Holdings are re-read from
 /// `portfolio` each cycle so hot-reloaded changes are picked up.
 pub async fn polling_stream(prices: SharedPriceMap, cycle: u64, portfolio: SharedPortfolio) {
     let mut interval = tokio::time::interval(Duration::from_secs(cycle));
@@ -671,58 +1286,119 @@ pub async fn polling_stream(prices: SharedPriceMap, cycle: u64, portfolio: Share
         // Read the latest holdings each cycle so hot-reloaded changes are picked
         // up without restarting the stream.
         let portfolio = portfolio.read().await.clone();
-
-        let mut tasks = FuturesUnordered::new();
-
-        for category in ["TW-Stock", "TW-ETF"] {
-            if let Some(items) = portfolio.get(category) {
-                for item in items {
-                    let symbol = item.symbol.clone();
-                    let amount = item.quantity;
-                    let category = category.to_string();
-
-                    tasks.push(async move {
-                        let mut attempts = 0;
-                        let max_attempts = 3;
-                        let mut delay = Duration::from_secs(1);
-
-                        loop {
-                            match get_price(&symbol, &category).await {
-                                Ok(price) => break Some((symbol.clone(), amount, price)),
-                                Err(e) => {
-                                    if attempts < max_attempts {
-                                        attempts += 1;
-                                        tokio::time::sleep(delay).await;
-                                        delay *= 2;
-                                    } else {
-                                        crate::log_line!(
-                                            "Failed to get price for {}: {}",
-                                            symbol,
-                                            e
-                                        );
-                                        break None;
-                                    }
-                                }
-                            }
-                        }
-                    });
+        let mut pending: Vec<(String, String)> = Vec::new();
+        for category in ["TW-Stock", "TW-ETF", "TW-OTC"] {
+            for item in portfolio.get(category).into_iter().flatten() {
+                if !pending.iter().any(|(symbol, _)| *symbol == item.symbol) {
+                    pending.push((item.symbol.clone(), category.to_string()));
                 }
             }
         }
 
-        while let Some(result) = tasks.next().await {
-            if let Some((symbol, _amount, price)) = result {
-                let mut map = prices.lock().await;
-                map.insert(symbol, price);
+        // One batched lookup per attempt; only the symbols that failed are
+        // retried, with exponential backoff.
+        let max_attempts = 3;
+        let mut delay = Duration::from_secs(1);
+        for attempt in 0..=max_attempts {
+            if pending.is_empty() {
+                break;
+            }
+            if attempt > 0 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
             }
+            let mut results = get_prices(&pending).await;
+            let now = Utc::now().timestamp();
+            let mut map = prices.lock().await;
+            pending.retain(|(symbol, _)| match results.remove(symbol) {
+                Some(Ok(price)) => {
+                    map.insert(symbol.clone(), price, "rest", now);
+                    false
+                }
+                Some(Err(e)) if attempt == max_attempts => {
+                    crate::log_line!("Failed to get price for {}: {}", symbol, e);
+                    false
+                }
+                _ => true,
+            });
         }
     }
 }
 
+/// Every [`CRYPTO_BACKUP_POLL_SECS`], refresh over REST each crypto holding
+/// whose stream has produced no price within the interval, so a dead feed falls
+/// back to RedStone/Binance the way Taiwan equities are polled.
+///
+/// Loops forever. Holdings are re-read from `portfolio` each cycle so
+/// hot-reloaded changes are picked up.
+pub async fn crypto_backup_stream(prices: SharedPriceMap, portfolio: SharedPortfolio) {
+    let mut interval = tokio::time::interval(Duration::from_secs(CRYPTO_BACKUP_POLL_SECS));
+    // Skip the first immediate tick; holdings are seeded on subscription.
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+
+        let symbols: Vec<String> = {
+            let portfolio = portfolio.read().await;
+            let map = prices.lock().await;
+            let now = Utc::now().timestamp();
+            portfolio
+                .iter()
+                .filter(|item| item.category == "Crypto")
+                .filter(|item| needs_backup(&map, &item.symbol, now))
+                .map(|item| item.symbol.clone())
+                .collect()
+        };
+        futures::future::join_all(
+            symbols
+                .into_iter()
+                .map(|symbol| backup_crypto_price(prices.clone(), symbol)),
+        )
+        .await;
+    }
+}
+
+/// Whether the crypto price under `key` is missing or was not updated within
+/// the last [`CRYPTO_BACKUP_POLL_SECS`].
+fn needs_backup(prices: &PriceMap, key: &str, now: i64) -> bool {
+    prices
+        .age(key, now)
+        .is_none_or(|age| age >= CRYPTO_BACKUP_POLL_SECS as i64)
+}
+
+/// Fetch `symbol`'s crypto price over REST and store it, unless a fresh price
+/// (e.g. from the Pyth stream) arrived in the meantime.
+async fn backup_crypto_price(prices: SharedPriceMap, symbol: String) {
+    match get_price(&symbol, "Crypto").await {
+        Ok(price) => {
+            let now = Utc::now().timestamp();
+            let mut map = prices.lock().await;
+            if needs_backup(&map, &symbol, now) {
+                map.insert(symbol, price, "rest", now);
+            }
+        }
+        Err(e) => crate::log_line!("[crypto] REST fallback for {} failed: {}", symbol, e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::PortfolioItem;
+    use crate::types::{PortfolioItem, PriceEntry};
+    use rust_decimal::prelude::FromPrimitive;
+
+    const NOW: i64 = 1_700_000_000;
+
+    /// Render `map` as if every price had just been updated.
+    fn display(map: &HashMap<String, f64>, p: &Portfolio) -> (Vec<String>, Decimal) {
+        build_portfolio_display(
+            &PriceMap::from_values(map.clone(), "test", NOW),
+            p,
+            "USD",
+            NOW,
+        )
+    }
 
     fn portfolio(items: &[(&str, &str)]) -> Portfolio {
         Portfolio(
@@ -731,7 +1407,8 @@ mod tests {
                 .map(|(category, symbol)| PortfolioItem {
                     symbol: symbol.to_string(),
                     category: category.to_string(),
-                    quantity: 1.0,
+                    quantity: Decimal::ONE,
+                    ..Default::default()
                 })
                 .collect(),
         )
@@ -740,23 +1417,107 @@ mod tests {
     #[test]
     fn tw_equities_require_usd_twd_even_without_twd_cash() {
         let p = portfolio(&[("TW-Stock", "2330"), ("US-Stock", "AAPL")]);
-        assert_eq!(required_forex_pairs(&p, "USD"), vec!["USD/TWD".to_string()]);
+        assert_eq!(
+            required_forex_pairs(&p, &["USD".to_string()]),
+            vec!["USD/TWD".to_string()]
+        );
     }
 
     #[test]
-    fn forex_holdings_and_display_currency_are_collected_without_usd() {
+    fn otc_equities_are_priced_in_twd() {
+        let p = portfolio(&[("TW-OTC", "6488")]);
+        assert_eq!(
+            required_forex_pairs(&p, &["USD".to_string()]),
+            vec!["USD/TWD".to_string()]
+        );
+
+        let map = HashMap::from([("6488".to_string(), 450.0), ("USD/TWD".to_string(), 30.0)]);
+        let p = Portfolio(vec![item_with_qty("6488", "TW-OTC", 10.0)]);
+        let (lines, total) = display(&map, &p);
+        assert!(lines[0].starts_with("6488: NT$450.00 x 10.0000 = NT$4500.00"));
+        assert!(total == Decimal::from(150));
+    }
+
+    #[test]
+    fn forex_holdings_and_display_currencies_are_collected_without_usd() {
         let p = portfolio(&[("Forex", "USD"), ("Forex", "TWD"), ("US-Stock", "AAPL")]);
-        // USD is the base currency and must never appear; TWD held as cash does.
+        // USD is the base currency and must never appear; TWD held as cash and
+        // every display currency do.
+        let targets = ["EUR".to_string(), "jpy".to_string(), "USD".to_string()];
         assert_eq!(
-            required_forex_pairs(&p, "EUR"),
-            vec!["USD/EUR".to_string(), "USD/TWD".to_string(),]
+            required_forex_pairs(&p, &targets),
+            vec!["USD/EUR", "USD/JPY", "USD/TWD"]
+        );
+    }
+
+    #[test]
+    fn holdings_are_converted_into_the_display_currency() {
+        let p = Portfolio(vec![
+            item_with_qty("AAPL", "US-Stock", 2.0),
+            item_with_qty("2330", "TW-Stock", 10.0),
+            item_with_qty("EUR", "Forex", 100.0),
+        ]);
+        let map = HashMap::from([
+            ("AAPL".to_string(), 150.0),
+            ("2330".to_string(), 600.0),
+            ("USD/TWD".to_string(), 30.0),
+            ("USD/EUR".to_string(), 0.8),
+        ]);
+        let prices = PriceMap::from_values(map, "test", NOW);
+
+        let (lines, total) = build_portfolio_display(&prices, &p, "EUR", NOW);
+        assert_eq!(total, Decimal::from(625));
+        assert!(
+            lines.contains(&"  (Converted to EUR): $300.00 / 1.2500 = EUR 240.00 [0s]".to_string())
+        );
+        assert!(lines.contains(
+            &"  (Converted to EUR): NT$6000.00 / 37.5000 = EUR 160.00 [0s] [0s]".to_string()
+        ));
+        // Cash already in the display currency needs no conversion line.
+        assert_eq!(
+            lines.last().unwrap(),
+            "EUR: EUR 1.00 x 100.0000 = EUR 100.00"
+        );
+
+        let (lines, _) = build_portfolio_display(&prices, &p, "JPY", NOW);
+        assert!(lines.contains(&"  [Warning] USD/JPY rate not available".to_string()));
+    }
+
+    #[test]
+    fn foreign_cost_currency_requires_its_rate() {
+        let mut p = portfolio(&[("US-Stock", "AAPL")]);
+        p.0[0].avg_cost = Some(150.into());
+        p.0[0].cost_currency = Some("EUR".to_string());
+        assert_eq!(
+            required_forex_pairs(&p, &["USD".to_string()]),
+            vec!["USD/EUR".to_string()]
         );
     }
 
     #[test]
     fn usd_display_currency_alone_needs_no_pairs() {
         let p = portfolio(&[("US-Stock", "AAPL"), ("Forex", "USD")]);
-        assert!(required_forex_pairs(&p, "USD").is_empty());
+        assert!(required_forex_pairs(&p, &["USD".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn exchange_listings_are_priced_in_their_currency() {
+        let p = portfolio(&[("Stock.JP", "7203"), ("Stock.UK", "SHEL")]);
+        assert_eq!(
+            required_forex_pairs(&p, &["USD".to_string()]),
+            vec!["USD/GBP".to_string(), "USD/JPY".to_string()]
+        );
+        assert!(wanted_subscriptions(&p, &["USD".to_string()]).contains_key("Stock.JP:7203"));
+
+        let p = Portfolio(vec![item_with_qty("7203", "Stock.JP", 100.0)]);
+        let map = HashMap::from([
+            ("7203".to_string(), 3_000.0),
+            ("USD/JPY".to_string(), 150.0),
+        ]);
+        let (lines, total) = display(&map, &p);
+        assert!(total == Decimal::from(2_000));
+        assert!(lines[0].starts_with("7203: JPY 3000.00 x 100.0000 = JPY 300000.00"));
+        assert!(lines[1].contains("(Converted to USD): JPY 300000.00 / 150.0000 = $2000.00"));
     }
 
     #[tokio::test]
@@ -766,8 +1527,11 @@ mod tests {
         let map: HashMap<String, f64> = [("2330".to_string(), 100.0), ("USD/TWD".to_string(), 0.0)]
             .into_iter()
             .collect();
-        let (lines, total) = build_portfolio_display(&map, &p);
-        assert!(total.is_finite(), "total_value must be finite, got {total}");
+        let (lines, total) = display(&map, &p);
+        assert!(
+            total.is_zero(),
+            "an unvalued holding must add nothing, got {total}"
+        );
         assert!(
             lines.iter().any(|l| l.contains("[Warning]")),
             "warning line expected when rate is 0.0",
@@ -779,8 +1543,11 @@ mod tests {
         let p = portfolio(&[("TW-Stock", "2330")]);
         // Rate key entirely absent — must not produce Infinity.
         let map: HashMap<String, f64> = [("2330".to_string(), 100.0)].into_iter().collect();
-        let (lines, total) = build_portfolio_display(&map, &p);
-        assert!(total.is_finite(), "total_value must be finite, got {total}");
+        let (lines, total) = display(&map, &p);
+        assert!(
+            total.is_zero(),
+            "an unvalued holding must add nothing, got {total}"
+        );
         assert!(
             lines.iter().any(|l| l.contains("[Warning]")),
             "warning line expected when rate is absent",
@@ -792,8 +1559,11 @@ mod tests {
         let p = portfolio(&[("Forex", "TWD")]);
         // Forex rate is 0.0 — must not produce Infinity.
         let map: HashMap<String, f64> = [("USD/TWD".to_string(), 0.0)].into_iter().collect();
-        let (_, total) = build_portfolio_display(&map, &p);
-        assert!(total.is_finite(), "total_value must be finite, got {total}");
+        let (_, total) = display(&map, &p);
+        assert!(
+            total.is_zero(),
+            "an unvalued holding must add nothing, got {total}"
+        );
     }
 
     #[tokio::test]
@@ -801,8 +1571,11 @@ mod tests {
         let p = portfolio(&[("Forex", "TWD")]);
         // Forex rate key absent — must not produce Infinity.
         let map: HashMap<String, f64> = HashMap::new();
-        let (_, total) = build_portfolio_display(&map, &p);
-        assert!(total.is_finite(), "total_value must be finite, got {total}");
+        let (_, total) = display(&map, &p);
+        assert!(
+            total.is_zero(),
+            "an unvalued holding must add nothing, got {total}"
+        );
     }
 
     /// Regression test for issue #13: the display loop must not panic when
@@ -829,116 +1602,20 @@ mod tests {
     /// as intended.
     #[test]
     fn build_portfolio_display_is_not_async_fn() {
-        let map: HashMap<String, f64> = HashMap::new();
+        let map = PriceMap::default();
         let p = portfolio(&[]);
         // Compiles only when build_portfolio_display is a plain `fn`.
         // If it is still `async fn`, this is a type-mismatch compile error:
-        //   expected `(Vec<String>, f64)`, found opaque type (Future).
-        let _: (Vec<String>, f64) = build_portfolio_display(&map, &p);
-    }
-
-    // ── is_twse_market_open_at ─────────────────────────────────────────────────
-    //
-    // All dates use Asia/Taipei via Taipei.with_ymd_and_hms so tests are
-    // deterministic and require no network access.
-    //
-    // Calendar reference (verified):
-    //   2024-01-22 Mon · 2024-01-25 Thu · 2024-01-27 Sat · 2024-01-28 Sun
-
-    #[test]
-    fn twse_open_at_exactly_09_00() {
-        let dt = Taipei.with_ymd_and_hms(2024, 1, 22, 9, 0, 0).unwrap();
-        assert!(
-            is_twse_market_open_at(dt),
-            "09:00 Taipei on a Monday must be open"
-        );
-    }
-
-    #[test]
-    fn twse_closed_at_exactly_13_30() {
-        let dt = Taipei.with_ymd_and_hms(2024, 1, 22, 13, 30, 0).unwrap();
-        assert!(
-            !is_twse_market_open_at(dt),
-            "13:30 Taipei (close boundary) must be closed"
-        );
-    }
-
-    #[test]
-    fn twse_open_at_13_29() {
-        let dt = Taipei.with_ymd_and_hms(2024, 1, 22, 13, 29, 0).unwrap();
-        assert!(
-            is_twse_market_open_at(dt),
-            "13:29 Taipei (last open minute) must be open"
-        );
-    }
-
-    #[test]
-    fn twse_closed_on_saturday() {
-        let dt = Taipei.with_ymd_and_hms(2024, 1, 27, 11, 0, 0).unwrap();
-        assert!(
-            !is_twse_market_open_at(dt),
-            "Saturday must be closed regardless of time"
-        );
-    }
-
-    #[test]
-    fn twse_closed_on_sunday() {
-        let dt = Taipei.with_ymd_and_hms(2024, 1, 28, 11, 0, 0).unwrap();
-        assert!(
-            !is_twse_market_open_at(dt),
-            "Sunday must be closed regardless of time"
-        );
-    }
-
-    #[test]
-    fn twse_closed_thursday_at_08_59() {
-        let dt = Taipei.with_ymd_and_hms(2024, 1, 25, 8, 59, 0).unwrap();
-        assert!(
-            !is_twse_market_open_at(dt),
-            "08:59 Taipei on Thursday must be closed (before open)"
-        );
-    }
-
-    /// Verifies that Asia/Taipei is used for both weekday detection and hour
-    /// comparison, not the server's UTC time.
-    ///
-    /// UTC Sunday 2024-01-21 16:00 = Taipei Monday 2024-01-22 00:00.
-    /// UTC Sunday 2024-01-22 01:00 = Taipei Monday 2024-01-22 09:00 (market open).
-    ///
-    /// A buggy implementation that checked trading hours in UTC (01:00) would
-    /// return `false`; the correct implementation uses Taipei time (09:00) and
-    /// returns `true`.  The intermediate UTC-Sunday timestamp shows that the
-    /// weekday check also uses Taipei dates: the same wall-clock moment is a
-    /// Sunday in UTC but a Monday in Taipei.
-    #[test]
-    fn twse_uses_taipei_tz_not_utc() {
-        use chrono::Utc;
-
-        // UTC Sunday 16:00 = Taipei Monday 00:00 — weekday in Taipei, weekend in UTC.
-        // Market is closed (before 09:00) but weekday detection must use Taipei date.
-        let utc_sunday = Utc.with_ymd_and_hms(2024, 1, 21, 16, 0, 0).unwrap();
-        let taipei_monday_midnight = utc_sunday.with_timezone(&Taipei);
-        assert_eq!(taipei_monday_midnight.weekday(), chrono::Weekday::Mon);
-        assert!(
-            !is_twse_market_open_at(taipei_monday_midnight),
-            "Taipei Monday 00:00 must be closed (before market open)"
-        );
-
-        // UTC Monday 01:00 = Taipei Monday 09:00 — exactly at open in Taipei.
-        // A UTC-hours check (01:00) would return false; Taipei (09:00) returns true.
-        let utc_monday_early = Utc.with_ymd_and_hms(2024, 1, 22, 1, 0, 0).unwrap();
-        let taipei_monday_09 = utc_monday_early.with_timezone(&Taipei);
-        assert!(
-            is_twse_market_open_at(taipei_monday_09),
-            "Taipei Monday 09:00 must be open even though UTC is 01:00 (outside UTC market hours)"
-        );
+        //   expected `(Vec<String>, Decimal)`, found opaque type (Future).
+        let _: (Vec<String>, Decimal) = build_portfolio_display(&map, &p, "USD", 0);
     }
 
     fn item_with_qty(symbol: &str, category: &str, quantity: f64) -> PortfolioItem {
         PortfolioItem {
             symbol: symbol.to_string(),
             category: category.to_string(),
-            quantity,
+            quantity: Decimal::from_f64(quantity).unwrap(),
+            ..Default::default()
         }
     }
 
@@ -961,19 +1638,57 @@ mod tests {
             item_with_qty("2330", "TW-Stock", 100.0),
             item_with_qty("TWD", "Forex", 3000.0),
         ]);
-        let (lines, total) = build_portfolio_display(&HashMap::new(), &p);
-        assert_eq!(total, 0.0);
+        let (lines, total) = display(&HashMap::new(), &p);
+        assert!(total.is_zero());
         no_nan_or_inf(&lines);
     }
 
+    #[test]
+    fn build_display_skips_values_too_large_for_a_decimal() {
+        let p = Portfolio(vec![
+            item_with_qty("AAPL", "US-Stock", 1e10),
+            item_with_qty("TWD", "Forex", 1e25),
+        ]);
+        let mut map = HashMap::new();
+        map.insert("AAPL".to_string(), 1e20);
+        map.insert("USD/TWD".to_string(), 1e-6);
+        let (lines, total) = display(&map, &p);
+        assert!(total.is_zero());
+        assert!(!lines.iter().any(|l| l.starts_with("AAPL")), "{lines:?}");
+        assert!(!lines.iter().any(|l| l.contains("Converted")), "{lines:?}");
+
+        // Each holding fits in a decimal but their sum does not: the category
+        // counts as missing instead of panicking.
+        let p = Portfolio(vec![
+            item_with_qty("AAPL", "US-Stock", 1e10),
+            item_with_qty("MSFT", "US-Stock", 1e10),
+        ]);
+        let mut map = HashMap::new();
+        map.insert("AAPL".to_string(), 5e18);
+        map.insert("MSFT".to_string(), 5e18);
+        let (lines, total) = display(&map, &p);
+        assert!(total.is_zero());
+        assert!(lines.iter().any(|l| l.starts_with("AAPL")), "{lines:?}");
+
+        // Two categories that overflow only together: one is left out.
+        let p = Portfolio(vec![
+            item_with_qty("AAPL", "US-Stock", 1e10),
+            item_with_qty("BTC", "Crypto", 1e10),
+        ]);
+        map.insert("BTC".to_string(), 5e18);
+        let (categories, total) = history::compute_category_values(&p, &map);
+        assert_eq!(total, Decimal::from_scientific("5e28").unwrap());
+        assert_eq!(categories.len(), 1);
+    }
+
     #[test]
     fn build_display_tw_stock_missing_twd_rate() {
         let p = Portfolio(vec![item_with_qty("2330", "TW-Stock", 10.0)]);
         let mut map = HashMap::new();
         map.insert("2330".to_string(), 600.0);
         // USD/TWD absent — total must be 0 and a warning line must appear
-        let (lines, total) = build_portfolio_display(&map, &p);
-        assert_eq!(total, 0.0);
+        let (lines, total) = display(&map, &p);
+        assert!(total.is_zero());
         assert!(
             lines.iter().any(|l| l.contains("[Warning]")),
             "expected a warning line, got: {lines:?}"
@@ -986,12 +1701,9 @@ mod tests {
         let p = Portfolio(vec![item_with_qty("TWD", "Forex", 30000.0)]);
         let mut map = HashMap::new();
         map.insert("USD/TWD".to_string(), 30.0);
-        let (lines, total) = build_portfolio_display(&map, &p);
+        let (lines, total) = display(&map, &p);
         // 30 000 TWD / 30 = 1 000 USD
-        assert!(
-            (total - 1000.0).abs() < 1e-6,
-            "expected 1000.0, got {total}"
-        );
+        assert!(total == Decimal::from(1000), "expected 1000.0, got {total}");
         assert!(lines.iter().any(|l| l.contains("TWD")));
         no_nan_or_inf(&lines);
     }
@@ -1009,11 +1721,8 @@ mod tests {
         map.insert("2330".to_string(), 600.0); // 100 × 600 TWD / 30 = 2000 USD
         map.insert("USD/TWD".to_string(), 30.0);
         // Cash: 500 USD + 3000 TWD / 30 = 600 USD  →  total 4600 USD
-        let (lines, total) = build_portfolio_display(&map, &p);
-        assert!(
-            (total - 4600.0).abs() < 1e-6,
-            "expected 4600.0, got {total}"
-        );
+        let (lines, total) = display(&map, &p);
+        assert!(total == Decimal::from(4600), "expected 4600.0, got {total}");
         assert!(!lines.is_empty());
         no_nan_or_inf(&lines);
     }
@@ -1033,27 +1742,257 @@ mod tests {
         map.insert("USD/TWD".to_string(), 32.0);
         map.insert("USD/EUR".to_string(), 1.1);
 
-        let (_, display_total) = build_portfolio_display(&map, &p);
+        let (_, display_total) = display(&map, &p);
         let (_, canon_total) = history::compute_category_values(&p, &map);
         assert!(
-            (display_total - canon_total).abs() < 1e-9,
+            display_total == canon_total,
             "build_portfolio_display total ({display_total}) != compute_category_values total ({canon_total})"
         );
     }
 
+    #[test]
+    fn build_display_shows_unrealized_pnl_for_holdings_with_cost() {
+        let p = Portfolio(vec![
+            PortfolioItem {
+                avg_cost: Some(100.into()),
+                ..item_with_qty("AAPL", "US-Stock", 10.0)
+            },
+            item_with_qty("MSFT", "US-Stock", 1.0),
+        ]);
+        let mut map = HashMap::new();
+        map.insert("AAPL".to_string(), 125.0);
+        map.insert("MSFT".to_string(), 400.0);
+        let (lines, _) = display(&map, &p);
+        let pnl_lines: Vec<_> = lines.iter().filter(|l| l.contains("P&L")).collect();
+        assert_eq!(pnl_lines.len(), 1, "only AAPL has a cost basis: {lines:?}");
+        assert!(pnl_lines[0].contains("+$250.00"), "got {}", pnl_lines[0]);
+        assert!(pnl_lines[0].contains("+25.00%"), "got {}", pnl_lines[0]);
+    }
+
+    #[tokio::test]
+    async fn reconcile_stops_and_evicts_unneeded_subscriptions() {
+        let prices: SharedPriceMap = Arc::new(Mutex::new(PriceMap::default()));
+        let streams = PythStreams::spawn(prices.clone());
+        let subscriptions: Subscriptions = Arc::new(Mutex::new(HashMap::new()));
+
+        let before = portfolio(&[
+            ("US-Stock", "AAPL"),
+            ("US-Stock", "MSFT"),
+            ("Commodity", "XAU"),
+        ]);
+        reconcile_subscriptions(
+            &before,
+            &["TWD".to_string()],
+            &prices,
+            &streams,
+            &subscriptions,
+        )
+        .await;
+        assert_eq!(streams.keys(), vec!["AAPL", "MSFT", "USD/TWD", "XAU"]);
+        {
+            let mut map = prices.lock().await;
+            for key in ["AAPL", "MSFT", "USD/TWD"] {
+                map.insert(key.to_string(), 1.0, "pyth", NOW);
+            }
+        }
+
+        // Drop MSFT and the gold, and switch the display currency to JPY.
+        let after = portfolio(&[("US-Stock", "AAPL")]);
+        reconcile_subscriptions(
+            &after,
+            &["JPY".to_string()],
+            &prices,
+            &streams,
+            &subscriptions,
+        )
+        .await;
+        assert_eq!(streams.keys(), vec!["AAPL", "USD/JPY"]);
+        let mut active: Vec<String> = subscriptions.lock().await.keys().cloned().collect();
+        active.sort();
+        assert_eq!(active, vec!["US-Stock:AAPL", "USD/JPY"]);
+        let map = prices.lock().await;
+        assert!(map.get("AAPL").is_some());
+        assert!(map.get("MSFT").is_none());
+        assert!(map.get("USD/TWD").is_none());
+    }
+
+    #[tokio::test]
+    async fn reconcile_streams_inverse_rates_through_their_legs() {
+        let prices: SharedPriceMap = Arc::new(Mutex::new(PriceMap::default()));
+        let streams = PythStreams::spawn(prices.clone());
+        let subscriptions: Subscriptions = Arc::new(Mutex::new(HashMap::new()));
+
+        // Pyth only lists EUR as EUR/USD, so USD/EUR is derived from it.
+        let p = portfolio(&[("Forex", "EUR"), ("Forex", "TWD")]);
+        reconcile_subscriptions(&p, &[], &prices, &streams, &subscriptions).await;
+        assert_eq!(streams.keys(), vec!["EUR/USD", "USD/TWD"]);
+        assert_eq!(
+            fx_routes(&subscriptions).await,
+            vec![
+                ("USD/EUR".to_string(), FxRoute::Inverse),
+                ("USD/TWD".to_string(), FxRoute::Direct),
+            ]
+        );
+
+        let p = portfolio(&[("Forex", "TWD")]);
+        reconcile_subscriptions(&p, &[], &prices, &streams, &subscriptions).await;
+        assert_eq!(streams.keys(), vec!["USD/TWD"]);
+        let map = prices.lock().await;
+        assert!(map.get("EUR/USD").is_none());
+        assert!(map.get("USD/EUR").is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn derived_rates_follow_their_legs() {
+        let prices: SharedPriceMap = Arc::new(Mutex::new(PriceMap::default()));
+        let task = tokio::spawn(derive_fx_rate(
+            prices.clone(),
+            "USD/EUR".to_string(),
+            FxRoute::Inverse.legs("EUR"),
+            FxRoute::Inverse.source(),
+        ));
+        prices
+            .lock()
+            .await
+            .insert("EUR/USD".to_string(), 1.25, "pyth", NOW);
+        tokio::time::advance(Duration::from_secs(FX_DERIVE_SECS)).await;
+        tokio::task::yield_now().await;
+        {
+            let map = prices.lock().await;
+            let rate = map.get("USD/EUR").unwrap();
+            assert_eq!((rate.price, rate.updated_at), (0.8, NOW));
+            assert_eq!(rate.source, "pyth-inverse");
+        }
+
+        prices
+            .lock()
+            .await
+            .insert("EUR/USD".to_string(), 2.0, "pyth", NOW + 1);
+        tokio::time::advance(Duration::from_secs(FX_DERIVE_SECS)).await;
+        tokio::task::yield_now().await;
+        task.abort();
+
+        let rate = prices.lock().await.get("USD/EUR").cloned().unwrap();
+        assert_eq!((rate.price, rate.updated_at), (0.5, NOW + 1));
+    }
+
+    #[test]
+    fn crypto_backup_only_for_missing_or_silent_prices() {
+        let mut prices = PriceMap::default();
+        assert!(needs_backup(&prices, "BTC", NOW));
+        prices.insert("BTC".to_string(), 60_000.0, "pyth", NOW - 5);
+        assert!(!needs_backup(&prices, "BTC", NOW));
+        prices.insert("BTC".to_string(), 60_000.0, "pyth", NOW - 30);
+        assert!(needs_backup(&prices, "BTC", NOW));
+    }
+
+    #[test]
+    fn build_display_marks_quote_age_and_stale_prices() {
+        let p = Portfolio(vec![
+            item_with_qty("BTC", "Crypto", 1.0),
+            item_with_qty("2330", "TW-Stock", 10.0),
+        ]);
+        let mut prices = PriceMap::default();
+        prices.insert("BTC".to_string(), 60_000.0, "pyth", NOW - 3_600);
+        prices.insert("2330".to_string(), 600.0, "rest", NOW - 30);
+        prices.insert("USD/TWD".to_string(), 30.0, "pyth", NOW - 5);
+        let (lines, _) = build_portfolio_display(&prices, &p, "USD", NOW);
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.starts_with("BTC:") && l.ends_with("[STALE 1h]")),
+            "{lines:?}"
+        );
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.starts_with("2330:") && l.ends_with("[30s]")),
+            "{lines:?}"
+        );
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.contains("(Converted to USD)") && l.ends_with("[5s]"))
+        );
+    }
+
+    #[test]
+    fn build_display_shows_confidence_and_publish_lag() {
+        let p = Portfolio(vec![
+            item_with_qty("AAPL", "US-Stock", 2.0),
+            item_with_qty("ETH", "Crypto", 1.0),
+        ]);
+        let mut prices = PriceMap::default();
+        for (key, price, conf, published) in [
+            ("AAPL", 150.0, 0.05, NOW - 7_200),
+            ("ETH", 3_000.0, 1.5, NOW - 1),
+        ] {
+            prices.0.insert(
+                key.to_string(),
+                PriceEntry {
+                    price,
+                    updated_at: NOW - 2,
+                    source: "pyth".to_string(),
+                    conf: Some(conf),
+                    publish_time: Some(published),
+                },
+            );
+        }
+        let (lines, _) = build_portfolio_display(&prices, &p, "USD", NOW);
+        // After the closing bell: the feed still ticks, but the price is two
+        // hours old, and AAPL is labelled with the after-hours session.
+        assert!(
+            lines.contains(
+                &"AAPL: $150.00 ±0.05 x 2.0000 = $300.00 [2s] [LAG 2h] [POST]".to_string()
+            ),
+            "{lines:?}"
+        );
+        assert!(
+            lines.contains(&"ETH: $3000.00 ±1.50 x 1.0000 = $3000.00 [2s]".to_string()),
+            "{lines:?}"
+        );
+    }
+
+    #[test]
+    fn us_closes_pin_extended_hours_prices() {
+        let p = Portfolio(vec![
+            item_with_qty("QQQ", "US-ETF", 2.0),
+            item_with_qty("ETH", "Crypto", 1.0),
+        ]);
+        let mut closes = HashMap::new();
+        let mut values = HashMap::from([("QQQ".to_string(), 500.0), ("ETH".to_string(), 3_000.0)]);
+        apply_us_closes(&p, &mut values, &mut closes, true);
+        assert_eq!(closes, HashMap::from([("QQQ".to_string(), 500.0)]));
+
+        // After hours the live QQQ price moves, but snapshots keep the close.
+        let mut values = HashMap::from([("QQQ".to_string(), 505.0), ("ETH".to_string(), 3_100.0)]);
+        apply_us_closes(&p, &mut values, &mut closes, false);
+        assert_eq!(values["QQQ"], 500.0);
+        assert_eq!(values["ETH"], 3_100.0);
+    }
+
     #[tokio::test]
     async fn display_loop_survives_event_poll_io_error() {
         use ratatui::backend::TestBackend;
 
-        let prices: SharedPriceMap = Arc::new(Mutex::new(HashMap::new()));
+        let prices: SharedPriceMap = Arc::new(Mutex::new(PriceMap::default()));
         let history: SharedHistory = Arc::new(Mutex::new(Vec::new()));
         let portfolio: SharedPortfolio = Arc::new(RwLock::new(Portfolio(vec![])));
-        let target_forex: SharedTargetForex = Arc::new(RwLock::new("USD".to_string()));
+        let target_forex: SharedTargetForex = Arc::new(RwLock::new(vec!["USD".to_string()]));
+        let subscriptions: Subscriptions = Arc::new(Mutex::new(HashMap::new()));
         let mut terminal =
             Terminal::new(TestBackend::new(80, 24)).expect("construct headless test terminal");
 
         let handle = tokio::spawn(async move {
-            run_display_loop(&mut terminal, &prices, &history, &portfolio, &target_forex).await;
+            run_display_loop(
+                &mut terminal,
+                &prices,
+                &history,
+                &portfolio,
+                &target_forex,
+                &subscriptions,
+            )
+            .await;
         });
 
         // Give the loop time to hit the failing event::poll path several times.
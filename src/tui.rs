@@ -11,10 +11,17 @@ use ratatui::{
     widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph},
 };
 
-use crate::history::compute_category_values;
-use crate::types::{Portfolio, PortfolioSnapshot};
+use crate::calendar::{self, MarketStatus, UsSession};
+use crate::fx::FxRoute;
+use crate::history::{
+    UnrealizedPnl, compute_category_values, compute_unrealized_pnl, stale_categories,
+};
+use crate::types::{Portfolio, PortfolioSnapshot, PriceMap};
+use crate::valuation;
 use chrono::{TimeZone, Utc};
-use std::collections::HashMap;
+use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use std::collections::{HashMap, HashSet};
 
 /// Which screen the TUI is currently showing.
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -47,18 +54,134 @@ const PALETTE: [Color; 6] = [
     Color::Green,
 ];
 
+/// Format an unrealized P&L as a signed USD amount and percentage, e.g.
+/// `+$250.00 (+25.00%)`.
+pub fn format_pnl(pnl: &UnrealizedPnl) -> String {
+    let amount = pnl.amount();
+    let sign = if amount.is_sign_negative() && !amount.is_zero() {
+        "-"
+    } else {
+        "+"
+    };
+    format!("{}${:.2} ({:+.2}%)", sign, amount.abs(), pnl.percent())
+}
+
+/// Format a quote age in seconds with its largest unit, e.g. `45s`, `12m`,
+/// `3h`, `2d`.
+pub fn format_age(secs: i64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3_600 => format!("{}m", s / 60),
+        s if s < 86_400 => format!("{}h", s / 3_600),
+        s => format!("{}d", s / 86_400),
+    }
+}
+
+/// Green for a gain, red for a loss.
+fn pnl_color(pnl: &UnrealizedPnl) -> Color {
+    if pnl.amount() < Decimal::ZERO {
+        Color::Red
+    } else {
+        Color::Green
+    }
+}
+
+/// Colour for a market status: green while trading, yellow on a holiday.
+fn status_color(status: MarketStatus) -> Color {
+    match status {
+        MarketStatus::Open => Color::Green,
+        MarketStatus::Closed => Color::DarkGray,
+        MarketStatus::Holiday => Color::Yellow,
+    }
+}
+
+/// Colour for a US session: green in the regular session, cyan in extended
+/// hours.
+pub fn session_color(session: UsSession) -> Color {
+    match session {
+        UsSession::Regular => Color::Green,
+        UsSession::Pre | UsSession::Post => Color::Cyan,
+        UsSession::Closed => Color::DarkGray,
+    }
+}
+
+/// `FX: USD/EUR inverse USD/TWD direct`: how each forex rate is obtained (see
+/// [`crate::fx`]); `None` if no rate is needed.
+fn fx_route_line(routes: &[(String, FxRoute)]) -> Option<Line<'static>> {
+    let mut spans = vec![Span::raw("FX:")];
+    for (pair, route) in routes {
+        let color = match route {
+            FxRoute::Direct => Color::Green,
+            FxRoute::Inverse | FxRoute::Cross { .. } => Color::Cyan,
+            FxRoute::Rest => Color::Yellow,
+        };
+        spans.push(Span::raw(format!(" {} ", pair)));
+        spans.push(Span::styled(route.to_string(), Style::default().fg(color)));
+    }
+    (!routes.is_empty()).then(|| Line::from(spans))
+}
+
+/// `Market: TWSE OPEN  NYSE POST`, naming only the exchanges the portfolio
+/// holds equities on; `None` if it holds none.
+fn market_status_line(portfolio: &Portfolio) -> Option<Line<'static>> {
+    let holds = |categories: &[&str]| {
+        portfolio
+            .iter()
+            .any(|item| categories.contains(&item.category.as_str()))
+    };
+    let now = Utc::now();
+    let mut spans = vec![Span::raw("Market:")];
+    if holds(&["TW-Stock", "TW-ETF", "TW-OTC"]) {
+        let status = calendar::twse().status_at(now);
+        spans.push(Span::raw(" TWSE "));
+        spans.push(Span::styled(
+            status.to_string(),
+            Style::default().fg(status_color(status)),
+        ));
+    }
+    if holds(&["US-Stock", "US-ETF"]) {
+        let session = calendar::nyse().session_at(now);
+        spans.push(Span::raw(" NYSE "));
+        spans.push(Span::styled(
+            session.to_string(),
+            Style::default().fg(session_color(session)),
+        ));
+    }
+    (spans.len() > 1).then(|| Line::from(spans))
+}
+
+/// The currencies the `c` key cycles the per-holding lines through: USD, then
+/// each of the `target_forex` display currencies in file order.
+pub fn display_currencies(target_forex: &[String]) -> Vec<String> {
+    let mut currencies = vec![valuation::BASE_CURRENCY.to_string()];
+    for currency in target_forex {
+        let currency = currency.to_uppercase();
+        if !currencies.contains(&currency) {
+            currencies.push(currency);
+        }
+    }
+    currencies
+}
+
 /// Render one frame to `terminal` for the current `view_mode`.
 ///
 /// In [`ViewMode::History`] it draws the history charts from `history`.
-/// Otherwise it draws the portfolio lines plus the total value in USD (and, when
-/// a `USD/<target_forex>` rate is present in `map`, the total converted to the
-/// target currency), with the asset-allocation panel below.
+/// Otherwise it draws the portfolio lines (converted into `display_currency`)
+/// plus the total value in USD and in each `target_forex` currency whose
+/// `USD/{ccy}` rate is present in `prices`, and the unrealized P&L of holdings
+/// with a cost basis, with the asset-allocation panel below. Categories valued
+/// from a stale price are marked in the allocation panel. A portfolio with
+/// Taiwan or US equities also gets a line with the TWSE status and the NYSE
+/// session, and one naming the route of each forex rate in `fx_routes`.
+#[allow(clippy::too_many_arguments)]
 pub fn render_portfolio<B: Backend>(
     terminal: &mut Terminal<B>,
     lines: &[String],
-    total_value: f64,
-    map: &HashMap<String, f64>,
-    target_forex: &str,
+    total_value: Decimal,
+    prices: &PriceMap,
+    target_forex: &[String],
+    display_currency: &str,
+    fx_routes: &[(String, FxRoute)],
     portfolio: &Portfolio,
     history: &[PortfolioSnapshot],
     view_mode: ViewMode,
@@ -70,6 +193,10 @@ pub fn render_portfolio<B: Backend>(
         return;
     }
 
+    let values = prices.values();
+    let map = &values;
+    let stale = stale_categories(portfolio, prices, Utc::now().timestamp());
+
     terminal
         .draw(|f| {
             let area = f.area();
@@ -83,33 +210,48 @@ pub fn render_portfolio<B: Backend>(
                 ])
                 .split(area);
 
-            // Upper part: Portfolio display
-            let mut display_lines: Vec<Line> = lines
-                .iter()
-                .map(|line| Line::from(Span::raw(line.clone())))
-                .collect();
+            // Upper part: Portfolio display, below the market status
+            let mut display_lines: Vec<Line> = market_status_line(portfolio).into_iter().collect();
+            display_lines.extend(fx_route_line(fx_routes));
+            display_lines.extend(lines.iter().map(|line| Line::from(Span::raw(line.clone()))));
 
             display_lines.push(Line::from(Span::styled(
                 format!("Total assets (USD): ${:.2}", total_value),
                 Style::default().fg(Color::Green),
             )));
 
-            if let Some(forex_price) = map.get(&format!("USD/{}", target_forex)) {
-                let converted_value = total_value * forex_price;
+            for currency in &display_currencies(target_forex)[1..] {
+                if let Some(converted_value) = valuation::from_usd(total_value, currency, map) {
+                    display_lines.push(Line::from(Span::styled(
+                        format!(
+                            "Total assets ({}): {}{:.2}",
+                            currency,
+                            valuation::currency_unit(currency),
+                            converted_value
+                        ),
+                        Style::default().fg(Color::Green),
+                    )));
+                }
+            }
+
+            if let (_, Some(pnl)) = compute_unrealized_pnl(portfolio, map) {
                 display_lines.push(Line::from(Span::styled(
-                    format!("Total assets ({}): ${:.2}", target_forex, converted_value),
-                    Style::default().fg(Color::Green),
+                    format!("Unrealized P&L (USD): {}", format_pnl(&pnl)),
+                    Style::default().fg(pnl_color(&pnl)),
                 )));
             }
 
             let portfolio_block = Block::default()
-                .title("Portfolio (Tab: history  e: export csv  q: quit)")
+                .title(format!(
+                    "Portfolio in {} (Tab: history  c: currency  e: export csv  r: realized gains  q: quit)",
+                    display_currency.to_uppercase()
+                ))
                 .borders(Borders::ALL);
             let portfolio_paragraph = Paragraph::new(display_lines).block(portfolio_block);
             f.render_widget(portfolio_paragraph, chunks[0]);
 
             // Lower part: Asset allocation
-            render_asset_allocation(f, chunks[1], portfolio, map, total_value);
+            render_asset_allocation(f, chunks[1], portfolio, map, total_value, &stale);
         })
         .unwrap();
 }
@@ -119,37 +261,48 @@ fn render_asset_allocation(
     area: ratatui::layout::Rect,
     portfolio: &Portfolio,
     map: &HashMap<String, f64>,
-    total_value: f64,
+    total_value: Decimal,
+    stale: &HashSet<String>,
 ) {
     // Calculate asset category values using the shared helper.
     let colors = PALETTE;
     let (categories, _total) = compute_category_values(portfolio, map);
+    let (category_pnl, _) = compute_unrealized_pnl(portfolio, map);
 
-    // Sort categories by value (largest to smallest), dropping any non-finite
-    // values (NaN or Infinity) so the sort never receives a None from partial_cmp.
-    let mut sorted_categories: Vec<(&str, f64)> =
+    // Sort categories by value (largest to smallest). Quotes that are not
+    // finite numbers never reach the decimal values (they count as missing).
+    let mut sorted_categories: Vec<(&str, Decimal)> =
         categories.iter().map(|(k, &v)| (k.as_str(), v)).collect();
-    sorted_categories.retain(|(_, v)| v.is_finite());
-    sorted_categories.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    sorted_categories.sort_by_key(|c| std::cmp::Reverse(c.1));
 
     // Create asset allocation display with sorted order
     let mut allocation_lines = Vec::new();
     let mut bars_data = Vec::new();
 
     for (i, (category, value)) in sorted_categories.iter().enumerate() {
-        let percentage = if total_value > 0.0 {
-            value / total_value * 100.0
+        let percentage = if total_value > Decimal::ZERO {
+            value / total_value * Decimal::ONE_HUNDRED
         } else {
-            0.0
+            Decimal::ZERO
         };
         let color = colors[i % colors.len()];
 
-        allocation_lines.push(Line::from(vec![
+        let mut spans = vec![
             Span::styled("█", Style::default().fg(color)),
             Span::raw(format!(" {}: ${:.0} ({:.1}%)", category, value, percentage)),
-        ]));
+        ];
+        if let Some(pnl) = category_pnl.get(*category) {
+            spans.push(Span::styled(
+                format!("  P&L {}", format_pnl(pnl)),
+                Style::default().fg(pnl_color(pnl)),
+            ));
+        }
+        if stale.contains(*category) {
+            spans.push(Span::styled("  STALE", Style::default().fg(Color::Yellow)));
+        }
+        allocation_lines.push(Line::from(spans));
 
-        bars_data.push((percentage, color));
+        bars_data.push((percentage.to_f64().unwrap_or(0.0), color));
     }
 
     // Split allocation area into text and single bar
@@ -242,7 +395,12 @@ fn render_total_value_chart(
 ) {
     let data: Vec<(f64, f64)> = history
         .iter()
-        .map(|s| (s.timestamp as f64, s.total_value_usd))
+        .map(|s| {
+            (
+                s.timestamp as f64,
+                s.total_value_usd.to_f64().unwrap_or(0.0),
+            )
+        })
         .collect();
 
     let x_min = data.first().map(|p| p.0).unwrap_or(0.0);
@@ -301,10 +459,11 @@ fn render_ratio_chart(
         .map(|cat| {
             let pts: Vec<(f64, f64)> = history
                 .iter()
-                .filter(|s| s.total_value_usd > 0.0)
+                .filter(|s| s.total_value_usd > Decimal::ZERO)
                 .map(|s| {
-                    let v = s.category_values.get(cat).copied().unwrap_or(0.0);
-                    (s.timestamp as f64, v / s.total_value_usd * 100.0)
+                    let v = s.category_values.get(cat).copied().unwrap_or_default();
+                    let ratio = v / s.total_value_usd * Decimal::ONE_HUNDRED;
+                    (s.timestamp as f64, ratio.to_f64().unwrap_or(0.0))
                 })
                 .collect();
             (cat.clone(), pts)
@@ -349,11 +508,12 @@ mod tests {
     use super::*;
     use ratatui::{Terminal, backend::TestBackend};
 
-    fn item(symbol: &str, category: &str, quantity: f64) -> crate::types::PortfolioItem {
+    fn item(symbol: &str, category: &str, quantity: i64) -> crate::types::PortfolioItem {
         crate::types::PortfolioItem {
             symbol: symbol.to_string(),
             category: category.to_string(),
-            quantity,
+            quantity: quantity.into(),
+            ..Default::default()
         }
     }
 
@@ -361,7 +521,7 @@ mod tests {
     /// contains NaN values (price * quantity produces NaN for that asset).
     #[test]
     fn render_asset_allocation_nan_price_does_not_panic() {
-        let portfolio = Portfolio(vec![item("BTC", "Crypto", 1.0), item("ETH", "Crypto", 2.0)]);
+        let portfolio = Portfolio(vec![item("BTC", "Crypto", 1), item("ETH", "Crypto", 2)]);
 
         let mut map = HashMap::new();
         map.insert("BTC".to_string(), f64::NAN);
@@ -371,16 +531,66 @@ mod tests {
         let mut terminal = Terminal::new(backend).unwrap();
         terminal
             .draw(|f| {
-                render_asset_allocation(f, f.area(), &portfolio, &map, 2000.0);
+                render_asset_allocation(
+                    f,
+                    f.area(),
+                    &portfolio,
+                    &map,
+                    Decimal::from(2000),
+                    &HashSet::new(),
+                );
             })
             .unwrap();
     }
 
+    #[test]
+    fn fx_route_line_names_each_pair_route() {
+        assert!(fx_route_line(&[]).is_none());
+        let routes = [
+            ("USD/EUR".to_string(), FxRoute::Inverse),
+            ("USD/TWD".to_string(), FxRoute::Direct),
+            ("USD/XYZ".to_string(), FxRoute::Rest),
+        ];
+        assert_eq!(
+            fx_route_line(&routes).unwrap().to_string(),
+            "FX: USD/EUR inverse USD/TWD direct USD/XYZ ExchangeRate-API"
+        );
+    }
+
+    #[test]
+    fn display_currencies_start_with_usd_without_duplicates() {
+        let targets = ["twd".to_string(), "USD".to_string(), "JPY".to_string()];
+        assert_eq!(display_currencies(&targets), vec!["USD", "TWD", "JPY"]);
+        assert_eq!(display_currencies(&[]), vec!["USD"]);
+    }
+
+    #[test]
+    fn format_pnl_signs_amount_and_percent() {
+        let gain = UnrealizedPnl {
+            cost_usd: 1000.into(),
+            value_usd: 1250.into(),
+        };
+        assert_eq!(format_pnl(&gain), "+$250.00 (+25.00%)");
+        let loss = UnrealizedPnl {
+            cost_usd: 1000.into(),
+            value_usd: 900.into(),
+        };
+        assert_eq!(format_pnl(&loss), "-$100.00 (-10.00%)");
+    }
+
+    #[test]
+    fn format_age_uses_largest_unit() {
+        assert_eq!(format_age(45), "45s");
+        assert_eq!(format_age(125), "2m");
+        assert_eq!(format_age(7_300), "2h");
+        assert_eq!(format_age(200_000), "2d");
+    }
+
     /// Verifies that non-finite category values (NaN, Infinity) are stripped
     /// before sorting so the sort never receives a None from partial_cmp.
     #[test]
     fn render_asset_allocation_infinity_does_not_panic() {
-        let portfolio = Portfolio(vec![item("AAPL", "US-Stock", 10.0)]);
+        let portfolio = Portfolio(vec![item("AAPL", "US-Stock", 10)]);
 
         let mut map = HashMap::new();
         map.insert("AAPL".to_string(), f64::INFINITY);
@@ -389,7 +599,14 @@ mod tests {
         let mut terminal = Terminal::new(backend).unwrap();
         terminal
             .draw(|f| {
-                render_asset_allocation(f, f.area(), &portfolio, &map, 0.0);
+                render_asset_allocation(
+                    f,
+                    f.area(),
+                    &portfolio,
+                    &map,
+                    Decimal::ZERO,
+                    &HashSet::new(),
+                );
             })
             .unwrap();
     }
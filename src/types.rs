@@ -1,31 +1,89 @@
-//! Core data types: the portfolio holdings model, historical snapshots and the
-//! API response/key structures used across the crate.
+//! Core data types: the portfolio holdings model, the live price map,
+//! historical snapshots and the API response/key structures used across the
+//! crate.
 
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// A flat list of portfolio holdings.
 ///
-/// Deserialized from a nested TOML table of `category -> { symbol -> quantity }`
-/// and flattened into one [`PortfolioItem`] per `(category, symbol)` pair.
+/// Deserialized from a nested TOML table of `category -> { symbol -> holding }`
+/// and flattened into one [`PortfolioItem`] per `(category, symbol)` pair. A
+/// holding is either a bare quantity (`amd = 10`) or an inline table that also
+/// carries the cost basis (`amd = { quantity = 10, avg_cost = 95.5 }`).
+/// Exchange-listed equities nest one level deeper (`[Stock.JP]`) and get the
+/// dotted category `Stock.JP`; see [`crate::exchange`].
 #[derive(Debug, Clone)]
 pub struct Portfolio(pub Vec<PortfolioItem>);
 
+/// One holding as written in `portfolio.toml`: either the flat quantity form or
+/// the detailed form with an optional average cost.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawHolding {
+    Quantity(Decimal),
+    Detailed(DetailedHolding),
+}
+
+/// A value under a category table: a holding, or (under `[Stock]` only) an
+/// exchange table of holdings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawEntry {
+    Holding(RawHolding),
+    Exchange(HashMap<String, RawHolding>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DetailedHolding {
+    quantity: Decimal,
+    #[serde(default)]
+    avg_cost: Option<Decimal>,
+    #[serde(default)]
+    cost_currency: Option<String>,
+}
+
 impl<'de> Deserialize<'de> for Portfolio {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let raw: HashMap<String, HashMap<String, f64>> = HashMap::deserialize(deserializer)?;
+        use serde::de::Error;
+
+        let raw: HashMap<String, HashMap<String, RawEntry>> = HashMap::deserialize(deserializer)?;
+        let exchange_root = crate::exchange::CATEGORY_PREFIX.trim_end_matches('.');
         let mut items = Vec::new();
 
-        for (category, symbols) in raw {
-            for (symbol, quantity) in symbols {
-                items.push(PortfolioItem {
-                    symbol,
-                    category: category.clone(),
-                    quantity,
-                });
+        for (category, entries) in raw {
+            for (key, entry) in entries {
+                match entry {
+                    RawEntry::Holding(_) if category == exchange_root => {
+                        return Err(D::Error::custom(format!(
+                            "[{exchange_root}] {key}: put the holding under its exchange, e.g. [{exchange_root}.JP]"
+                        )));
+                    }
+                    RawEntry::Holding(holding) => {
+                        items.push(PortfolioItem::from_raw(key, category.clone(), holding));
+                    }
+                    RawEntry::Exchange(holdings) if category == exchange_root => {
+                        let category = format!("{category}.{key}");
+                        if crate::exchange::for_category(&category).is_none() {
+                            return Err(D::Error::custom(format!(
+                                "[{category}]: unsupported exchange {key}"
+                            )));
+                        }
+                        for (symbol, holding) in holdings {
+                            items.push(PortfolioItem::from_raw(symbol, category.clone(), holding));
+                        }
+                    }
+                    RawEntry::Exchange(_) => {
+                        return Err(D::Error::custom(format!(
+                            "[{category}] {key}: expected a quantity or {{ quantity, avg_cost, cost_currency }}"
+                        )));
+                    }
+                }
             }
         }
 
@@ -33,6 +91,26 @@ impl<'de> Deserialize<'de> for Portfolio {
     }
 }
 
+impl PortfolioItem {
+    fn from_raw(symbol: String, category: String, holding: RawHolding) -> Self {
+        let (quantity, avg_cost, cost_currency) = match holding {
+            RawHolding::Quantity(quantity) => (quantity, None, None),
+            RawHolding::Detailed(d) => (
+                d.quantity,
+                d.avg_cost,
+                d.cost_currency.map(|c| c.to_uppercase()),
+            ),
+        };
+        PortfolioItem {
+            symbol,
+            category,
+            quantity,
+            avg_cost,
+            cost_currency,
+        }
+    }
+}
+
 impl Portfolio {
     /// Borrowing iterator over the holdings in storage order.
     pub fn iter(&self) -> std::slice::Iter<'_, PortfolioItem> {
@@ -45,9 +123,9 @@ impl Portfolio {
     /// ```
     /// use price::types::{Portfolio, PortfolioItem};
     /// let p = Portfolio(vec![
-    ///     PortfolioItem { symbol: "AAPL".into(), category: "US-Stock".into(), quantity: 1.0 },
-    ///     PortfolioItem { symbol: "VOO".into(),  category: "US-Stock".into(), quantity: 2.0 },
-    ///     PortfolioItem { symbol: "BTC".into(),  category: "Crypto".into(),   quantity: 3.0 },
+    ///     PortfolioItem { symbol: "AAPL".into(), category: "US-Stock".into(), quantity: 1.into(), ..Default::default() },
+    ///     PortfolioItem { symbol: "VOO".into(),  category: "US-Stock".into(), quantity: 2.into(), ..Default::default() },
+    ///     PortfolioItem { symbol: "BTC".into(),  category: "Crypto".into(),   quantity: 3.into(), ..Default::default() },
     /// ]);
     /// let groups = p.group_by_category();
     /// assert_eq!(groups["US-Stock"].len(), 2);
@@ -68,7 +146,7 @@ impl Portfolio {
     /// ```
     /// use price::types::{Portfolio, PortfolioItem};
     /// let p = Portfolio(vec![
-    ///     PortfolioItem { symbol: "AAPL".into(), category: "US-Stock".into(), quantity: 1.0 },
+    ///     PortfolioItem { symbol: "AAPL".into(), category: "US-Stock".into(), quantity: 1.into(), ..Default::default() },
     /// ]);
     /// assert!(p.get("US-Stock").is_some());
     /// assert!(p.get("Crypto").is_none());
@@ -79,28 +157,135 @@ impl Portfolio {
 }
 
 /// A single portfolio holding.
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Deserialize, Clone, Default)]
 pub struct PortfolioItem {
     /// Asset symbol (e.g. `AAPL`, `2330`, `BTC`, `TWD`).
     pub symbol: String,
-    /// Asset category (e.g. `US-Stock`, `TW-Stock`, `Crypto`, `Forex`).
+    /// Asset category (e.g. `US-Stock`, `TW-Stock`, `Crypto`, `Commodity`,
+    /// `Forex`).
     pub category: String,
-    /// Number of units held.
-    pub quantity: f64,
+    /// Number of units held, exactly as written (no binary rounding).
+    pub quantity: Decimal,
+    /// Average cost per unit, if known. Enables unrealized P&L for the holding.
+    pub avg_cost: Option<Decimal>,
+    /// Currency `avg_cost` is expressed in (upper-case). `None` means the
+    /// currency the holding is quoted in (TWD for Taiwan equities, the currency
+    /// itself for Forex cash, USD otherwise).
+    pub cost_currency: Option<String>,
 }
 
 /// A point-in-time snapshot of the portfolio, used to build historical
 /// price and allocation series. Persisted as one JSON line per snapshot.
+///
+/// Values are decimals written as JSON strings (`"1234.5"`), so every digit
+/// loads back exactly. Lines recorded when values were `f64` numbers still
+/// load, at the precision they were written with.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortfolioSnapshot {
     /// Unix epoch seconds.
     pub timestamp: i64,
     /// Total portfolio value expressed in USD.
-    pub total_value_usd: f64,
+    #[serde(with = "crate::serde_util::decimal_str")]
+    pub total_value_usd: Decimal,
     /// Category -> USD value. Allocation ratio = value / total_value_usd.
-    pub category_values: HashMap<String, f64>,
+    #[serde(with = "crate::serde_util::decimal_str_map")]
+    pub category_values: HashMap<String, Decimal>,
     /// Per-symbol price at this point in time (the historical price).
-    pub prices: HashMap<String, f64>,
+    #[serde(with = "crate::serde_util::decimal_str_map")]
+    pub prices: HashMap<String, Decimal>,
+}
+
+/// One live price together with when it was written and by which source, so
+/// a stalled feed can be told apart from a live one.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PriceEntry {
+    /// The price (or, for a `USD/{ccy}` key, units of ccy per USD).
+    pub price: f64,
+    /// Unix epoch seconds of the last update.
+    pub updated_at: i64,
+    /// Where the price came from (`pyth`, `twse`, `rest`, ...).
+    pub source: String,
+    /// Confidence interval (±, same units as `price`) reported by the feed;
+    /// only Pyth provides one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conf: Option<f64>,
+    /// Unix epoch seconds at which the feed published the price, which can lag
+    /// `updated_at` (e.g. a US equity feed while the market is closed); only
+    /// Pyth provides one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_time: Option<i64>,
+}
+
+/// The live price map shared by the streams, pollers and display: price-map key
+/// (the symbol, or `USD/{ccy}` for a forex rate) -> latest [`PriceEntry`].
+///
+/// Valuation works on plain prices; use [`PriceMap::values`] to get them.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PriceMap(pub HashMap<String, PriceEntry>);
+
+impl PriceMap {
+    /// Build a map where every price shares one `source` and `updated_at`.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use price::types::PriceMap;
+    /// let map = PriceMap::from_values(HashMap::from([("AAPL".to_string(), 1.0)]), "test", 7);
+    /// assert_eq!(map.get("AAPL").unwrap().updated_at, 7);
+    /// ```
+    pub fn from_values(values: HashMap<String, f64>, source: &str, updated_at: i64) -> Self {
+        let mut map = PriceMap::default();
+        for (key, price) in values {
+            map.insert(key, price, source, updated_at);
+        }
+        map
+    }
+
+    /// Set `key` to `price`, stamped with `source` and `updated_at` (epoch secs).
+    pub fn insert(&mut self, key: String, price: f64, source: &str, updated_at: i64) {
+        self.0.insert(
+            key,
+            PriceEntry {
+                price,
+                updated_at,
+                source: source.to_string(),
+                conf: None,
+                publish_time: None,
+            },
+        );
+    }
+
+    /// Drop `key` (e.g. a holding that was removed), returning its entry.
+    pub fn remove(&mut self, key: &str) -> Option<PriceEntry> {
+        self.0.remove(key)
+    }
+
+    /// The entry for `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&PriceEntry> {
+        self.0.get(key)
+    }
+
+    /// Seconds since `key` was updated, as of `now`, or `None` if absent.
+    pub fn age(&self, key: &str, now: i64) -> Option<i64> {
+        self.get(key).map(|entry| (now - entry.updated_at).max(0))
+    }
+
+    /// Number of keys with a price.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether no price has arrived yet.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The bare `key -> price` map the valuation functions take.
+    pub fn values(&self) -> HashMap<String, f64> {
+        self.0
+            .iter()
+            .map(|(key, entry)| (key.clone(), entry.price))
+            .collect()
+    }
 }
 
 /// API credentials, deserialized from a flat TOML table of `name -> key`.
@@ -0,0 +1,217 @@
+//! Currency-aware valuation: the currency each holding is quoted in, the
+//! price-map keys its value depends on, and conversion of any amount into USD
+//! or into the display currency.
+//!
+//! USD is the base currency. Every other currency converts through the live
+//! `USD/{ccy}` rate (units of `ccy` per USD). A category declares its quote
+//! currency once, in [`quote_currency`]; valuation, completeness checks, forex
+//! subscriptions, back-fill and display all derive from it, so a new market
+//! needs no other change here.
+//!
+//! Live quotes arrive as `f64`, but every amount is computed as a
+//! [`Decimal`]: a quote is read into a decimal once (`0.1` stays `0.1`) and
+//! quantities, FX divisions and totals never pick up binary rounding.
+
+use std::collections::HashMap;
+
+use rust_decimal::Decimal;
+use rust_decimal::prelude::FromPrimitive;
+
+use crate::exchange;
+use crate::types::PortfolioItem;
+
+/// The currency every value is ultimately expressed in.
+pub const BASE_CURRENCY: &str = "USD";
+
+/// The quote currency of each non-USD category other than Forex (whose
+/// holdings are the currency itself) and exchange-listed equities (see
+/// [`crate::exchange`]). Anything not listed is priced in USD.
+const CATEGORY_CURRENCIES: &[(&str, &str)] =
+    &[("TW-Stock", "TWD"), ("TW-ETF", "TWD"), ("TW-OTC", "TWD")];
+
+/// The currency a `(symbol, category)` holding is quoted in: the currency
+/// itself for Forex cash, the exchange's currency for `Stock.{code}` listings,
+/// the category's declared currency, or USD.
+pub fn quote_currency(symbol: &str, category: &str) -> String {
+    if category == "Forex" {
+        return symbol.to_uppercase();
+    }
+    if let Some(exchange) = exchange::for_category(category) {
+        return exchange.currency.to_string();
+    }
+    CATEGORY_CURRENCIES
+        .iter()
+        .find(|(c, _)| *c == category)
+        .map_or(BASE_CURRENCY, |(_, currency)| currency)
+        .to_string()
+}
+
+/// The currency a holding's `avg_cost` is expressed in: the explicit
+/// `cost_currency` if set, otherwise the holding's quote currency.
+pub fn cost_currency(item: &PortfolioItem) -> String {
+    item.cost_currency
+        .clone()
+        .unwrap_or_else(|| quote_currency(&item.symbol, &item.category))
+}
+
+/// The price-map key of the `USD/{currency}` rate, or `None` for USD itself.
+pub fn rate_key(currency: &str) -> Option<String> {
+    let currency = currency.to_uppercase();
+    (currency != BASE_CURRENCY).then(|| format!("USD/{}", currency))
+}
+
+/// The quote under `key` in `map` as a decimal, or `None` if it is missing or
+/// not a finite number.
+pub fn price(map: &HashMap<String, f64>, key: &str) -> Option<Decimal> {
+    map.get(key).copied().and_then(Decimal::from_f64)
+}
+
+/// How many units of `currency` one USD buys according to `map`, or `None` if
+/// the `USD/{currency}` rate is missing or zero. USD itself is always 1.
+pub fn usd_rate(map: &HashMap<String, f64>, currency: &str) -> Option<Decimal> {
+    match rate_key(currency) {
+        None => Some(Decimal::ONE),
+        Some(key) => price(map, &key).filter(|rate| !rate.is_zero()),
+    }
+}
+
+/// `amount` of `currency` in USD, or `None` if the rate is missing.
+pub fn to_usd(amount: Decimal, currency: &str, map: &HashMap<String, f64>) -> Option<Decimal> {
+    amount.checked_div(usd_rate(map, currency)?)
+}
+
+/// `usd` converted into `currency`, or `None` if the rate is missing.
+pub fn from_usd(usd: Decimal, currency: &str, map: &HashMap<String, f64>) -> Option<Decimal> {
+    usd.checked_mul(usd_rate(map, currency)?)
+}
+
+/// How many units of `quote` one unit of `base` buys, crossed through the two
+/// USD rates, or `None` if either is missing.
+pub fn cross_rate(map: &HashMap<String, f64>, base: &str, quote: &str) -> Option<Decimal> {
+    usd_rate(map, quote)?.checked_div(usd_rate(map, base)?)
+}
+
+/// The price-map key of a holding's own quote, or `None` for Forex cash, which
+/// needs no quote (one unit is worth one unit of its currency).
+pub fn price_key(item: &PortfolioItem) -> Option<&str> {
+    (item.category != "Forex").then_some(item.symbol.as_str())
+}
+
+/// The price-map keys one holding's USD value depends on: its quote and the
+/// rate of its quote currency.
+pub fn item_price_keys(item: &PortfolioItem) -> Vec<String> {
+    price_key(item)
+        .map(str::to_string)
+        .into_iter()
+        .chain(rate_key(&quote_currency(&item.symbol, &item.category)))
+        .collect()
+}
+
+/// The value of a holding in its quote currency, or `None` if its price is
+/// missing from `map`.
+pub fn local_value(item: &PortfolioItem, map: &HashMap<String, f64>) -> Option<Decimal> {
+    match price_key(item) {
+        Some(key) => price(map, key)?.checked_mul(item.quantity),
+        None => Some(item.quantity),
+    }
+}
+
+/// USD value of a single holding, or `None` if a price or rate it depends on
+/// is missing from `map`.
+pub fn item_usd_value(item: &PortfolioItem, map: &HashMap<String, f64>) -> Option<Decimal> {
+    let currency = quote_currency(&item.symbol, &item.category);
+    to_usd(local_value(item, map)?, &currency, map)
+}
+
+/// The currencies whose rates a holding needs: its quote currency and, when it
+/// has a cost basis, the currency of that cost. May include USD.
+pub fn item_currencies(item: &PortfolioItem) -> Vec<String> {
+    let mut currencies = vec![quote_currency(&item.symbol, &item.category)];
+    if item.avg_cost.is_some()
+        && let Some(currency) = &item.cost_currency
+    {
+        currencies.push(currency.to_uppercase());
+    }
+    currencies
+}
+
+/// The prefix amounts in `currency` are displayed with: `$` for USD, `NT$` for
+/// TWD, otherwise the currency code and a space.
+pub fn currency_unit(currency: &str) -> String {
+    match currency.to_uppercase().as_str() {
+        "USD" => "$".to_string(),
+        "TWD" => "NT$".to_string(),
+        other => format!("{} ", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(symbol: &str, category: &str, quantity: i64) -> PortfolioItem {
+        PortfolioItem {
+            symbol: symbol.to_string(),
+            category: category.to_string(),
+            quantity: quantity.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_quote_currency_per_category() {
+        assert_eq!(quote_currency("2330", "TW-Stock"), "TWD");
+        assert_eq!(quote_currency("6488", "TW-OTC"), "TWD");
+        assert_eq!(quote_currency("7203", "Stock.JP"), "JPY");
+        assert_eq!(quote_currency("eur", "Forex"), "EUR");
+        assert_eq!(quote_currency("AAPL", "US-Stock"), "USD");
+        assert_eq!(quote_currency("XAU", "Commodity"), "USD");
+    }
+
+    #[test]
+    fn test_every_holding_converts_through_its_quote_currency() {
+        let map = HashMap::from([
+            ("2330".to_string(), 600.0),
+            ("AAPL".to_string(), 200.0),
+            ("USD/TWD".to_string(), 30.0),
+        ]);
+        let tsmc = item("2330", "TW-Stock", 10);
+        let aapl = item("AAPL", "US-Stock", 2);
+        let twd = item("TWD", "Forex", 3_000);
+
+        assert_eq!(item_price_keys(&tsmc), vec!["2330", "USD/TWD"]);
+        assert_eq!(item_price_keys(&aapl), vec!["AAPL"]);
+        assert_eq!(item_price_keys(&twd), vec!["USD/TWD"]);
+        assert!(item_price_keys(&item("USD", "Forex", 1)).is_empty());
+
+        assert_eq!(item_usd_value(&tsmc, &map), Some(200.into()));
+        assert_eq!(item_usd_value(&aapl, &map), Some(400.into()));
+        assert_eq!(item_usd_value(&twd, &map), Some(100.into()));
+        assert_eq!(from_usd(100.into(), "TWD", &map), Some(3_000.into()));
+        assert!(to_usd(Decimal::ONE, "JPY", &map).is_none());
+
+        // 30 TWD per USD and 1.25 USD per EUR: 37.5 TWD per EUR.
+        let map = HashMap::from([("USD/TWD".to_string(), 30.0), ("USD/EUR".to_string(), 0.8)]);
+        assert_eq!(
+            cross_rate(&map, "EUR", "TWD"),
+            Some("37.5".parse().unwrap())
+        );
+        assert_eq!(cross_rate(&map, "USD", "TWD"), Some(30.into()));
+        assert!(cross_rate(&map, "JPY", "TWD").is_none());
+    }
+
+    #[test]
+    fn test_fractional_quantities_and_round_trips_are_exact() {
+        let map = HashMap::from([("BTC".to_string(), 0.1), ("USD/TWD".to_string(), 3.0)]);
+        let btc = PortfolioItem {
+            quantity: "0.3".parse().unwrap(),
+            ..item("BTC", "Crypto", 0)
+        };
+        // 0.3 x 0.1 is 0.03 exactly, not 0.030000000000000006.
+        assert_eq!(item_usd_value(&btc, &map), Some("0.03".parse().unwrap()));
+
+        let usd: Decimal = "0.1".parse().unwrap();
+        let twd = from_usd(usd, "TWD", &map).unwrap();
+        assert_eq!(to_usd(twd, "TWD", &map), Some(usd));
+    }
+}